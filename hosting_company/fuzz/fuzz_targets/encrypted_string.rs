@@ -0,0 +1,35 @@
+//! Feeds arbitrary `encryptedString` parameters to the decrypt path, which must answer every one
+//! of them with a plaintext or a [`DecryptError`], never a panic.
+//!
+//! Run with `cargo +nightly fuzz run encrypted_string` from the `hosting_company` directory.
+
+#![no_main]
+
+use hosting_company::crypto::{self, DecryptError, Padding};
+use libfuzzer_sys::fuzz_target;
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use std::sync::OnceLock;
+
+/// Key generation is slow, so every run decrypts with the same key.
+fn private_key() -> &'static RsaPrivateKey {
+    static KEY: OnceLock<RsaPrivateKey> = OnceLock::new();
+    KEY.get_or_init(|| RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap())
+}
+
+fuzz_target!(|encrypted_string: &str| {
+    if encrypted_string.len() > crypto::MAX_ENCRYPTED_STRING_LEN {
+        return;
+    }
+    let private_key = private_key();
+    for padding in [Padding::Pkcs1v15, Padding::OaepSha256] {
+        match crypto::decrypt(private_key, padding, encrypted_string) {
+            Ok(_) => {}
+            Err(DecryptError::Length { len, key_len }) => {
+                assert_eq!(key_len, private_key.size());
+                assert!(len != key_len && len < key_len + 12 + 16);
+            }
+            Err(_) => {}
+        }
+    }
+});
@@ -0,0 +1,142 @@
+nav-home = Startseite
+
+## Verify consent screen
+
+consent-title = { $domain } möchte bestätigen, dass dir „{ $podcast }“ gehört
+consent-app = Anfragende App
+consent-app-claimed = Name und Symbol, wie { $domain } sich selbst beschreibt. Geprüft wird nur die Domain.
+consent-transfer-locked = { $domain } möchte diesen Feed zu einem anderen Anbieter umziehen, aber du hast ihn gegen Umzüge gesperrt. Entsperre ihn zuerst in deiner Übersicht, wenn du das erlauben möchtest.
+consent-feed = Podcast-Feed
+consent-key-fingerprint = Fingerabdruck des Schlüssels
+consent-key-fingerprint-details = SHA-256 des Schlüssels im Feed. Die App kann prüfen, ob sie ihre Anfrage mit demselben Schlüssel verschlüsselt hat.
+consent-claim = Was die App erfährt
+consent-claim-details = Dass du dich als Eigentümer von { $feed } anmelden kannst. Die App sieht weder deine E-Mail-Adresse noch dein Passwort und erhält keinen Zugriff auf dein Konto.
+consent-login = Melde dich als Eigentümer des Podcasts an, um zuzustimmen.
+consent-approve = Zustimmen
+consent-deny = Ablehnen
+consent-magic-link-prompt = Kein Passwort? Lass dir stattdessen einen Anmeldelink per E-Mail schicken.
+consent-magic-link = Anmeldelink per E-Mail senden
+consent-forgot-password = Passwort vergessen?
+magic-link-sent-title = Sieh in dein Postfach
+magic-link-sent = Falls { $email } dem Eigentümer von „{ $podcast }“ gehört, ist ein Anmeldelink unterwegs. Er funktioniert einmal und läuft in { $minutes } Minuten ab.
+magic-link-subject = Anmelden, um „{ $podcast }“ zu bestätigen
+magic-link-body =
+    Folge diesem Link, damit { $domain } bestätigen kann, dass dir „{ $podcast }“ gehört:
+    { $link }
+    Er funktioniert einmal und läuft in { $minutes } Minuten ab. Falls du ihn nicht angefordert hast, kannst du diese E-Mail ignorieren.
+verify-flagged-warning = Achtung: Bestätigungsanfragen von { $domain } wurden bereits als verdächtig gemeldet.
+verify-email = E-Mail
+verify-password = Passwort ({ $hint })
+verify-password-hint = Hinweis
+verify-report-prompt = Du hast diese Anfrage nicht gestellt? Melde dich oben an, um sie zu melden, damit wir uns { $domain } ansehen können.
+verify-report = Anfrage melden
+verify-ownership = Eigentümerschaft von „{ $podcast }“ bestätigen
+verify-ownership-generic = Eigentümerschaft bestätigen
+verify-approved = Du hast { $domain } bestätigt, dass dir dieser Podcast gehört.
+redirect-countdown = Weiterleitung zu { $url } in { $seconds } { $count ->
+        [one] Sekunde
+       *[other] Sekunden
+    }...
+
+## Batch verification
+
+batch-title = { $domain } möchte bestätigen, dass dir { $count ->
+        [one] ein Podcast gehört
+       *[other] { $count } Podcasts gehören
+    }
+batch-login = Melde dich als Eigentümer an, um auszuwählen, welche davon bestätigt werden.
+batch-password = Passwort
+batch-log-in = Anmelden
+batch-choose = Wähle die Podcasts aus, für die { $domain } bestätigen darf, dass sie dir gehören. Die anderen werden abgelehnt.
+batch-approve = Auswahl bestätigen
+
+## Dashboard
+
+dashboard-title = Übersicht für { $podcast }
+dashboard-draft = Dieser Podcast ist ein Entwurf. Der Feed ist nur für dich sichtbar, und Apps können ihn noch nicht verifizieren.
+dashboard-publish = Veröffentlichen
+dashboard-locked = Dieser Feed ist gesperrt: Apps können ihn nicht zu einem anderen Anbieter umziehen.
+dashboard-unlocked = Dieser Feed ist nicht gesperrt, Apps, denen du zustimmst, können ihn also zu einem anderen Anbieter umziehen.
+dashboard-lock = Gegen Umzüge sperren
+dashboard-unlock = Entsperren
+dashboard-password-heading = Passwort
+dashboard-change-password = Passwort ändern
+grants-heading = Bestätigte Apps
+grants-empty = Du hast noch keine App bestätigt.
+grants-granted = Bestätigt
+grants-status = Status
+grants-revoked = Widerrufen { $time }
+grants-notify = App benachrichtigen
+grants-revoke = Widerrufen
+attempts-heading = Verifizierungsversuche
+attempts-intro = Alle Anfragen von Apps, die bestätigen wollten, dass dir dieser Feed gehört, die neuesten zuerst.
+attempts-empty = Bisher hat keine App angefragt, diesen Feed zu verifizieren.
+attempts-time = Zeit
+attempts-app = App
+attempts-outcome = Ergebnis
+attempts-ip = IP-Adresse
+attempts-unknown-app = unbekannt
+outcome-requested = Um Bestätigung gebeten
+outcome-approved = Bestätigt
+outcome-denied = Abgelehnt
+outcome-sign-in-link-sent = Anmeldelink gesendet
+outcome-reported = Gemeldet
+outcome-invalid-credentials = Falsche E-Mail-Adresse oder falsches Passwort
+outcome-rejected = Zurückgewiesen ({ $reason })
+
+## Passwords
+
+forgot-password-title = Passwort zurücksetzen
+forgot-password-prompt = Gib die E-Mail-Adresse ein, mit der du dich anmeldest, und wir schicken dir einen Link, um ein neues Passwort zu wählen.
+forgot-password-send = Link zum Zurücksetzen senden
+password-reset-sent = Falls { $email } zu einem Podcast-Eigentümer gehört, ist ein Link zum Zurücksetzen unterwegs. Er funktioniert einmal und läuft in { $minutes } Minuten ab.
+password-reset-subject = Dein Passwort bei Hosting Company zurücksetzen
+password-reset-body =
+    Folge diesem Link, um ein neues Passwort zu wählen:
+    { $link }
+    Er funktioniert einmal und läuft in { $minutes } Minuten ab. Falls du ihn nicht angefordert hast, kannst du diese E-Mail ignorieren; dein Passwort bleibt, wie es ist.
+reset-password-title = Neues Passwort wählen
+reset-password-save = Passwort speichern
+current-password = Aktuelles Passwort
+new-password = Neues Passwort (mindestens { $min } Zeichen)
+new-password-confirmation = Neues Passwort wiederholen
+password-changed-title = Passwort geändert
+password-changed = Dein Passwort wurde geändert. Melde dich ab jetzt mit dem neuen an.
+
+## Abuse reports
+
+reported-title = Anfrage gemeldet
+reported-thanks = Danke. Die Bestätigungsanfrage von { $domain } wurde gemeldet und wird geprüft.
+reported-blocked = Weitere Bestätigungsanfragen von { $domain } sind bis zum Abschluss der Prüfung gesperrt.
+
+## Errors
+
+error = Fehler
+error-title = Fehler: { $status }
+error-podcast-not-found = Kein Podcast mit dem Slug { $slug } gefunden.
+error-missing-param = Der Parameter { $name } ist erforderlich.
+error-invalid-param = Ungültiger Wert für { $name }.
+error-invalid-credentials = E-Mail-Adresse oder Passwort ist falsch.
+error-link-expired = Dieser Anmeldelink ist abgelaufen oder wurde bereits verwendet.
+error-csrf-rejected = Dieses Formular ist abgelaufen oder wurde von einer anderen Website gesendet. Lade die Seite neu und versuche es noch einmal.
+error-reset-link-expired = Dieser Link zum Zurücksetzen ist abgelaufen oder wurde bereits verwendet. Fordere einen neuen an.
+error-password-rejected = Passwörter brauchen mindestens { $min } Zeichen, und beide Eingaben müssen übereinstimmen.
+error-crypto-failure = Mit unseren Schlüsseln ist etwas schiefgelaufen. Bitte versuche es später erneut.
+error-access-denied = Zugriff verweigert.
+error-feed-locked = Der Eigentümer hat diesen Feed gegen Umzüge zu einem anderen Anbieter gesperrt.
+error-too-many-requests = Zu viele Anfragen. Bitte versuche es in { $seconds ->
+        [one] einer Sekunde
+       *[other] { $seconds } Sekunden
+    } erneut.
+error-login-locked = Zu viele fehlgeschlagene Anmeldungen. Bitte versuche es in { $minutes ->
+        [one] einer Minute
+       *[other] { $minutes } Minuten
+    } erneut oder fordere stattdessen einen Anmeldelink an.
+error-storage = Beim Speichern deiner Änderungen ist etwas schiefgelaufen. Bitte versuche es später erneut.
+error-import-failed = Der Feed konnte nicht importiert werden: { $details }
+error-not-found = Seite nicht gefunden.
+too-many-requests-title = Zu viele Anfragen
+too-many-requests-message = Wir haben zu viele Bestätigungsanfragen von dir oder für diesen Podcast erhalten. Bitte versuche es in { $seconds ->
+        [one] einer Sekunde
+       *[other] { $seconds } Sekunden
+    } erneut.
@@ -0,0 +1,142 @@
+nav-home = Home
+
+## Verify consent screen
+
+consent-title = { $domain } wants to verify that you own “{ $podcast }”
+consent-app = Requesting app
+consent-app-claimed = Name and icon as { $domain } describes itself. Only the domain is checked.
+consent-transfer-locked = { $domain } wants to move this feed to another host, but you have locked it against transfers. Unlock it from your dashboard first if you want to allow this.
+consent-feed = Podcast feed
+consent-key-fingerprint = Key fingerprint
+consent-key-fingerprint-details = SHA-256 of the key in the feed. The app can check that it encrypted its request to the same key.
+consent-claim = What the app will learn
+consent-claim-details = That you can log in as the owner of { $feed }. The app won't see your email address or password and gets no access to your account.
+consent-login = Log in as the podcast's owner to approve.
+consent-approve = Approve
+consent-deny = Deny
+consent-magic-link-prompt = No password? Get a sign-in link by email instead.
+consent-magic-link = Email me a sign-in link
+consent-forgot-password = Forgot your password?
+magic-link-sent-title = Check your email
+magic-link-sent = If { $email } belongs to the owner of “{ $podcast }”, a sign-in link is on its way. It works once and expires in { $minutes } minutes.
+magic-link-subject = Sign in to verify “{ $podcast }”
+magic-link-body =
+    Follow this link to let { $domain } verify that you own “{ $podcast }”:
+    { $link }
+    It works once and expires in { $minutes } minutes. If you didn't ask for it, you can ignore this email.
+verify-flagged-warning = Warning: verification requests from { $domain } have previously been reported as suspicious.
+verify-email = Email
+verify-password = Password ({ $hint })
+verify-password-hint = hint
+verify-report-prompt = Didn't start this request? Log in above to report it, so we can look into { $domain }.
+verify-report = Report this request
+verify-ownership = Verify ownership of “{ $podcast }”
+verify-ownership-generic = Verify ownership
+verify-approved = You confirmed to { $domain } that you own this podcast.
+redirect-countdown = Redirecting to { $url } in { $seconds } { $count ->
+        [one] second
+       *[other] seconds
+    }...
+
+## Batch verification
+
+batch-title = { $domain } wants to verify that you own { $count ->
+        [one] a podcast
+       *[other] { $count } podcasts
+    }
+batch-login = Log in as their owner to choose which of them to verify.
+batch-password = Password
+batch-log-in = Log in
+batch-choose = Choose the podcasts { $domain } may verify that you own. The others are denied.
+batch-approve = Approve selected
+
+## Dashboard
+
+dashboard-title = Dashboard for { $podcast }
+dashboard-draft = This podcast is a draft. Its feed is only shown to you, and apps can't verify it yet.
+dashboard-publish = Publish
+dashboard-locked = This feed is locked: apps can't move it to another host.
+dashboard-unlocked = This feed isn't locked, so apps you approve can move it to another host.
+dashboard-lock = Lock against transfers
+dashboard-unlock = Unlock
+dashboard-password-heading = Password
+dashboard-change-password = Change password
+grants-heading = Approved apps
+grants-empty = You haven't approved any app yet.
+grants-granted = Approved
+grants-status = Status
+grants-revoked = Revoked { $time }
+grants-notify = Tell the app
+grants-revoke = Revoke
+attempts-heading = Verification attempts
+attempts-intro = Every request an app has made to verify that you own this feed, most recent first.
+attempts-empty = No app has asked to verify this feed yet.
+attempts-time = Time
+attempts-app = App
+attempts-outcome = Outcome
+attempts-ip = IP address
+attempts-unknown-app = unknown
+outcome-requested = Asked for approval
+outcome-approved = Approved
+outcome-denied = Denied
+outcome-sign-in-link-sent = Sign-in link sent
+outcome-reported = Reported
+outcome-invalid-credentials = Wrong email or password
+outcome-rejected = Rejected ({ $reason })
+
+## Passwords
+
+forgot-password-title = Reset your password
+forgot-password-prompt = Enter the email address you sign in with, and we'll send you a link to choose a new password.
+forgot-password-send = Send reset link
+password-reset-sent = If { $email } belongs to a podcast owner, a reset link is on its way. It works once and expires in { $minutes } minutes.
+password-reset-subject = Reset your Hosting Company password
+password-reset-body =
+    Follow this link to choose a new password:
+    { $link }
+    It works once and expires in { $minutes } minutes. If you didn't ask for it, you can ignore this email and your password stays as it is.
+reset-password-title = Choose a new password
+reset-password-save = Save password
+current-password = Current password
+new-password = New password (at least { $min } characters)
+new-password-confirmation = New password again
+password-changed-title = Password changed
+password-changed = Your password has been changed. Sign in with the new one from now on.
+
+## Abuse reports
+
+reported-title = Request reported
+reported-thanks = Thank you. The verification request from { $domain } has been reported and will be reviewed.
+reported-blocked = Further verification requests from { $domain } are blocked until the review is complete.
+
+## Errors
+
+error = Error
+error-title = Error: { $status }
+error-podcast-not-found = Podcast with slug { $slug } not found.
+error-missing-param = Parameter { $name } is required.
+error-invalid-param = Invalid { $name }.
+error-invalid-credentials = Incorrect email or password.
+error-link-expired = This sign-in link has expired or has already been used.
+error-csrf-rejected = This form has expired or was sent from another site. Reload the page and try again.
+error-reset-link-expired = This reset link has expired or has already been used. Ask for a new one.
+error-password-rejected = Passwords need at least { $min } characters, and both entries must match.
+error-crypto-failure = Something went wrong with our keys. Please try again later.
+error-access-denied = Access denied.
+error-feed-locked = The owner has locked this feed against transfers to another host.
+error-too-many-requests = Too many requests. Please try again in { $seconds ->
+        [one] one second
+       *[other] { $seconds } seconds
+    }.
+error-login-locked = Too many failed logins. Please try again in { $minutes ->
+        [one] one minute
+       *[other] { $minutes } minutes
+    }, or ask for a sign-in link instead.
+error-storage = Something went wrong saving your changes. Please try again later.
+error-import-failed = The feed could not be imported: { $details }
+error-not-found = Page not found.
+too-many-requests-title = Too many requests
+too-many-requests-message = We've received too many verification requests from you or for this podcast. Please try again in { $seconds ->
+        [one] one second
+       *[other] { $seconds } seconds
+    }.
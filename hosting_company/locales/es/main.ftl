@@ -0,0 +1,142 @@
+nav-home = Inicio
+
+## Verify consent screen
+
+consent-title = { $domain } quiere verificar que eres propietario de «{ $podcast }»
+consent-app = Aplicación solicitante
+consent-app-claimed = Nombre e icono tal como se describe { $domain }. Solo se comprueba el dominio.
+consent-transfer-locked = { $domain } quiere trasladar este feed a otro proveedor, pero lo has bloqueado contra traslados. Desbloquéalo primero desde tu panel si quieres permitirlo.
+consent-feed = Feed del podcast
+consent-key-fingerprint = Huella de la clave
+consent-key-fingerprint-details = SHA-256 de la clave del feed. La aplicación puede comprobar que cifró su solicitud con la misma clave.
+consent-claim = Qué sabrá la aplicación
+consent-claim-details = Que puedes iniciar sesión como propietario de { $feed }. La aplicación no verá tu correo electrónico ni tu contraseña y no obtiene acceso a tu cuenta.
+consent-login = Inicia sesión como propietario del podcast para aprobar.
+consent-approve = Aprobar
+consent-deny = Denegar
+consent-magic-link-prompt = ¿Sin contraseña? Recibe en su lugar un enlace de acceso por correo electrónico.
+consent-magic-link = Enviarme un enlace de acceso
+consent-forgot-password = ¿Olvidaste tu contraseña?
+magic-link-sent-title = Revisa tu correo
+magic-link-sent = Si { $email } pertenece al propietario de «{ $podcast }», te hemos enviado un enlace de acceso. Funciona una sola vez y caduca en { $minutes } minutos.
+magic-link-subject = Inicia sesión para verificar «{ $podcast }»
+magic-link-body =
+    Sigue este enlace para que { $domain } pueda verificar que eres propietario de «{ $podcast }»:
+    { $link }
+    Funciona una sola vez y caduca en { $minutes } minutos. Si no lo solicitaste, puedes ignorar este correo.
+verify-flagged-warning = Atención: las solicitudes de verificación de { $domain } ya han sido denunciadas como sospechosas.
+verify-email = Correo electrónico
+verify-password = Contraseña ({ $hint })
+verify-password-hint = pista
+verify-report-prompt = ¿No iniciaste esta solicitud? Inicia sesión arriba para denunciarla y así podremos revisar { $domain }.
+verify-report = Denunciar esta solicitud
+verify-ownership = Verificar la propiedad de «{ $podcast }»
+verify-ownership-generic = Verificar la propiedad
+verify-approved = Has confirmado a { $domain } que este podcast es tuyo.
+redirect-countdown = Redirigiendo a { $url } en { $seconds } { $count ->
+        [one] segundo
+       *[other] segundos
+    }...
+
+## Batch verification
+
+batch-title = { $domain } quiere verificar que eres propietario de { $count ->
+        [one] un podcast
+       *[other] { $count } podcasts
+    }
+batch-login = Inicia sesión como propietario para elegir cuáles verificar.
+batch-password = Contraseña
+batch-log-in = Iniciar sesión
+batch-choose = Elige los podcasts de los que { $domain } puede verificar que eres propietario. Los demás se deniegan.
+batch-approve = Aprobar seleccionados
+
+## Dashboard
+
+dashboard-title = Panel de { $podcast }
+dashboard-draft = Este podcast es un borrador. Su feed solo es visible para ti y las apps aún no pueden verificarlo.
+dashboard-publish = Publicar
+dashboard-locked = Este feed está bloqueado: las apps no pueden trasladarlo a otro proveedor.
+dashboard-unlocked = Este feed no está bloqueado, así que las apps que apruebes pueden trasladarlo a otro proveedor.
+dashboard-lock = Bloquear contra traslados
+dashboard-unlock = Desbloquear
+dashboard-password-heading = Contraseña
+dashboard-change-password = Cambiar contraseña
+grants-heading = Apps aprobadas
+grants-empty = Todavía no has aprobado ninguna app.
+grants-granted = Aprobada
+grants-status = Estado
+grants-revoked = Revocada { $time }
+grants-notify = Avisar a la app
+grants-revoke = Revocar
+attempts-heading = Intentos de verificación
+attempts-intro = Todas las solicitudes de apps para verificar que este feed es tuyo, de la más reciente a la más antigua.
+attempts-empty = Ninguna app ha pedido verificar este feed todavía.
+attempts-time = Hora
+attempts-app = App
+attempts-outcome = Resultado
+attempts-ip = Dirección IP
+attempts-unknown-app = desconocida
+outcome-requested = Pidió aprobación
+outcome-approved = Aprobado
+outcome-denied = Denegado
+outcome-sign-in-link-sent = Enlace de inicio de sesión enviado
+outcome-reported = Denunciada
+outcome-invalid-credentials = Correo o contraseña incorrectos
+outcome-rejected = Rechazado ({ $reason })
+
+## Passwords
+
+forgot-password-title = Restablecer la contraseña
+forgot-password-prompt = Introduce el correo electrónico con el que inicias sesión y te enviaremos un enlace para elegir una contraseña nueva.
+forgot-password-send = Enviar enlace de restablecimiento
+password-reset-sent = Si { $email } pertenece a un propietario de podcast, le hemos enviado un enlace de restablecimiento. Funciona una vez y caduca en { $minutes } minutos.
+password-reset-subject = Restablece tu contraseña de Hosting Company
+password-reset-body =
+    Sigue este enlace para elegir una contraseña nueva:
+    { $link }
+    Funciona una vez y caduca en { $minutes } minutos. Si no lo has solicitado, puedes ignorar este correo; tu contraseña no cambiará.
+reset-password-title = Elige una contraseña nueva
+reset-password-save = Guardar contraseña
+current-password = Contraseña actual
+new-password = Contraseña nueva (al menos { $min } caracteres)
+new-password-confirmation = Repite la contraseña nueva
+password-changed-title = Contraseña cambiada
+password-changed = Tu contraseña se ha cambiado. A partir de ahora, inicia sesión con la nueva.
+
+## Abuse reports
+
+reported-title = Solicitud denunciada
+reported-thanks = Gracias. La solicitud de verificación de { $domain } ha sido denunciada y será revisada.
+reported-blocked = Las próximas solicitudes de verificación de { $domain } quedan bloqueadas hasta que termine la revisión.
+
+## Errors
+
+error = Error
+error-title = Error: { $status }
+error-podcast-not-found = No se encontró ningún podcast con el slug { $slug }.
+error-missing-param = El parámetro { $name } es obligatorio.
+error-invalid-param = Valor no válido para { $name }.
+error-invalid-credentials = Correo electrónico o contraseña incorrectos.
+error-link-expired = Este enlace de acceso ha caducado o ya se ha utilizado.
+error-csrf-rejected = Este formulario ha caducado o se ha enviado desde otro sitio. Recarga la página e inténtalo de nuevo.
+error-reset-link-expired = Este enlace de restablecimiento ha caducado o ya se ha utilizado. Solicita uno nuevo.
+error-password-rejected = Las contraseñas necesitan al menos { $min } caracteres y ambas entradas deben coincidir.
+error-crypto-failure = Algo salió mal con nuestras claves. Inténtalo de nuevo más tarde.
+error-access-denied = Acceso denegado.
+error-feed-locked = El propietario ha bloqueado este feed contra traslados a otro proveedor.
+error-too-many-requests = Demasiadas solicitudes. Inténtalo de nuevo en { $seconds ->
+        [one] un segundo
+       *[other] { $seconds } segundos
+    }.
+error-login-locked = Demasiados inicios de sesión fallidos. Inténtalo de nuevo en { $minutes ->
+        [one] un minuto
+       *[other] { $minutes } minutos
+    } o solicita un enlace de inicio de sesión.
+error-storage = Algo salió mal al guardar tus cambios. Inténtalo de nuevo más tarde.
+error-import-failed = No se pudo importar el feed: { $details }
+error-not-found = Página no encontrada.
+too-many-requests-title = Demasiadas solicitudes
+too-many-requests-message = Hemos recibido demasiadas solicitudes de verificación tuyas o para este podcast. Inténtalo de nuevo en { $seconds ->
+        [one] un segundo
+       *[other] { $seconds } segundos
+    }.
@@ -0,0 +1,64 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// A verification request that a podcast owner flagged as suspicious from the consent screen.
+#[derive(Clone, Debug, Serialize)]
+pub struct AbuseReport {
+    pub slug: String,
+    pub domain: String,
+    pub return_url: String,
+    pub encrypted_string: Option<String>,
+    /// Whether `encrypted_string` decrypts with the podcast's keys. Phishing pages often send
+    /// made-up challenges, so reports are taken either way.
+    pub decrypted: bool,
+    /// Seconds since the Unix epoch.
+    pub reported_at: u64,
+}
+
+/// In-memory record of abuse reports and the directory domains they flagged.
+#[derive(Clone, Default)]
+pub struct AbuseReports {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    reports: Vec<AbuseReport>,
+    flagged_domains: HashSet<String>,
+}
+
+impl AbuseReports {
+    /// Records the report and flags its domain. The challenge stays out of the logs, which get
+    /// a digest of it to tell reports apart.
+    pub fn report(&self, report: AbuseReport) {
+        tracing::warn!(
+            slug = report.slug,
+            domain = report.domain,
+            encrypted_string_sha256 = report.encrypted_string.as_deref().map(digest_prefix),
+            decrypted = report.decrypted,
+            "verification request reported as abuse",
+        );
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.flagged_domains.insert(report.domain.clone());
+        inner.reports.push(report);
+    }
+
+    pub fn is_flagged(&self, domain: &str) -> bool {
+        self.inner.lock().unwrap().flagged_domains.contains(domain)
+    }
+
+    pub fn reports(&self) -> Vec<AbuseReport> {
+        self.inner.lock().unwrap().reports.clone()
+    }
+}
+
+/// The first 8 bytes of the SHA-256 digest of `value`, in hex.
+fn digest_prefix(value: &str) -> String {
+    Sha256::digest(value.as_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
@@ -0,0 +1,426 @@
+use crate::blocklist::BlocklistData;
+use crate::callback::RegisteredDomain;
+use crate::error::AppError;
+use crate::{credentials, csrf};
+use crate::{import, slug_to_podcast, slugs, txt, view, AppState, Podcast};
+use axum::extract::{Form, FromRequestParts, Path, Query, State};
+use axum::headers::authorization::{Basic, Bearer};
+use axum::headers::{Authorization, HeaderMapExt};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::{async_trait, Json};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+use url::Url;
+
+/// Proof that the request carries the admin token as the password of HTTP Basic credentials
+/// with the username [`USERNAME`], as the browser UI sends it. The browser keeps sending them, so
+/// the UI's forms carry a CSRF token as well.
+pub struct Admin;
+
+/// Proof that the request carries the admin token as a Bearer token, as API clients send it.
+/// Browsers don't send one on their own, so another site can't make them call the API.
+pub struct ApiAdmin;
+
+/// The username the admin signs in to the browser UI with.
+const USERNAME: &str = "admin";
+
+#[async_trait]
+impl FromRequestParts<AppState> for Admin {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Response> {
+        let token = admin_token(state).map_err(IntoResponse::into_response)?;
+        let authorized = parts
+            .headers
+            .typed_get::<Authorization<Basic>>()
+            .is_some_and(|basic| {
+                let username_matches = credentials::matches(basic.username(), USERNAME);
+                let password_matches =
+                    credentials::matches(basic.password(), token.expose_secret());
+                username_matches & password_matches
+            });
+        if authorized {
+            Ok(Admin)
+        } else {
+            Err(unauthorized("Basic realm=\"admin\""))
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ApiAdmin {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Response> {
+        let token = admin_token(state).map_err(IntoResponse::into_response)?;
+        let authorized = parts
+            .headers
+            .typed_get::<Authorization<Bearer>>()
+            .is_some_and(|bearer| credentials::matches(bearer.token(), token.expose_secret()));
+        if authorized {
+            Ok(ApiAdmin)
+        } else {
+            Err(unauthorized("Bearer realm=\"admin\""))
+        }
+    }
+}
+
+/// The admin token, or a `404 Not Found` as the admin routes are disabled without one.
+fn admin_token(state: &AppState) -> Result<SecretString, StatusCode> {
+    state
+        .config()
+        .admin_token
+        .clone()
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+fn unauthorized(challenge: &'static str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, challenge)],
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct BlockForm {
+    domain: String,
+    #[serde(default)]
+    reason: Option<String>,
+    csrf: String,
+}
+
+#[derive(Deserialize)]
+pub struct ReasonParams {
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+pub async fn page(_: Admin, State(state): State<AppState>) -> Html<String> {
+    view::admin(
+        state.blocklist.snapshot(),
+        state.abuse_reports.reports(),
+        &state.csrf.token(csrf::ADMIN_SCOPE),
+    )
+}
+
+pub async fn block(
+    _: Admin,
+    State(state): State<AppState>,
+    Form(form): Form<BlockForm>,
+) -> Result<Redirect, AppError> {
+    state.csrf.check(csrf::ADMIN_SCOPE, &form.csrf)?;
+    if form.domain.trim().is_empty() {
+        return Err(AppError::MissingParam("domain"));
+    }
+    state
+        .blocklist
+        .block(&form.domain, "admin", non_empty(form.reason))
+        .map_err(AppError::Storage)?;
+    Ok(Redirect::to("/admin"))
+}
+
+pub async fn unblock(
+    _: Admin,
+    State(state): State<AppState>,
+    Form(form): Form<BlockForm>,
+) -> Result<Redirect, AppError> {
+    state.csrf.check(csrf::ADMIN_SCOPE, &form.csrf)?;
+    state
+        .blocklist
+        .unblock(&form.domain, "admin", non_empty(form.reason))
+        .map_err(AppError::Storage)?;
+    Ok(Redirect::to("/admin"))
+}
+
+pub async fn api_list(_: ApiAdmin, State(state): State<AppState>) -> Json<BlocklistData> {
+    Json(state.blocklist.snapshot())
+}
+
+pub async fn api_block(
+    _: ApiAdmin,
+    State(state): State<AppState>,
+    Path(domain): Path<String>,
+    Query(params): Query<ReasonParams>,
+) -> Result<StatusCode, AppError> {
+    let added = state
+        .blocklist
+        .block(&domain, "admin-api", non_empty(params.reason))
+        .map_err(AppError::Storage)?;
+    Ok(if added {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    })
+}
+
+pub async fn api_unblock(
+    _: ApiAdmin,
+    State(state): State<AppState>,
+    Path(domain): Path<String>,
+    Query(params): Query<ReasonParams>,
+) -> Result<StatusCode, AppError> {
+    let removed = state
+        .blocklist
+        .unblock(&domain, "admin-api", non_empty(params.reason))
+        .map_err(AppError::Storage)?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+/// Installs new keys from the pool, which feeds advertise ahead of the previous ones until the
+/// next rotation.
+pub async fn api_rotate_keys(_: ApiAdmin, State(state): State<AppState>) -> StatusCode {
+    state.keys.rotate(&state.key_pool).await;
+    tracing::info!("keys rotated");
+    StatusCode::NO_CONTENT
+}
+
+pub async fn api_list_callbacks(
+    _: ApiAdmin,
+    State(state): State<AppState>,
+) -> Json<Vec<RegisteredDomain>> {
+    Json(state.callbacks.registered())
+}
+
+/// The secret a domain's callbacks are signed with, shown once when it is issued.
+#[derive(Serialize)]
+pub struct CallbackSecret {
+    domain: String,
+    secret: String,
+}
+
+/// Lets apps on the domain, `host` or `host:port` as in their `returnUrl`, pass a `callbackUrl`,
+/// and issues the secret their callbacks are signed with. Registering a domain again replaces its
+/// secret.
+pub async fn api_register_callbacks(
+    _: ApiAdmin,
+    State(state): State<AppState>,
+    Path(domain): Path<String>,
+) -> Result<(StatusCode, Json<CallbackSecret>), AppError> {
+    let domain = domain.to_lowercase();
+    let (secret, added) = state
+        .callbacks
+        .register(&domain)
+        .map_err(AppError::Storage)?;
+    tracing::info!(domain, "callback secret issued");
+    let status = if added {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    Ok((
+        status,
+        Json(CallbackSecret {
+            domain,
+            secret: secret.expose_secret().clone(),
+        }),
+    ))
+}
+
+pub async fn api_unregister_callbacks(
+    _: ApiAdmin,
+    State(state): State<AppState>,
+    Path(domain): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let removed = state
+        .callbacks
+        .unregister(&domain.to_lowercase())
+        .map_err(AppError::Storage)?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.trim().is_empty())
+}
+
+pub async fn api_list_txt(
+    _: ApiAdmin,
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<Json<Vec<String>>, AppError> {
+    require_podcast(&state, &slug)?;
+    Ok(Json(state.txt_tokens.tokens(&slug)))
+}
+
+pub async fn api_add_txt(
+    _: ApiAdmin,
+    State(state): State<AppState>,
+    Path((slug, token)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    require_podcast(&state, &slug)?;
+    if !txt::is_valid_token(&token) {
+        return Err(AppError::InvalidParam("token"));
+    }
+    tracing::info!(slug, token, "verification token published");
+    Ok(if state.txt_tokens.add(&slug, &token) {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    })
+}
+
+pub async fn api_remove_txt(
+    _: ApiAdmin,
+    State(state): State<AppState>,
+    Path((slug, token)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    require_podcast(&state, &slug)?;
+    if state.txt_tokens.remove(&slug, &token) {
+        tracing::info!(slug, token, "verification token withdrawn");
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+pub async fn api_list_renames(
+    _: ApiAdmin,
+    State(state): State<AppState>,
+) -> Json<BTreeMap<String, String>> {
+    Json(state.slug_history.snapshot())
+}
+
+#[derive(Deserialize)]
+pub struct RenameParams {
+    /// The podcast's current slug.
+    to: String,
+}
+
+/// Records that a podcast was renamed from `old`, so that its former URLs redirect.
+pub async fn api_rename(
+    _: ApiAdmin,
+    State(state): State<AppState>,
+    Path(old): Path<String>,
+    Query(params): Query<RenameParams>,
+) -> Result<StatusCode, AppError> {
+    let podcast = slug_to_podcast(state.podcasts.all(), &params.to)
+        .ok_or_else(|| AppError::PodcastNotFound(params.to.clone()))?;
+    if slug_to_podcast(state.podcasts.all(), &old).is_some() {
+        // A podcast still uses the slug, so requests for it would never be redirected.
+        return Err(AppError::InvalidParam("old"));
+    }
+    let added = state
+        .slug_history
+        .rename(&old, &podcast.slug)
+        .map_err(AppError::Storage)?;
+    tracing::info!(from = old, to = podcast.slug, "slug rename recorded");
+    Ok(if added {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    })
+}
+
+pub async fn api_forget_rename(
+    _: ApiAdmin,
+    State(state): State<AppState>,
+    Path(old): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let removed = state.slug_history.forget(&old).map_err(AppError::Storage)?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportRequest {
+    /// The feed as it is served elsewhere.
+    url: String,
+    owner_email: String,
+    owner_password: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedPodcast {
+    slug: String,
+    title: String,
+    description: String,
+    episodes: Vec<import::Episode>,
+    feed_url: Url,
+    /// The feed's own `<podcast:guid>`, or the one the host added.
+    guid: String,
+}
+
+/// Imports the podcast at an external feed URL as `slug`, keeping the feed as it is and
+/// republishing it with the host's verification tags.
+pub async fn api_import(
+    _: ApiAdmin,
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<ImportRequest>,
+) -> Result<(StatusCode, Json<ImportedPodcast>), AppError> {
+    // A podcast that used to have the slug would lose the redirect to where it is now.
+    if !slugs::is_valid_slug(&slug)
+        || slug_to_podcast(state.podcasts.all(), &slug).is_some()
+        || state.slug_history.snapshot().contains_key(&slug)
+    {
+        return Err(AppError::InvalidParam("slug"));
+    }
+    let url = Url::parse(&request.url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or(AppError::InvalidParam("url"))?;
+    let owner_email = request.owner_email.trim();
+    if owner_email.is_empty() {
+        return Err(AppError::MissingParam("ownerEmail"));
+    }
+    if request.owner_password.is_empty() {
+        return Err(AppError::MissingParam("ownerPassword"));
+    }
+
+    let parsed = import::fetch(&url)
+        .await
+        .map_err(|e| AppError::ImportFailed(e.to_string()))?;
+    let mut podcast = Podcast::new(&parsed.title, &slug, owner_email, &request.owner_password);
+    podcast.imported = Some(Arc::new(parsed.feed));
+    podcast.updated_at = Some(SystemTime::now());
+    let config = state.config();
+    let feed_url = podcast.feed_url(&config.public_base_url(&headers));
+    let guid = podcast.guid(&config.public_base_url);
+    if !state.podcasts.add(podcast) {
+        return Err(AppError::InvalidParam("slug"));
+    }
+    state.load_keys().await;
+    tracing::info!(
+        slug,
+        %url,
+        episodes = parsed.episodes.len(),
+        "podcast imported"
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ImportedPodcast {
+            slug,
+            title: parsed.title,
+            description: parsed.description,
+            episodes: parsed.episodes,
+            feed_url,
+            guid,
+        }),
+    ))
+}
+
+fn require_podcast(state: &AppState, slug: &str) -> Result<(), AppError> {
+    slug_to_podcast(state.podcasts.all(), slug)
+        .map(|_| ())
+        .ok_or_else(|| AppError::PodcastNotFound(slug.to_string()))
+}
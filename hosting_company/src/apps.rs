@@ -0,0 +1,322 @@
+//! What the app asking to verify says about itself: its name and icon, from its website's web
+//! app manifest, `<title>` and `<link rel="icon">`. Shown on the consent screen next to the
+//! `returnUrl` domain, so that owners can see who is asking before they type their password.
+//!
+//! Websites are fetched in the background and cached, so the consent screen never waits for an
+//! app's website, and the first one shown for an app may lack its identity. Apps choose what is
+//! fetched, so it is fetched as [`outbound`] allows, and only from the app's own origin.
+
+use crate::fetch::{FeedFetcher, FetchConfig};
+use crate::outbound;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::{Host, Url};
+
+/// How long an app's identity is cached before its website is fetched again.
+const TTL: Duration = Duration::from_secs(60 * 60);
+/// Apps cached at most, so that requests with made-up domains can't fill memory.
+const MAX_APPS: usize = 1024;
+/// Longest name shown, in characters.
+const MAX_NAME_CHARS: usize = 80;
+
+/// An app's name and icon, as claimed by its website. Only the domain is vouched for.
+#[derive(Clone, Debug, Default)]
+pub struct AppIdentity {
+    /// The manifest's `name` or `short_name`, or else the page's `<title>`.
+    pub name: Option<String>,
+    pub icon_url: Option<Url>,
+}
+
+/// App identities by origin.
+#[derive(Clone)]
+pub struct AppIdentities {
+    /// `None` when looking up apps is disabled.
+    fetcher: Option<FeedFetcher>,
+    /// Whether apps on loopback hosts are looked up, over HTTP too, for local testing.
+    allow_loopback: bool,
+    apps: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+struct Entry {
+    /// `None` until the first lookup finishes.
+    identity: Option<AppIdentity>,
+    looked_up_at: Instant,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    name: Option<String>,
+    short_name: Option<String>,
+    #[serde(default)]
+    icons: Vec<ManifestIcon>,
+}
+
+#[derive(Deserialize)]
+struct ManifestIcon {
+    src: String,
+}
+
+impl AppIdentities {
+    pub fn new(enabled: bool, allow_loopback: bool, proxy: Option<&outbound::Proxy>) -> Self {
+        let fetcher = enabled.then(|| {
+            let config = FetchConfig {
+                connect_timeout: Duration::from_secs(3),
+                timeout: Duration::from_secs(5),
+                max_redirects: 3,
+                max_bytes: 512 * 1024,
+                retries: 0,
+                ..FetchConfig::default()
+            };
+            let client = outbound::client_builder(config.max_redirects, allow_loopback, proxy);
+            FeedFetcher::with_client(client, config).expect("failed to build HTTP client")
+        });
+        AppIdentities {
+            fetcher,
+            allow_loopback,
+            apps: Arc::default(),
+        }
+    }
+
+    /// The identity of the app at `return_url`'s origin, if it has been looked up. Looks it up
+    /// in the background if it hasn't, or if the cached identity is stale.
+    pub fn lookup(&self, return_url: &Url) -> Option<AppIdentity> {
+        let fetcher = self.fetcher.as_ref()?;
+        if !self.may_fetch(return_url) {
+            return None;
+        }
+
+        let origin = return_url.origin().ascii_serialization();
+        let mut apps = self.apps.lock().unwrap();
+        let identity = match apps.get(&origin) {
+            Some(entry) if entry.looked_up_at.elapsed() < TTL => return entry.identity.clone(),
+            Some(entry) => entry.identity.clone(),
+            None => None,
+        };
+
+        if apps.len() >= MAX_APPS && !apps.contains_key(&origin) {
+            let oldest = apps
+                .iter()
+                .min_by_key(|(_, entry)| entry.looked_up_at)
+                .map(|(origin, _)| origin.clone());
+            if let Some(oldest) = oldest {
+                apps.remove(&oldest);
+            }
+        }
+        // Marked as looked up now, so that requests in the meantime don't start another lookup.
+        apps.insert(
+            origin.clone(),
+            Entry {
+                identity: identity.clone(),
+                looked_up_at: Instant::now(),
+            },
+        );
+
+        let home_page = return_url.join("/").expect("an origin forms a valid URL");
+        let (fetcher, apps) = (fetcher.clone(), self.apps.clone());
+        tokio::spawn(async move {
+            let identity = fetch_identity(&fetcher, &home_page).await;
+            tracing::debug!(origin = %origin, ?identity, "looked up app identity");
+            if let Some(entry) = apps.lock().unwrap().get_mut(&origin) {
+                entry.identity = Some(identity);
+            }
+        });
+        identity
+    }
+
+    /// Only websites on domain names are fetched, as addresses are more likely to be internal
+    /// services than apps, and only if [`outbound::allows`] them.
+    fn may_fetch(&self, url: &Url) -> bool {
+        let on_domain = match url.host() {
+            Some(Host::Domain(_)) => true,
+            Some(Host::Ipv4(ip)) => ip.is_loopback(),
+            Some(Host::Ipv6(ip)) => ip.is_loopback(),
+            None => false,
+        };
+        on_domain && outbound::allows(url, self.allow_loopback)
+    }
+}
+
+async fn fetch_identity(fetcher: &FeedFetcher, home_page: &Url) -> AppIdentity {
+    let Ok(page) = fetcher.fetch(home_page).await else {
+        return AppIdentity::default();
+    };
+    let links = links(&page);
+    let mut name = title(&page);
+    let mut icon_url = None;
+
+    if let Some(manifest_url) = link_href(&links, "manifest", home_page) {
+        let manifest = fetcher
+            .fetch(&manifest_url)
+            .await
+            .ok()
+            .and_then(|manifest| serde_json::from_str::<Manifest>(&manifest).ok());
+        if let Some(manifest) = manifest {
+            name = manifest.name.or(manifest.short_name).or(name);
+            icon_url = manifest
+                .icons
+                .iter()
+                .filter_map(|icon| manifest_url.join(&icon.src).ok())
+                .find(|icon_url| same_origin(icon_url, home_page));
+        }
+    }
+    if icon_url.is_none() {
+        icon_url = link_href(&links, "icon", home_page);
+    }
+    if icon_url.is_none() {
+        let favicon = home_page
+            .join("/favicon.ico")
+            .expect("favicon path forms a valid URL");
+        if fetcher.fetch(&favicon).await.is_ok() {
+            icon_url = Some(favicon);
+        }
+    }
+
+    AppIdentity {
+        name: name.as_deref().and_then(clean_name),
+        icon_url,
+    }
+}
+
+fn same_origin(url: &Url, home_page: &Url) -> bool {
+    url.origin() == home_page.origin()
+}
+
+/// The `href` of the first link with `rel` among its link types, resolved against `base`, that
+/// is on the same origin.
+fn link_href(links: &[(String, String)], rel: &str, base: &Url) -> Option<Url> {
+    links
+        .iter()
+        .filter(|(types, _)| {
+            types
+                .split_ascii_whitespace()
+                .any(|link_type| link_type == rel)
+        })
+        .filter_map(|(_, href)| base.join(href).ok())
+        .find(|url| same_origin(url, base))
+}
+
+/// The text of the page's `<title>`.
+fn title(page: &str) -> Option<String> {
+    // Lowercasing ASCII keeps byte offsets, so they can be used to slice the page.
+    let lower = page.to_ascii_lowercase();
+    let start = lower.find("<title")?;
+    let start = start + lower[start..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    Some(decode_entities(&page[start..end]))
+}
+
+/// The lowercase `rel` and the `href` of each of the page's `<link>` tags.
+fn links(page: &str) -> Vec<(String, String)> {
+    let lower = page.to_ascii_lowercase();
+    let mut links = Vec::new();
+    let mut position = 0;
+    while let Some(start) = lower[position..].find("<link") {
+        let start = position + start + "<link".len();
+        let Some(length) = lower[start..].find('>') else {
+            break;
+        };
+        let attributes = attributes(&page[start..start + length]);
+        if let (Some(rel), Some(href)) = (attributes.get("rel"), attributes.get("href")) {
+            links.push((rel.to_ascii_lowercase(), href.clone()));
+        }
+        position = start + length;
+    }
+    links
+}
+
+/// A tag's attributes by lowercase name, from the text between its name and `>`.
+fn attributes(tag: &str) -> HashMap<String, String> {
+    let separator = |c: char| c.is_whitespace() || c == '/';
+    let mut attributes = HashMap::new();
+    let mut rest = tag.trim_start_matches(separator);
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| separator(c) || c == '=')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+
+        let mut value = String::new();
+        if let Some(after_equals) = rest.strip_prefix('=') {
+            let after_equals = after_equals.trim_start();
+            let (raw, remainder) = match after_equals.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let quoted = &after_equals[1..];
+                    let end = quoted.find(quote).unwrap_or(quoted.len());
+                    (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+                }
+                _ => after_equals.split_at(
+                    after_equals
+                        .find(char::is_whitespace)
+                        .unwrap_or(after_equals.len()),
+                ),
+            };
+            value = decode_entities(raw);
+            rest = remainder;
+        }
+        if !name.is_empty() {
+            attributes.entry(name).or_insert(value);
+        }
+        rest = rest.trim_start_matches(separator);
+    }
+    attributes
+}
+
+/// Decodes the character references names and URLs are likely to contain.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let reference = rest
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| Some((char_reference(&rest[1..end])?, end)));
+        match reference {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn char_reference(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// The name on one line without control characters, shortened if needed. `None` if nothing is
+/// left.
+fn clean_name(name: &str) -> Option<String> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut chars = name.chars().filter(|c| !c.is_control());
+    let mut cleaned: String = chars.by_ref().take(MAX_NAME_CHARS).collect();
+    if chars.next().is_some() {
+        cleaned.push('…');
+    }
+    (!cleaned.is_empty()).then_some(cleaned)
+}
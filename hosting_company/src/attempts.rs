@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// One verification request made to a podcast's verify URL, as shown to its owner.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Attempt {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub slug: String,
+    /// Domain of the requesting app's `returnUrl`, if it sent a valid one.
+    pub domain: Option<String>,
+    pub outcome: Outcome,
+    /// Why the request was rejected, as in logs and metrics.
+    pub reason: Option<String>,
+    pub client_ip: IpAddr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// The consent screen was shown.
+    Requested,
+    Approved,
+    Denied,
+    SignInLinkSent,
+    /// The owner logged in and reported the request as abuse.
+    Reported,
+    /// Someone tried to log in or ask for a sign-in link with the wrong email or password.
+    InvalidCredentials,
+    /// The request was invalid or refused; see the attempt's reason.
+    Rejected,
+}
+
+/// Log of every verification attempt, appended to a JSON Lines file so that it survives
+/// restarts.
+#[derive(Clone)]
+pub struct Attempts {
+    path: PathBuf,
+    attempts: Arc<Mutex<Vec<Attempt>>>,
+}
+
+impl Attempts {
+    /// Loads the log from `path`, starting empty if the file does not exist yet.
+    pub fn load(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let attempts = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<Result<_, _>>()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Attempts {
+            path,
+            attempts: Arc::new(Mutex::new(attempts)),
+        })
+    }
+
+    /// Appends the attempt to the log. Failing to persist it is logged rather than failing the
+    /// request, which has already been answered.
+    pub fn record(&self, attempt: Attempt) {
+        let mut attempts = self.attempts.lock().unwrap();
+        let result = serde_json::to_string(&attempt)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            .and_then(|line| {
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?;
+                writeln!(file, "{line}")
+            });
+        if let Err(e) = result {
+            tracing::error!(path = %self.path.display(), "failed to persist verification attempt: {e}");
+        }
+        attempts.push(attempt);
+    }
+
+    /// The podcast's attempts, most recent first.
+    pub fn for_podcast(&self, slug: &str) -> Vec<Attempt> {
+        self.attempts
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|attempt| attempt.slug == slug)
+            .cloned()
+            .collect()
+    }
+}
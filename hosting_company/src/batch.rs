@@ -0,0 +1,430 @@
+//! Verifying several feeds at once, so that an owner with many podcasts doesn't go through the
+//! flow for each of them. The app sends a challenge per feed to `/verify/batch`, the owner logs
+//! in once and picks which of their podcasts to verify, and the app gets every result in one
+//! redirect.
+
+use crate::attempts::Outcome;
+use crate::error::AppError;
+use crate::{
+    crypto, domain_name, feed_identity, magic, record_attempt, sign_verification, slug_to_podcast,
+    view, with_state, AppState, Podcast,
+};
+use axum::extract::{ConnectInfo, Form, Query, RawQuery, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Feeds a request may ask to verify at most.
+pub const MAX_FEEDS: usize = 20;
+/// How long an owner who logged in has to choose their podcasts.
+const LOGIN_TTL: Duration = Duration::from_secs(10 * 60);
+/// Prefix of the parameters with each feed's challenge, followed by the feed's slug.
+const CHALLENGE_PREFIX: &str = "encryptedString.";
+
+/// A batch verification request whose parameters have been checked.
+struct BatchRequest {
+    return_url: Url,
+    /// `return_url` with the app's `state`.
+    return_to: Url,
+    domain_name: String,
+    state: Option<String>,
+    /// The requested podcasts that exist and are published, with their challenges.
+    feeds: Vec<(Podcast, String)>,
+}
+
+/// The outcome for one requested feed, sent to the app in the JSON array of the `results`
+/// parameter.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FeedResult {
+    feed_url: Url,
+    /// The feed's `<podcast:guid>`, which apps should match results to feeds by.
+    feed_guid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decrypted_string: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grant_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signed_verification: Option<String>,
+    /// The same codes as the `error` and `error_description` parameters of a failed
+    /// verification, for a feed that wasn't verified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_description: Option<&'static str>,
+}
+
+/// Owners who logged in to a batch request and are choosing their podcasts. Only hashes of the
+/// tokens are kept, as for sign-in links.
+#[derive(Clone, Default)]
+pub struct BatchLogins {
+    pending: Arc<Mutex<HashMap<[u8; 32], PendingLogin>>>,
+}
+
+struct PendingLogin {
+    email: String,
+    /// The request's query string, so that a token only works for the request it was issued
+    /// for.
+    query: String,
+    expires_at: Instant,
+}
+
+impl BatchLogins {
+    fn issue(&self, email: &str, query: &str) -> String {
+        let token = magic::new_token();
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, login| login.expires_at > now);
+        pending.insert(
+            magic::hash(&token),
+            PendingLogin {
+                email: email.to_string(),
+                query: query.to_string(),
+                expires_at: now + LOGIN_TTL,
+            },
+        );
+        token
+    }
+
+    /// The email address the token was issued to, if it was issued for this request and hasn't
+    /// expired. A token can only be consumed once.
+    fn consume(&self, token: &str, query: &str) -> Option<String> {
+        let mut pending = self.pending.lock().unwrap();
+        let key = magic::hash(token);
+        if pending.get(&key)?.query != query {
+            return None;
+        }
+        pending
+            .remove(&key)
+            .filter(|login| login.expires_at > Instant::now())
+            .map(|login| login.email)
+    }
+}
+
+/// Shows the owner the login form for an app's request to verify several feeds.
+pub async fn request(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Result<(StatusCode, Html<String>), AppError> {
+    state.metrics.verification_attempts.inc();
+    let request = validate(&state, &params)?;
+    record(&state, &request, addr.ip(), Outcome::Requested);
+    Ok(login_screen(&request, false))
+}
+
+/// Handles the owner's answers, which post back to the batch URL: logging in, then approving
+/// the podcasts they chose, or denying the request at either step.
+pub async fn answer(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    RawQuery(query): RawQuery,
+    Query(params): Query<Vec<(String, String)>>,
+    Form(form): Form<Vec<(String, String)>>,
+) -> Result<Response, AppError> {
+    let request = validate(&state, &params)?;
+    let query = query.unwrap_or_default();
+    let field = |name: &str| {
+        form.iter()
+            .find(|(key, _)| key == name)
+            .map_or("", |(_, value)| value.as_str())
+    };
+
+    match field("decision") {
+        "login" => log_in(
+            &state,
+            addr.ip(),
+            request,
+            &query,
+            field("email"),
+            field("password"),
+        ),
+        "approve" => {
+            let email = state
+                .batch_logins
+                .consume(field("token"), &query)
+                .ok_or_else(|| {
+                    AppError::LinkExpired.in_flow(None, Some(request.return_to.clone()))
+                })?;
+            let chosen: Vec<&str> = form
+                .iter()
+                .filter(|(key, _)| key == "slug")
+                .map(|(_, slug)| slug.as_str())
+                .collect();
+            Ok(approve(
+                &state,
+                &headers,
+                addr.ip(),
+                request,
+                &email,
+                &chosen,
+            ))
+        }
+        "deny" => {
+            tracing::info!(outcome = "denied", "batch verification denied");
+            record(&state, &request, addr.ip(), Outcome::Denied);
+            Ok(AppError::AccessDenied(request.return_to).into_response())
+        }
+        _ => Err(AppError::InvalidParam("decision").in_flow(None, Some(request.return_to))),
+    }
+}
+
+fn validate(state: &AppState, params: &[(String, String)]) -> Result<BatchRequest, AppError> {
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    let return_url = param("returnUrl").ok_or(AppError::MissingParam("returnUrl"))?;
+    let return_url = Url::parse(&return_url).map_err(|_| AppError::InvalidReturnUrl)?;
+    let domain_name = domain_name(&return_url).ok_or(AppError::InvalidReturnUrl)?;
+    let app_state = param("state");
+    let return_to = with_state(&return_url, app_state.as_deref());
+    let allowed = state.config().allows_return_to(&domain_name);
+    if state.blocklist.is_blocked(&domain_name) || !allowed {
+        return Err(AppError::AccessDenied(return_to));
+    }
+    let in_flow = |error: AppError| error.in_flow(None, Some(return_to.clone()));
+
+    let challenges: Vec<(&str, &str)> = params
+        .iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix(CHALLENGE_PREFIX)?, value.as_str())))
+        .collect();
+    let Some(first_slug) = challenges.first().map(|(slug, _)| slug.to_string()) else {
+        return Err(in_flow(AppError::MissingParam("encryptedString")));
+    };
+    if challenges.len() > MAX_FEEDS
+        || challenges
+            .iter()
+            .any(|(_, challenge)| challenge.len() > crypto::MAX_ENCRYPTED_STRING_LEN)
+    {
+        return Err(in_flow(AppError::InvalidParam("encryptedString")));
+    }
+
+    let mut feeds: Vec<(Podcast, String)> = Vec::new();
+    for (slug, challenge) in challenges {
+        let Some(podcast) =
+            slug_to_podcast(state.podcasts.all(), slug).filter(Podcast::is_published)
+        else {
+            continue;
+        };
+        if feeds.iter().all(|(known, _)| known.slug != podcast.slug) {
+            feeds.push((podcast, challenge.to_string()));
+        }
+    }
+    if feeds.is_empty() {
+        return Err(in_flow(AppError::PodcastNotFound(first_slug)));
+    }
+
+    Ok(BatchRequest {
+        return_url,
+        return_to,
+        domain_name,
+        state: app_state,
+        feeds,
+    })
+}
+
+fn login_screen(request: &BatchRequest, login_failed: bool) -> (StatusCode, Html<String>) {
+    let podcasts: Vec<Podcast> = request
+        .feeds
+        .iter()
+        .map(|(podcast, _)| podcast.clone())
+        .collect();
+    view::batch_login(&request.domain_name, &podcasts, login_failed)
+}
+
+/// Checks the owner's credentials against the requested podcasts, and lets them choose among
+/// those that are theirs.
+fn log_in(
+    state: &AppState,
+    client_ip: IpAddr,
+    request: BatchRequest,
+    query: &str,
+    email: &str,
+    password: &str,
+) -> Result<Response, AppError> {
+    // Checked before the password, so that guesses during a lockout reveal nothing.
+    if let Err(retry_after) = state.login_throttle.check(email, client_ip) {
+        return Err(AppError::LoginLocked {
+            retry_after_secs: retry_after.as_secs().max(1),
+        }
+        .in_flow(None, None));
+    }
+
+    // Every requested podcast's owner is checked, so that the time taken doesn't tell which of
+    // them the email address belongs to.
+    let mut owned: Vec<Podcast> = Vec::new();
+    let valid = state.metrics.time_login(|| {
+        owned = request
+            .feeds
+            .iter()
+            .map(|(podcast, _)| podcast)
+            .filter(|podcast| podcast.owner.verify(email, password))
+            .cloned()
+            .collect();
+        !owned.is_empty()
+    });
+    if !valid {
+        state.login_throttle.record_failure(email, client_ip);
+        record(state, &request, client_ip, Outcome::InvalidCredentials);
+        let (_, page) = login_screen(&request, true);
+        return Ok((AppError::InvalidCredentials.status(), page).into_response());
+    }
+    state.login_throttle.record_success(email, client_ip);
+
+    let token = state.batch_logins.issue(email, query);
+    Ok(view::batch_choose(&request.domain_name, &owned, &token).into_response())
+}
+
+/// Verifies the chosen podcasts that belong to the owner who logged in, and sends the app back
+/// a result for every requested feed.
+fn approve(
+    state: &AppState,
+    headers: &HeaderMap,
+    client_ip: IpAddr,
+    request: BatchRequest,
+    email: &str,
+    chosen: &[&str],
+) -> Response {
+    let config = state.config();
+    let base_url = config.public_base_url(headers);
+    let mut results = Vec::new();
+    for (podcast, encrypted_string) in &request.feeds {
+        let approved = podcast.owner.email == email && chosen.contains(&podcast.slug.as_str());
+        let result = if approved {
+            verify_feed(state, &request, podcast, &base_url, encrypted_string)
+        } else {
+            Err(AppError::AccessDenied(request.return_to.clone()))
+        };
+
+        let outcome = match &result {
+            Ok(_) => Ok(Outcome::Approved),
+            Err(AppError::AccessDenied(_)) => Ok(Outcome::Denied),
+            Err(error) => Err(error),
+        };
+        let reason = match outcome {
+            Ok(Outcome::Approved) => "approved",
+            Ok(_) => "denied",
+            Err(error) => error.reason(),
+        };
+        tracing::info!(
+            slug = podcast.slug,
+            outcome = reason,
+            "batch verification answered"
+        );
+        match outcome {
+            Ok(_) => state
+                .metrics
+                .verification_completions
+                .with_label_values(&[reason])
+                .inc(),
+            Err(_) => state
+                .metrics
+                .verification_failures
+                .with_label_values(&[reason])
+                .inc(),
+        }
+        record_attempt(
+            state,
+            &podcast.slug,
+            Some(request.return_url.as_str()),
+            client_ip,
+            outcome,
+        );
+
+        let feed_url = podcast.feed_url(&base_url);
+        let feed_guid = podcast.guid(&config.public_base_url);
+        results.push(match result {
+            Ok(verified) => FeedResult {
+                feed_url,
+                feed_guid,
+                decrypted_string: Some(verified.decrypted_string),
+                grant_id: Some(verified.grant_id),
+                signed_verification: Some(verified.signed_verification),
+                error: None,
+                error_description: None,
+            },
+            Err(error) => FeedResult {
+                feed_url,
+                feed_guid,
+                decrypted_string: None,
+                grant_id: None,
+                signed_verification: None,
+                error: Some(error.oauth_error()),
+                error_description: Some(error.reason()),
+            },
+        });
+    }
+
+    let mut return_to = request.return_to;
+    return_to.query_pairs_mut().append_pair(
+        "results",
+        &serde_json::to_string(&results).expect("results serialize to JSON"),
+    );
+    Redirect::to(return_to.as_str()).into_response()
+}
+
+/// A feed the owner verified.
+struct Verified {
+    decrypted_string: String,
+    grant_id: String,
+    /// The token for the `signedVerification` field.
+    signed_verification: String,
+}
+
+/// Decrypts and signs a feed's challenge and records the grant.
+fn verify_feed(
+    state: &AppState,
+    request: &BatchRequest,
+    podcast: &Podcast,
+    base_url: &Url,
+    encrypted_string: &str,
+) -> Result<Verified, AppError> {
+    let padding = podcast.crypto.padding;
+    let pairs = state.keys.get(&podcast.crypto)?;
+    let decrypted_string = state.metrics.time_crypto("decrypt", || {
+        crypto::decrypt_with_any(&pairs, padding, encrypted_string)
+    })?;
+    let signed_verification = sign_verification(
+        &state.metrics,
+        &state.keys.signing()?,
+        feed_identity(state, podcast, base_url),
+        &decrypted_string,
+        &request.return_url,
+    )?;
+    let grant = state
+        .grants
+        .grant(
+            &podcast.slug,
+            &request.domain_name,
+            request.state.clone(),
+            None,
+        )
+        .map_err(AppError::Storage)?;
+    Ok(Verified {
+        decrypted_string,
+        grant_id: grant.id,
+        signed_verification: signed_verification.to_token(),
+    })
+}
+
+/// Adds the request to the attempts log of every requested podcast.
+fn record(state: &AppState, request: &BatchRequest, client_ip: IpAddr, outcome: Outcome) {
+    for (podcast, _) in &request.feeds {
+        record_attempt(
+            state,
+            &podcast.slug,
+            Some(request.return_url.as_str()),
+            client_ip,
+            Ok(outcome),
+        );
+    }
+}
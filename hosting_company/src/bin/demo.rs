@@ -0,0 +1,95 @@
+//! Runs the demo's host, its app, or both in one process.
+//!
+//! ```text
+//! demo hosting
+//! demo app [--port <port>] [--feed-url <url>]
+//! demo all [--app-port <port>]
+//! ```
+//!
+//! Both read the host's settings from the environment, so the app offers to verify a feed at the
+//! host's `PUBLIC_BASE_URL`.
+
+use clap::{Parser, Subcommand};
+use hosting_company::config::Config;
+use hosting_company::demo_app;
+use std::net::{SocketAddr, TcpListener};
+use tracing_subscriber::EnvFilter;
+use url::Url;
+
+const HOST_PORT: u16 = 8081;
+const APP_PORT: u16 = 8082;
+
+#[derive(Parser)]
+#[command(about = "Runs the <podcast:verify> demo")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Runs the hosting company, on port 8081.
+    Hosting,
+    /// Runs an app that verifies podcasts against a host.
+    App {
+        /// The port the app listens on.
+        #[arg(long, default_value_t = APP_PORT)]
+        port: u16,
+        /// The feed the app offers to verify. Alice's podcast on the host by default.
+        #[arg(long)]
+        feed_url: Option<Url>,
+    },
+    /// Runs the hosting company and the app together.
+    All {
+        /// The port the app listens on.
+        #[arg(long, default_value_t = APP_PORT)]
+        app_port: u16,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::new("hosting_company=info,tower_http=info")),
+        )
+        .init();
+
+    let config = Config::from_env();
+    match Cli::parse().command {
+        Command::Hosting => run_host(config).await,
+        Command::App { port, feed_url } => run_app(&config, port, feed_url).await,
+        Command::All { app_port } => {
+            tokio::join!(run_app(&config, app_port, None), run_host(config.clone()));
+        }
+    }
+}
+
+async fn run_host(config: Config) {
+    let listener = bind(HOST_PORT);
+    tracing::info!("Hosting Company listening on {}", config.public_base_url);
+    hosting_company::run(config, listener)
+        .await
+        .expect("failed to serve the host");
+}
+
+async fn run_app(config: &Config, port: u16, feed_url: Option<Url>) {
+    let base_url = Url::parse(&format!("http://localhost:{port}/")).unwrap();
+    let feed_url =
+        feed_url.unwrap_or_else(|| config.public_base_url.join("feed/alice-podcast").unwrap());
+    let router = demo_app::router(base_url.clone(), feed_url);
+
+    tracing::info!("Demo App listening on {base_url}");
+    axum::Server::from_tcp(bind(port))
+        .expect("failed to serve the app")
+        .serve(router.into_make_service())
+        .with_graceful_shutdown(hosting_company::shutdown_signal())
+        .await
+        .expect("failed to serve the app");
+}
+
+fn bind(port: u16) -> TcpListener {
+    TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port)))
+        .unwrap_or_else(|e| panic!("failed to bind port {port}: {e}"))
+}
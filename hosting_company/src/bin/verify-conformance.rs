@@ -0,0 +1,380 @@
+//! Checks a host's `<podcast:verify>` implementation from the outside, the way an app sees it.
+//!
+//! ```text
+//! verify-conformance <feed-url> [--email <email> --password <password>]
+//! ```
+//!
+//! With an owner's login, the approve and deny steps are also run. They post the consent form
+//! used by this example's host, so they only apply to hosts that use the same form fields.
+
+use hosting_company::crypto::Padding;
+use hosting_company::fetch::{FeedFetcher, FetchConfig};
+use hosting_company::handshake::{self, Encoding};
+use hosting_company::tag::{feed_attr_to_public_key, verify_attributes};
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPublicKey;
+use std::collections::HashMap;
+use std::process::ExitCode;
+use url::Url;
+
+/// Where the host is told to send the owner back to. Nothing needs to listen there, as
+/// redirects are inspected rather than followed.
+const RETURN_URL: &str = "http://127.0.0.1:9/conformance-return";
+const STATE: &str = "conformance state/+&=";
+
+struct Login {
+    email: String,
+    password: String,
+}
+
+enum Verdict {
+    Pass(String),
+    Fail(String),
+    Skip(String),
+}
+
+#[derive(Default)]
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    fn record(&mut self, requirement: &str, verdict: Verdict) {
+        match verdict {
+            Verdict::Pass(details) => println!("PASS  {requirement}: {details}"),
+            Verdict::Fail(details) => {
+                self.failures += 1;
+                println!("FAIL  {requirement}: {details}");
+            }
+            Verdict::Skip(details) => println!("SKIP  {requirement}: {details}"),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let (feed_url, login) = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{message}");
+            eprintln!(
+                "usage: verify-conformance <feed-url> [--email <email> --password <password>]"
+            );
+            return ExitCode::from(2);
+        }
+    };
+
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("failed to build HTTP client");
+    let mut report = Report::default();
+    run(&client, &feed_url, login.as_ref(), &mut report).await;
+
+    if report.failures == 0 {
+        println!("\nAll checks passed.");
+        ExitCode::SUCCESS
+    } else {
+        println!("\n{} check(s) failed.", report.failures);
+        ExitCode::FAILURE
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(Url, Option<Login>), String> {
+    let feed_url = args.next().ok_or("missing feed URL")?;
+    let feed_url = Url::parse(&feed_url).map_err(|e| format!("invalid feed URL: {e}"))?;
+
+    let mut email = None;
+    let mut password = None;
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("missing value for {flag}"))?;
+        match flag.as_str() {
+            "--email" => email = Some(value),
+            "--password" => password = Some(value),
+            _ => return Err(format!("unknown option {flag}")),
+        }
+    }
+    let login = match (email, password) {
+        (Some(email), Some(password)) => Some(Login { email, password }),
+        (None, None) => None,
+        _ => {
+            return Err(String::from(
+                "--email and --password must be given together",
+            ))
+        }
+    };
+    Ok((feed_url, login))
+}
+
+async fn run(client: &reqwest::Client, feed_url: &Url, login: Option<&Login>, report: &mut Report) {
+    let feed = match fetch_feed(feed_url).await {
+        Ok(feed) => {
+            report.record("feed is served", Verdict::Pass(feed_url.to_string()));
+            feed
+        }
+        Err(e) => return report.record("feed is served", Verdict::Fail(e)),
+    };
+
+    let Some(tag) = verify_attributes(&feed) else {
+        return report.record(
+            "<podcast:verify> is present",
+            Verdict::Fail(String::from("no <podcast:verify> element in the feed")),
+        );
+    };
+    report.record("<podcast:verify> is present", Verdict::Pass(String::new()));
+    let tags = feed.matches("<podcast:verify").count();
+    if tags > 1 {
+        println!("      note: {tags} tags, as during a key rotation; checking the first");
+    }
+
+    let verify_url = match tag.get("verifyUrl").map(|url| Url::parse(url)) {
+        Some(Ok(url)) if matches!(url.scheme(), "http" | "https") => {
+            report.record(
+                "verifyUrl is an absolute URL",
+                Verdict::Pass(url.to_string()),
+            );
+            Some(url)
+        }
+        Some(_) => {
+            report.record(
+                "verifyUrl is an absolute URL",
+                Verdict::Fail(String::from("not an absolute http(s) URL")),
+            );
+            None
+        }
+        None => {
+            report.record(
+                "verifyUrl is an absolute URL",
+                Verdict::Fail(String::from("attribute missing")),
+            );
+            None
+        }
+    };
+    if let Some(url) = &verify_url {
+        if url.scheme() != "https" {
+            println!(
+                "      note: verifyUrl isn't HTTPS, which is only acceptable for local testing"
+            );
+        }
+    }
+
+    let public_key = tag
+        .get("publicKey")
+        .ok_or_else(|| String::from("attribute missing"))
+        .and_then(|key| feed_attr_to_public_key(key).map_err(|e| e.to_string()));
+    let public_key = match public_key {
+        Ok(key) if key.size() * 8 < 2048 => {
+            report.record(
+                "publicKey parses",
+                Verdict::Fail(format!("{}-bit keys are too weak", key.size() * 8)),
+            );
+            None
+        }
+        Ok(key) => {
+            report.record(
+                "publicKey parses",
+                Verdict::Pass(format!("{}-bit RSA key", key.size() * 8)),
+            );
+            Some(key)
+        }
+        Err(e) => {
+            report.record("publicKey parses", Verdict::Fail(e));
+            None
+        }
+    };
+
+    let encryption = match tag.get("encryption").map(String::as_str) {
+        Some(name @ ("RSA-OAEP-256" | "RSA1_5")) => {
+            report.record("encryption is supported", Verdict::Pass(name.to_string()));
+            Some(name.to_string())
+        }
+        Some(other) => {
+            report.record(
+                "encryption is supported",
+                Verdict::Fail(format!("unknown scheme {other:?}")),
+            );
+            None
+        }
+        None => {
+            report.record(
+                "encryption is supported",
+                Verdict::Pass(String::from("not advertised, assuming RSA1_5")),
+            );
+            Some(String::from("RSA1_5"))
+        }
+    };
+
+    let (Some(verify_url), Some(public_key), Some(encryption)) =
+        (verify_url, public_key, encryption)
+    else {
+        return report.record(
+            "handshake",
+            Verdict::Skip(String::from("the tag is unusable")),
+        );
+    };
+
+    let challenge = challenge();
+    let encrypted_string = match encrypt(&public_key, &encryption, &challenge) {
+        Ok(encrypted) => encrypted,
+        Err(e) => return report.record("challenge encrypts", Verdict::Fail(e)),
+    };
+    let handshake_url = with_params(
+        &verify_url,
+        &[
+            ("encryptedString", &encrypted_string),
+            ("returnUrl", RETURN_URL),
+            ("state", STATE),
+        ],
+    );
+
+    report.record(
+        "verifyUrl shows a page to the owner",
+        match client.get(handshake_url.clone()).send().await {
+            Ok(response) if response.status().is_success() => {
+                Verdict::Pass(response.status().to_string())
+            }
+            Ok(response) => Verdict::Fail(format!("status {}", response.status())),
+            Err(e) => Verdict::Fail(e.to_string()),
+        },
+    );
+
+    let without_return_url = with_params(&verify_url, &[("encryptedString", &encrypted_string)]);
+    report.record(
+        "a request without returnUrl is refused",
+        match client.get(without_return_url).send().await {
+            Ok(response) if response.status().is_client_error() => {
+                Verdict::Pass(response.status().to_string())
+            }
+            Ok(response) => Verdict::Fail(format!("status {}", response.status())),
+            Err(e) => Verdict::Fail(e.to_string()),
+        },
+    );
+
+    let Some(login) = login else {
+        for requirement in [
+            "approval returns the decrypted challenge",
+            "denial redirects with error=access_denied",
+        ] {
+            report.record(
+                requirement,
+                Verdict::Skip(String::from("needs --email and --password")),
+            );
+        }
+        report.record(
+            "signature validates",
+            Verdict::Skip(String::from("the protocol doesn't sign results yet")),
+        );
+        return;
+    };
+
+    let approved = submit(
+        client,
+        &handshake_url,
+        &[
+            ("decision", "approve"),
+            ("email", &login.email),
+            ("password", &login.password),
+        ],
+    )
+    .await;
+    report.record(
+        "approval returns the decrypted challenge",
+        match approved {
+            Ok(params) if params.get("decryptedString") != Some(&challenge) => Verdict::Fail(
+                format!("decryptedString was {:?}", params.get("decryptedString")),
+            ),
+            Ok(params) if params.get("state").map(String::as_str) != Some(STATE) => {
+                Verdict::Fail(format!("state was {:?}", params.get("state")))
+            }
+            Ok(_) => Verdict::Pass(String::new()),
+            Err(e) => Verdict::Fail(e),
+        },
+    );
+
+    let denied = submit(client, &handshake_url, &[("decision", "deny")]).await;
+    report.record(
+        "denial redirects with error=access_denied",
+        match denied {
+            Ok(params) if params.get("error").map(String::as_str) != Some("access_denied") => {
+                Verdict::Fail(format!("error was {:?}", params.get("error")))
+            }
+            Ok(params) if params.contains_key("decryptedString") => {
+                Verdict::Fail(String::from("decryptedString was returned anyway"))
+            }
+            Ok(params) if params.get("state").map(String::as_str) != Some(STATE) => {
+                Verdict::Fail(format!("state was {:?}", params.get("state")))
+            }
+            Ok(_) => Verdict::Pass(String::new()),
+            Err(e) => Verdict::Fail(e),
+        },
+    );
+
+    report.record(
+        "signature validates",
+        Verdict::Skip(String::from("the protocol doesn't sign results yet")),
+    );
+}
+
+/// Fetches the feed as an app would, following redirects within the default limits.
+async fn fetch_feed(feed_url: &Url) -> Result<String, String> {
+    let fetcher = FeedFetcher::new(FetchConfig::default()).map_err(|e| e.to_string())?;
+    fetcher.fetch(feed_url).await.map_err(|e| e.to_string())
+}
+
+fn challenge() -> String {
+    let bytes: [u8; 16] = rand::random();
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn encrypt(public_key: &RsaPublicKey, encryption: &str, challenge: &str) -> Result<String, String> {
+    let padding = match encryption {
+        "RSA-OAEP-256" => Padding::OaepSha256,
+        _ => Padding::Pkcs1v15,
+    };
+    // Standard base64, which hosts that aren't lenient about the encoding expect.
+    handshake::encrypt_as(public_key, padding, challenge, Encoding::Standard)
+        .map_err(|e| e.to_string())
+}
+
+fn with_params(url: &Url, params: &[(&str, &str)]) -> Url {
+    let mut url = url.clone();
+    url.query_pairs_mut().extend_pairs(params);
+    url
+}
+
+/// Posts the consent form and returns the query parameters of the redirect to `returnUrl`.
+async fn submit(
+    client: &reqwest::Client,
+    handshake_url: &Url,
+    form: &[(&str, &str)],
+) -> Result<HashMap<String, String>, String> {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(form)
+        .finish();
+    let response = client
+        .post(handshake_url.clone())
+        .header(
+            reqwest::header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_redirection() {
+        return Err(format!(
+            "expected a redirect, got status {}",
+            response.status()
+        ));
+    }
+    let location = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .ok_or("redirect without a Location")?;
+    let location = Url::parse(location).map_err(|e| format!("invalid Location: {e}"))?;
+    if !location.as_str().starts_with(RETURN_URL) {
+        return Err(format!("redirected to {location} rather than returnUrl"));
+    }
+    Ok(location.query_pairs().into_owned().collect())
+}
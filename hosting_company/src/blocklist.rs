@@ -0,0 +1,145 @@
+use crate::unix_timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Directory domains that may not start verifications, persisted as JSON together with an audit
+/// trail of every change.
+///
+/// Domains are hosts: they are stored lowercased and without a port, and blocking one blocks it
+/// on every port and all of its subdomains, so that a phishing app can't get around a block by
+/// moving to another port or a new subdomain.
+#[derive(Clone)]
+pub struct Blocklist {
+    path: PathBuf,
+    data: Arc<Mutex<BlocklistData>>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct BlocklistData {
+    pub domains: BTreeSet<String>,
+    pub audit: Vec<AuditEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub action: AuditAction,
+    pub domain: String,
+    /// Who made the change, e.g. `admin` or `abuse-report`.
+    pub actor: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Block,
+    Unblock,
+}
+
+impl Blocklist {
+    /// Loads the blocklist from `path`, starting empty if the file does not exist yet.
+    pub fn load(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let mut data: BlocklistData = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BlocklistData::default(),
+            Err(e) => return Err(e),
+        };
+        data.domains = data
+            .domains
+            .iter()
+            .map(|domain| normalize(domain))
+            .collect();
+        Ok(Blocklist {
+            path,
+            data: Arc::new(Mutex::new(data)),
+        })
+    }
+
+    /// Whether `domain`, a host with or without a port, or any domain it is a subdomain of is
+    /// blocked.
+    pub fn is_blocked(&self, domain: &str) -> bool {
+        let host = normalize(domain);
+        let data = self.data.lock().unwrap();
+        let mut parents = host.match_indices('.').map(|(dot, _)| &host[dot + 1..]);
+        data.domains.contains(&host) || parents.any(|parent| data.domains.contains(parent))
+    }
+
+    pub fn snapshot(&self) -> BlocklistData {
+        self.data.lock().unwrap().clone()
+    }
+
+    /// Blocks the domain. Returns `false` if it was already blocked.
+    pub fn block(
+        &self,
+        domain: &str,
+        actor: &str,
+        reason: Option<String>,
+    ) -> std::io::Result<bool> {
+        self.change(AuditAction::Block, domain, actor, reason)
+    }
+
+    /// Unblocks the domain. Returns `false` if it was not blocked.
+    pub fn unblock(
+        &self,
+        domain: &str,
+        actor: &str,
+        reason: Option<String>,
+    ) -> std::io::Result<bool> {
+        self.change(AuditAction::Unblock, domain, actor, reason)
+    }
+
+    /// Applies the change to a copy of the blocklist, and only keeps it once it is written, so
+    /// that a failed write leaves the blocklist as it is on disk.
+    fn change(
+        &self,
+        action: AuditAction,
+        domain: &str,
+        actor: &str,
+        reason: Option<String>,
+    ) -> std::io::Result<bool> {
+        let domain = normalize(domain);
+        let mut data = self.data.lock().unwrap();
+        let mut changed_data = data.clone();
+        let changed = match action {
+            AuditAction::Block => changed_data.domains.insert(domain.clone()),
+            AuditAction::Unblock => changed_data.domains.remove(&domain),
+        };
+        if !changed {
+            return Ok(false);
+        }
+
+        changed_data.audit.push(AuditEntry {
+            timestamp: unix_timestamp(),
+            action,
+            domain,
+            actor: actor.to_string(),
+            reason,
+        });
+        let contents = serde_json::to_string_pretty(&changed_data)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        std::fs::write(&self.path, contents)?;
+        *data = changed_data;
+        Ok(true)
+    }
+}
+
+/// The host of `domain`, as it is stored: trimmed, lowercased, and without a port or a trailing
+/// dot. IPv6 addresses keep their brackets.
+fn normalize(domain: &str) -> String {
+    let domain = domain.trim().to_lowercase();
+    let host = match domain
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+    {
+        Some((address, _)) => format!("[{address}]"),
+        None if domain.matches(':').count() == 1 => domain.split(':').next().unwrap().to_string(),
+        None => domain,
+    };
+    host.trim_end_matches('.').to_string()
+}
@@ -0,0 +1,262 @@
+use crate::metrics::Metrics;
+use crate::outbound;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use url::Url;
+
+/// Delivery attempts per callback before giving up.
+const MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first retry, doubled for every retry after it.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What an app's `callbackUrl` is sent when the owner approves a verification.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationResult {
+    /// Identifies the grant, for polling its status.
+    pub grant_id: String,
+    pub feed_url: Url,
+    pub decrypted_string: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    /// Unix timestamp of the approval.
+    pub verified_at: u64,
+}
+
+/// What an app's `callbackUrl` is sent when the owner revokes a verification.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrantRevocation {
+    pub grant_id: String,
+    pub feed_url: Url,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    /// Unix timestamp of the revocation.
+    pub revoked_at: u64,
+}
+
+/// Posts verification results to apps' `callbackUrl`s, so that the result reaches the app even
+/// if the owner's browser never follows the redirect.
+///
+/// Only apps whose domain the admin registered may pass a `callbackUrl`, and registering issues
+/// the domain its own secret. Each delivery names its event in `X-Verify-Event`,
+/// `verification.approved` or `grant.revoked`. It is signed with HMAC-SHA256 over
+/// `<timestamp>.<body>` using the secret of the callback's domain, so that no app can forge
+/// another's, and retried with exponential backoff until the app responds with a 2xx status.
+///
+/// Registrations are persisted as JSON, secrets included, so the file has to be kept private.
+#[derive(Clone)]
+pub struct Callbacks {
+    client: reqwest::Client,
+    /// Whether callbacks may go to loopback hosts, for local testing.
+    allow_loopback: bool,
+    path: PathBuf,
+    registrations: Arc<Mutex<BTreeMap<String, Registration>>>,
+    metrics: Metrics,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Registration {
+    /// Hex-encoded.
+    secret: String,
+    /// Seconds since the Unix epoch.
+    registered_at: u64,
+}
+
+/// A domain registered for callbacks, as listed to the admin.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisteredDomain {
+    pub domain: String,
+    /// Seconds since the Unix epoch when its current secret was issued.
+    pub registered_at: u64,
+}
+
+impl Callbacks {
+    /// Loads the registrations from `path`, starting with none if the file does not exist yet.
+    pub fn load(
+        path: impl Into<PathBuf>,
+        allow_loopback: bool,
+        proxy: Option<&outbound::Proxy>,
+        metrics: Metrics,
+    ) -> std::io::Result<Self> {
+        let path = path.into();
+        let registrations = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        let client = outbound::client_builder(0, allow_loopback, proxy)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("failed to build HTTP client");
+        Ok(Callbacks {
+            client,
+            allow_loopback,
+            path,
+            registrations: Arc::new(Mutex::new(registrations)),
+            metrics,
+        })
+    }
+
+    /// Whether apps on `domain`, `host` or `host:port` as in their `returnUrl`, may pass a
+    /// `callbackUrl` there.
+    pub fn is_registered(&self, domain: &str) -> bool {
+        self.registrations.lock().unwrap().contains_key(domain)
+    }
+
+    /// Whether a callback may be posted to `callback_url`, as far as the URL itself tells. See
+    /// [`outbound::allows`].
+    pub fn allows(&self, callback_url: &Url) -> bool {
+        outbound::allows(callback_url, self.allow_loopback)
+    }
+
+    pub fn registered(&self) -> Vec<RegisteredDomain> {
+        self.registrations
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(domain, registration)| RegisteredDomain {
+                domain: domain.clone(),
+                registered_at: registration.registered_at,
+            })
+            .collect()
+    }
+
+    /// Registers `domain` with a new secret, replacing any it had. Returns the secret and whether
+    /// the domain is new.
+    pub fn register(&self, domain: &str) -> std::io::Result<(SecretString, bool)> {
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        let secret = hex(&secret);
+
+        let mut registrations = self.registrations.lock().unwrap();
+        let previous = registrations.insert(
+            domain.to_string(),
+            Registration {
+                secret: secret.clone(),
+                registered_at: crate::unix_timestamp(),
+            },
+        );
+        self.save(&registrations)?;
+        Ok((SecretString::new(secret), previous.is_none()))
+    }
+
+    /// Returns `false` if the domain wasn't registered.
+    pub fn unregister(&self, domain: &str) -> std::io::Result<bool> {
+        let mut registrations = self.registrations.lock().unwrap();
+        if registrations.remove(domain).is_none() {
+            return Ok(false);
+        }
+        self.save(&registrations)?;
+        Ok(true)
+    }
+
+    fn save(&self, registrations: &BTreeMap<String, Registration>) -> std::io::Result<()> {
+        let contents = serde_json::to_string_pretty(registrations)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        std::fs::write(&self.path, contents)
+    }
+
+    /// The secret of the callback's domain, if it is still registered.
+    fn secret(&self, callback_url: &Url) -> Option<SecretString> {
+        let host = callback_url.host_str()?;
+        let domain = match callback_url.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        };
+        let registrations = self.registrations.lock().unwrap();
+        let registration = registrations.get(&domain)?;
+        Some(SecretString::new(registration.secret.clone()))
+    }
+
+    /// Delivers the event in the background.
+    pub fn deliver(&self, callback_url: Url, event: &'static str, payload: &impl Serialize) {
+        if !self.allows(&callback_url) {
+            tracing::warn!(%callback_url, event, "callback to a disallowed address dropped");
+            return;
+        }
+        let Some(secret) = self.secret(&callback_url) else {
+            tracing::warn!(%callback_url, event, "callback to an unregistered domain dropped");
+            return;
+        };
+        let body = serde_json::to_string(payload).expect("callback payload serializes");
+        let mut delivery_id = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut delivery_id);
+        let delivery_id = hex(&delivery_id);
+
+        let client = self.client.clone();
+        let metrics = self.metrics.clone();
+        tokio::spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+            for attempt in 1..=MAX_ATTEMPTS {
+                // Signed afresh for every attempt, so that the timestamp stays current.
+                let timestamp = crate::unix_timestamp().to_string();
+                let response = client
+                    .post(callback_url.clone())
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .header("X-Verify-Event", event)
+                    .header("X-Verify-Delivery", &delivery_id)
+                    .header("X-Verify-Timestamp", &timestamp)
+                    .header(
+                        "X-Verify-Signature",
+                        format!("sha256={}", signature(&secret, &timestamp, &body)),
+                    )
+                    .body(body.clone())
+                    .send()
+                    .await;
+
+                match response {
+                    Ok(response) if response.status().is_success() => {
+                        tracing::info!(%callback_url, event, attempt, "callback delivered");
+                        metrics
+                            .callback_deliveries
+                            .with_label_values(&["delivered"])
+                            .inc();
+                        return;
+                    }
+                    Ok(response) => tracing::warn!(
+                        %callback_url,
+                        attempt,
+                        status = %response.status(),
+                        "callback rejected"
+                    ),
+                    Err(e) => tracing::warn!(%callback_url, attempt, "callback failed: {e}"),
+                }
+
+                if attempt < MAX_ATTEMPTS {
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+            }
+            tracing::error!(%callback_url, "callback abandoned after {MAX_ATTEMPTS} attempts");
+            metrics
+                .callback_deliveries
+                .with_label_values(&["abandoned"])
+                .inc();
+        });
+    }
+}
+
+fn signature(secret: &SecretString, timestamp: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex(&mac.finalize().into_bytes())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
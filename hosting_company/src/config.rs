@@ -0,0 +1,238 @@
+use crate::outbound;
+use crate::security::DEFAULT_CONTENT_SECURITY_POLICY;
+use axum::http::{HeaderMap, HeaderValue};
+use secrecy::SecretString;
+use url::{Position, Url};
+
+/// How long errors are shown before the user is sent back to the app, unless configured.
+pub const DEFAULT_ERROR_REDIRECT_DELAY_SECS: u64 = 10;
+
+/// Runtime settings, read from environment variables with sensible defaults for the demo.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Whether a reported directory domain is blocked from starting new verifications until an
+    /// admin reviews it.
+    pub auto_block_reported_domains: bool,
+    /// Verification requests allowed per client IP per minute.
+    pub verify_rate_limit_per_ip: u32,
+    /// Verification requests allowed per podcast per minute.
+    pub verify_rate_limit_per_slug: u32,
+    /// Failed owner logins per email address or client IP before logins are locked.
+    pub login_lockout_threshold: u32,
+    /// How long the first lockout lasts. Every further failed login doubles it, up to an hour.
+    pub login_lockout_secs: u64,
+    /// Key pairs of each size generated ahead of time, for key rotations.
+    pub key_pool_size: usize,
+    /// How long an owner who approved sees a confirmation before being sent back to the app.
+    /// With zero they are redirected straight away, without the confirmation.
+    pub redirect_delay_secs: u64,
+    /// How long an error during verification is shown before the user is sent back to the app.
+    pub error_redirect_delay_secs: u64,
+    /// Where the directory domain blocklist is persisted.
+    pub blocklist_path: String,
+    /// Where the log of verification attempts shown to owners is persisted.
+    pub attempts_path: String,
+    /// Where the verifications owners have granted are persisted.
+    pub grants_path: String,
+    /// Where the former slugs of renamed podcasts are persisted.
+    pub slug_history_path: String,
+    /// Where the domains registered for callbacks and their secrets are persisted.
+    pub callbacks_path: String,
+    /// Password for the admin UI and API. Admin routes are disabled when unset.
+    pub admin_token: Option<SecretString>,
+    /// How long in-flight requests may take to finish after a shutdown signal.
+    pub shutdown_drain_timeout_secs: u64,
+    /// PEM certificate chain. Together with `tls_key_path`, enables HTTPS.
+    pub tls_cert_path: Option<String>,
+    /// PEM private key for `tls_cert_path`.
+    pub tls_key_path: Option<String>,
+    /// Base URL the server is reachable at, used to build absolute URLs such as `verifyUrl`.
+    /// Always ends with a slash.
+    pub public_base_url: Url,
+    /// Whether to build public URLs from `X-Forwarded-Proto` and `X-Forwarded-Host` when a
+    /// reverse proxy sets them.
+    pub trust_forwarded_headers: bool,
+    /// Whether to fetch the websites of apps asking to verify, to show their name and icon on
+    /// the consent screen.
+    pub fetch_app_identity: bool,
+    /// Whether `<podcast:verify>` tags carry a `keyFingerprint` attribute next to `publicKey`.
+    pub feed_key_fingerprints: bool,
+    /// Enables developer pages such as the protocol trace viewer, and looking up the identity of
+    /// apps on loopback hosts.
+    pub dev_mode: bool,
+    /// Domains apps may send owners back to, as `host` or `host:port`. Any domain that isn't
+    /// blocked may when it is empty.
+    pub return_url_allowlist: Vec<String>,
+    /// `Content-Security-Policy` of HTML pages.
+    pub content_security_policy: String,
+    /// `X-Frame-Options` of HTML pages, for browsers that don't know the policy's
+    /// `frame-ancestors`.
+    pub frame_options: String,
+    /// `Referrer-Policy` of HTML pages.
+    pub referrer_policy: String,
+    /// `max-age` of the `Strict-Transport-Security` header, sent when serving HTTPS.
+    pub hsts_max_age_secs: u64,
+    /// Proxy for callbacks and other requests to URLs apps choose, instead of the proxies in
+    /// the environment.
+    pub outbound_proxy: Option<outbound::Proxy>,
+    /// JSON file with more podcasts and settings overriding these, reloaded while the server
+    /// runs when it changes or on `SIGHUP`.
+    pub config_path: Option<String>,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let tls_cert_path = std::env::var("TLS_CERT_PATH").ok();
+        let default_base_url = match tls_cert_path {
+            Some(_) => "https://localhost:8081/",
+            None => "http://localhost:8081/",
+        };
+
+        Config {
+            auto_block_reported_domains: env_bool("AUTO_BLOCK_REPORTED_DOMAINS", false),
+            verify_rate_limit_per_ip: env_parse("VERIFY_RATE_LIMIT_PER_IP", 30),
+            verify_rate_limit_per_slug: env_parse("VERIFY_RATE_LIMIT_PER_SLUG", 120),
+            login_lockout_threshold: env_parse("LOGIN_LOCKOUT_THRESHOLD", 5),
+            login_lockout_secs: env_parse("LOGIN_LOCKOUT_SECS", 60),
+            key_pool_size: env_parse("KEY_POOL_SIZE", 1),
+            redirect_delay_secs: env_parse("REDIRECT_DELAY_SECS", 0),
+            error_redirect_delay_secs: env_parse(
+                "ERROR_REDIRECT_DELAY_SECS",
+                DEFAULT_ERROR_REDIRECT_DELAY_SECS,
+            ),
+            blocklist_path: env_parse("BLOCKLIST_PATH", String::from("blocklist.json")),
+            attempts_path: env_parse("ATTEMPTS_PATH", String::from("attempts.jsonl")),
+            grants_path: env_parse("GRANTS_PATH", String::from("grants.json")),
+            slug_history_path: env_parse("SLUG_HISTORY_PATH", String::from("slug_history.json")),
+            callbacks_path: env_parse("CALLBACKS_PATH", String::from("callbacks.json")),
+            admin_token: env_secret("ADMIN_TOKEN"),
+            shutdown_drain_timeout_secs: env_parse("SHUTDOWN_DRAIN_TIMEOUT_SECS", 10),
+            tls_cert_path,
+            tls_key_path: std::env::var("TLS_KEY_PATH").ok(),
+            public_base_url: with_trailing_slash(env_parse(
+                "PUBLIC_BASE_URL",
+                Url::parse(default_base_url).unwrap(),
+            )),
+            trust_forwarded_headers: env_bool("TRUST_FORWARDED_HEADERS", false),
+            fetch_app_identity: env_bool("FETCH_APP_IDENTITY", true),
+            feed_key_fingerprints: env_bool("FEED_KEY_FINGERPRINTS", false),
+            dev_mode: env_bool("DEV_MODE", false),
+            return_url_allowlist: env_list("RETURN_URL_ALLOWLIST"),
+            content_security_policy: env_header(
+                "CONTENT_SECURITY_POLICY",
+                DEFAULT_CONTENT_SECURITY_POLICY,
+            ),
+            frame_options: env_header("FRAME_OPTIONS", "DENY"),
+            referrer_policy: env_header("REFERRER_POLICY", "no-referrer"),
+            hsts_max_age_secs: env_parse("HSTS_MAX_AGE_SECS", 365 * 24 * 60 * 60),
+            outbound_proxy: env_proxy("OUTBOUND_PROXY", "OUTBOUND_NO_PROXY"),
+            config_path: std::env::var("CONFIG_PATH").ok(),
+        }
+    }
+
+    /// Whether apps may send owners back to the `returnUrl` domain.
+    pub fn allows_return_to(&self, domain_name: &str) -> bool {
+        self.return_url_allowlist.is_empty()
+            || self
+                .return_url_allowlist
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(domain_name))
+    }
+
+    /// The public base URL for a request, taking the reverse proxy's `X-Forwarded-*` headers
+    /// into account if they are trusted.
+    pub fn public_base_url(&self, headers: &HeaderMap) -> Url {
+        if !self.trust_forwarded_headers {
+            return self.public_base_url.clone();
+        }
+
+        let forwarded = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let scheme = forwarded("x-forwarded-proto")
+            .unwrap_or_else(|| self.public_base_url.scheme().to_string());
+        let host = forwarded("x-forwarded-host").unwrap_or_else(|| {
+            self.public_base_url[Position::BeforeHost..Position::AfterPort].to_string()
+        });
+
+        Url::parse(&format!("{scheme}://{host}{}", self.public_base_url.path()))
+            .unwrap_or_else(|_| self.public_base_url.clone())
+    }
+}
+
+/// Makes relative URLs resolve beneath the base URL's path rather than next to it.
+pub(crate) fn with_trailing_slash(mut url: Url) -> Url {
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    url
+}
+
+fn env_bool(name: &str, default: bool) -> bool {
+    match std::env::var(name) {
+        Ok(value) => matches!(value.to_lowercase().as_str(), "1" | "true" | "yes"),
+        Err(_) => default,
+    }
+}
+
+/// A header value, which may be empty for no header.
+fn env_header(name: &str, default: &str) -> String {
+    let value = std::env::var(name).unwrap_or_else(|_| default.to_string());
+    if HeaderValue::from_str(&value).is_err() {
+        panic!("invalid value for {name}: {value:?}");
+    }
+    value
+}
+
+/// A comma-separated list, empty if unset.
+fn env_list(name: &str) -> Vec<String> {
+    std::env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid value for {name}: {value:?}")),
+        Err(_) => default,
+    }
+}
+
+/// A proxy from the URL in `name` and the hosts to bypass it for in `no_proxy_name`, if the URL is
+/// set.
+fn env_proxy(name: &str, no_proxy_name: &str) -> Option<outbound::Proxy> {
+    let value = std::env::var(name).ok()?;
+    let url = Url::parse(&value)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+        .unwrap_or_else(|| panic!("invalid value for {name}: not an http or https URL"));
+    Some(outbound::Proxy {
+        url,
+        no_proxy: std::env::var(no_proxy_name).ok(),
+    })
+}
+
+/// Reads a secret from `NAME`, or from the file named by `NAME_FILE` (as used by Docker and
+/// Kubernetes secrets). A trailing newline in the file is ignored.
+fn env_secret(name: &str) -> Option<SecretString> {
+    if let Ok(value) = std::env::var(name) {
+        return Some(SecretString::new(value));
+    }
+    let path = std::env::var(format!("{name}_FILE")).ok()?;
+    let contents = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("failed to read {name}_FILE ({path}): {e}"));
+    Some(SecretString::new(
+        contents.trim_end_matches(['\n', '\r']).to_string(),
+    ))
+}
@@ -0,0 +1,56 @@
+//! Checking owners' credentials in time that doesn't depend on them, so that how long a failed
+//! login takes tells neither whether the email address is an owner's nor how much of the
+//! password was right. Passwords are only kept as Argon2id hashes.
+
+use crate::Customer;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+/// Whether `given` is `expected`. Both are hashed first, so that the comparison takes as long
+/// whatever their lengths, and the hashes are compared in constant time.
+pub fn matches(given: &str, expected: &str) -> bool {
+    let given = Sha256::digest(given.as_bytes());
+    let expected = Sha256::digest(expected.as_bytes());
+    given.as_slice().ct_eq(expected.as_slice()).into()
+}
+
+/// Hashes a password with Argon2id and a random salt, as a PHC string that also records the
+/// parameters, so that hashes stay verifiable if the defaults change.
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("the default Argon2 parameters are valid")
+        .to_string()
+}
+
+/// Whether `password` is the one `password_hash` was made from. A malformed hash matches nothing.
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash).is_ok_and(|password_hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &password_hash)
+            .is_ok()
+    })
+}
+
+impl Customer {
+    /// A customer whose password is hashed straight away.
+    pub(crate) fn new(email: &str, password: &str) -> Customer {
+        Customer {
+            email: email.to_string(),
+            password_hash: hash_password(password),
+        }
+    }
+
+    /// Whether the email address and password are the customer's. The password is checked even
+    /// when the email address is wrong, so that an unknown address fails as slowly as a wrong
+    /// password.
+    pub fn verify(&self, email: &str, password: &str) -> bool {
+        let email_matches = matches(email, &self.email);
+        let password_matches = verify_password(password, &self.password_hash);
+        email_matches & password_matches
+    }
+}
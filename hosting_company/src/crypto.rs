@@ -0,0 +1,463 @@
+use crate::error::AppError;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use rsa::traits::PublicKeyParts;
+use rsa::{Oaep, Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey};
+use sha2::Sha256;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+use tokio::sync::Notify;
+
+/// Length of the AES-GCM nonce that follows the wrapped key in an envelope.
+const NONCE_LEN: usize = 12;
+/// Length of the AES-GCM authentication tag that ends an envelope.
+const TAG_LEN: usize = 16;
+/// Longest `encryptedString` accepted, in base64 characters. Envelopes make any length possible,
+/// so this bounds the work an app can make the host do per request.
+pub const MAX_ENCRYPTED_STRING_LEN: usize = 8 * 1024;
+
+/// How apps encrypt `encryptedString` for a podcast.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CryptoConfig {
+    /// Size of the host key advertised in the feed.
+    pub key_bits: usize,
+    pub padding: Padding,
+}
+
+impl Default for CryptoConfig {
+    fn default() -> Self {
+        CryptoConfig {
+            key_bits: 2048,
+            padding: Padding::Pkcs1v15,
+        }
+    }
+}
+
+/// RSA encryption padding, advertised in the `encryption` attribute of `<podcast:verify>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Padding {
+    Pkcs1v15,
+    /// OAEP with SHA-256 for both the hash and MGF1.
+    OaepSha256,
+}
+
+impl Padding {
+    /// The padding's JSON Web Algorithms (RFC 7518) name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Padding::Pkcs1v15 => "RSA1_5",
+            Padding::OaepSha256 => "RSA-OAEP-256",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct KeyPair {
+    pub private_key: RsaPrivateKey,
+    pub public_key: RsaPublicKey,
+}
+
+impl KeyPair {
+    /// Whether the private key is consistent and matches the public key.
+    fn is_valid(&self) -> bool {
+        self.private_key.validate().is_ok()
+            && RsaPublicKey::from(&self.private_key) == self.public_key
+    }
+
+    fn generate(bits: usize) -> KeyPair {
+        let private_key =
+            RsaPrivateKey::new(&mut rand::thread_rng(), bits).expect("failed to generate a key");
+        KeyPair {
+            public_key: RsaPublicKey::from(&private_key),
+            private_key,
+        }
+    }
+}
+
+/// How many keys of each size feeds advertise: the current one and, after a rotation, the one
+/// before it, so that apps that fetched the feed before the rotation can still verify.
+pub const ADVERTISED_KEYS: usize = 2;
+
+/// Size of the key [`SignedVerification`](crate::handshake::SignedVerification)s are signed
+/// with.
+pub const SIGNING_KEY_BITS: usize = 2048;
+
+/// The host's key pairs for each key size in use, most recent first, and the key it signs
+/// verifications with.
+#[derive(Clone)]
+pub struct Keys {
+    inner: Arc<RwLock<KeysInner>>,
+}
+
+struct KeysInner {
+    pairs: BTreeMap<usize, Vec<KeyPair>>,
+    /// Only ever used to sign, and the encryption keys only to decrypt, so that an app can't get
+    /// a signature out of the host by sending something to decrypt or the other way round.
+    signing: Option<KeyPair>,
+    /// When the keys were last generated or rotated.
+    updated_at: SystemTime,
+    /// Whether every pair was consistent when last checked, which is whenever they change, as
+    /// checking takes too long to do on every health check.
+    valid: bool,
+}
+
+impl KeysInner {
+    fn check(&mut self) {
+        self.valid = self
+            .pairs
+            .values()
+            .flatten()
+            .chain(&self.signing)
+            .all(KeyPair::is_valid);
+    }
+}
+
+impl Keys {
+    /// No keys at all, for a server that loads them from a [`KeyPool`] once it is running.
+    pub fn empty() -> Keys {
+        Keys {
+            inner: Arc::new(RwLock::new(KeysInner {
+                pairs: BTreeMap::new(),
+                signing: None,
+                updated_at: SystemTime::now(),
+                valid: true,
+            })),
+        }
+    }
+
+    /// Generates a key pair for each of the given sizes, and a signing key.
+    pub fn generate(key_bits: impl IntoIterator<Item = usize>) -> Keys {
+        let mut pairs = BTreeMap::new();
+        for bits in key_bits {
+            pairs
+                .entry(bits)
+                .or_insert_with(|| vec![KeyPair::generate(bits)]);
+        }
+        let mut inner = KeysInner {
+            pairs,
+            signing: Some(KeyPair::generate(SIGNING_KEY_BITS)),
+            updated_at: SystemTime::now(),
+            valid: false,
+        };
+        inner.check();
+        Keys {
+            inner: Arc::new(RwLock::new(inner)),
+        }
+    }
+
+    /// The key pairs advertised for the configuration's key size, most recent first.
+    pub fn get(&self, config: &CryptoConfig) -> Result<Vec<KeyPair>, AppError> {
+        self.inner
+            .read()
+            .unwrap()
+            .pairs
+            .get(&config.key_bits)
+            .cloned()
+            .ok_or_else(|| {
+                AppError::CryptoFailure(format!("no {}-bit key loaded", config.key_bits))
+            })
+    }
+
+    /// The key pair verifications are signed with, which feeds advertise in `signingKey`.
+    pub fn signing(&self) -> Result<KeyPair, AppError> {
+        self.inner
+            .read()
+            .unwrap()
+            .signing
+            .clone()
+            .ok_or_else(|| AppError::CryptoFailure(String::from("no signing key loaded")))
+    }
+
+    /// Makes the pair the signing key. It isn't rotated with the encryption keys, as a
+    /// verification is checked against the feed right after it is issued.
+    pub fn install_signing(&self, pair: KeyPair) {
+        let mut inner = self.inner.write().unwrap();
+        inner.signing = Some(pair);
+        inner.updated_at = SystemTime::now();
+        inner.check();
+    }
+
+    pub fn updated_at(&self) -> SystemTime {
+        self.inner.read().unwrap().updated_at
+    }
+
+    /// Whether a key of the size is loaded.
+    pub fn has(&self, key_bits: usize) -> bool {
+        self.inner.read().unwrap().pairs.contains_key(&key_bits)
+    }
+
+    /// Makes the pair the current key of its size, which feeds advertise first from now on.
+    /// The oldest key of the size stops being advertised and accepted once there are more than
+    /// [`ADVERTISED_KEYS`].
+    pub fn install(&self, pair: KeyPair) {
+        let bits = pair.public_key.size() * 8;
+        let mut inner = self.inner.write().unwrap();
+        let pairs = inner.pairs.entry(bits).or_default();
+        pairs.insert(0, pair);
+        pairs.truncate(ADVERTISED_KEYS);
+        inner.updated_at = SystemTime::now();
+        inner.check();
+    }
+
+    /// Installs a new key pair of every size in use, taken from the pool.
+    pub async fn rotate(&self, pool: &KeyPool) {
+        let sizes: Vec<usize> = self.inner.read().unwrap().pairs.keys().copied().collect();
+        for bits in sizes {
+            self.install(pool.take(bits).await);
+        }
+    }
+
+    /// Whether every private key was consistent and matched its public key when installed.
+    pub fn valid(&self) -> bool {
+        self.inner.read().unwrap().valid
+    }
+}
+
+/// Key pairs generated ahead of time on a background task, so that installing a key doesn't wait
+/// for the seconds RSA key generation takes.
+#[derive(Clone)]
+pub struct KeyPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    pairs: Mutex<BTreeMap<usize, VecDeque<KeyPair>>>,
+    /// Pairs kept ready of each size.
+    target: usize,
+    /// Wakes the background task when a pair is taken.
+    taken: Notify,
+}
+
+impl KeyPool {
+    /// Starts filling the pool with `target` pairs of each of the sizes. With a target of zero,
+    /// nothing is generated ahead of time. Must be called within a Tokio runtime.
+    pub fn start(key_bits: impl IntoIterator<Item = usize>, target: usize) -> KeyPool {
+        let pool = KeyPool {
+            inner: Arc::new(PoolInner {
+                pairs: Mutex::new(
+                    key_bits
+                        .into_iter()
+                        .map(|bits| (bits, VecDeque::new()))
+                        .collect(),
+                ),
+                target,
+                taken: Notify::new(),
+            }),
+        };
+        if target > 0 {
+            tokio::spawn(pool.clone().refill());
+        }
+        pool
+    }
+
+    /// A pair of the given size: a pre-generated one if the pool has one, or else a new one,
+    /// generated on a blocking thread.
+    pub async fn take(&self, key_bits: usize) -> KeyPair {
+        let pair = self
+            .inner
+            .pairs
+            .lock()
+            .unwrap()
+            .get_mut(&key_bits)
+            .and_then(VecDeque::pop_front);
+        self.inner.taken.notify_one();
+        match pair {
+            Some(pair) => pair,
+            None => generate(key_bits).await,
+        }
+    }
+
+    /// Pairs of the size ready to be taken.
+    pub fn available(&self, key_bits: usize) -> usize {
+        self.inner
+            .pairs
+            .lock()
+            .unwrap()
+            .get(&key_bits)
+            .map_or(0, VecDeque::len)
+    }
+
+    async fn refill(self) {
+        loop {
+            let missing = self
+                .inner
+                .pairs
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(_, pairs)| pairs.len() < self.inner.target)
+                .map(|(bits, _)| *bits);
+            match missing {
+                Some(bits) => {
+                    let pair = generate(bits).await;
+                    self.inner
+                        .pairs
+                        .lock()
+                        .unwrap()
+                        .entry(bits)
+                        .or_default()
+                        .push_back(pair);
+                }
+                None => self.inner.taken.notified().await,
+            }
+        }
+    }
+}
+
+async fn generate(key_bits: usize) -> KeyPair {
+    tokio::task::spawn_blocking(move || KeyPair::generate(key_bits))
+        .await
+        .expect("key generation panicked")
+}
+
+/// Why an `encryptedString` couldn't be decrypted. Whatever an app sends, decryption ends in one
+/// of these rather than a panic, and each is the app's mistake, answered with `400 Bad Request`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecryptError {
+    /// Not base64 in any of the variants [`decode_encrypted_string`] accepts.
+    Base64,
+    /// Neither a single RSA block the size of the key nor long enough to be an envelope.
+    Length { len: usize, key_len: usize },
+    /// The RSA padding doesn't check out: the wrong key or padding, or a corrupted ciphertext.
+    Rsa,
+    /// The envelope's AES-GCM tag doesn't verify, or its wrapped key isn't an AES-256 key.
+    Envelope,
+    /// The plaintext isn't UTF-8.
+    Utf8,
+}
+
+impl fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecryptError::Base64 => write!(f, "not base64"),
+            DecryptError::Length { len, key_len } => write!(
+                f,
+                "{len} bytes is neither {key_len} bytes nor long enough for an envelope"
+            ),
+            DecryptError::Rsa => write!(f, "RSA decryption failed"),
+            DecryptError::Envelope => write!(f, "the envelope doesn't open"),
+            DecryptError::Utf8 => write!(f, "the plaintext isn't UTF-8"),
+        }
+    }
+}
+
+impl From<DecryptError> for AppError {
+    fn from(_: DecryptError) -> Self {
+        AppError::InvalidParam("encryptedString")
+    }
+}
+
+/// Decrypts the `encryptedString` an app sent: the base64-encoded ciphertext of a UTF-8 string,
+/// encrypted to the public key in the feed's `<podcast:verify>` tag with the padding it names.
+/// Any of the base64 variants [`decode_encrypted_string`] accepts will do.
+///
+/// Plain RSA can only encrypt a few hundred bytes, so ciphertexts longer than the key are taken
+/// to be envelopes (see [`seal_envelope`]) instead.
+pub fn decrypt(
+    private_key: &RsaPrivateKey,
+    padding: Padding,
+    encrypted_string: &str,
+) -> Result<String, DecryptError> {
+    let ciphertext = decode_ciphertext(encrypted_string, private_key.size())?;
+    let plaintext = if ciphertext.len() > private_key.size() {
+        open_envelope(private_key, &ciphertext)?
+    } else {
+        match padding {
+            Padding::Pkcs1v15 => private_key.decrypt(Pkcs1v15Encrypt, &ciphertext),
+            Padding::OaepSha256 => private_key.decrypt(Oaep::new::<Sha256>(), &ciphertext),
+        }
+        .map_err(|_| DecryptError::Rsa)?
+    };
+    String::from_utf8(plaintext).map_err(|_| DecryptError::Utf8)
+}
+
+/// Decodes `encryptedString` and checks that it is as long as a ciphertext for a key of
+/// `key_len` bytes can be: exactly one RSA block, or an envelope with room for the wrapped key,
+/// the nonce and the tag. Cheap enough to run before the owner is asked to sign in.
+pub fn decode_ciphertext(encrypted_string: &str, key_len: usize) -> Result<Vec<u8>, DecryptError> {
+    let ciphertext = decode_encrypted_string(encrypted_string).ok_or(DecryptError::Base64)?;
+    let len = ciphertext.len();
+    if len != key_len && len < key_len + NONCE_LEN + TAG_LEN {
+        return Err(DecryptError::Length { len, key_len });
+    }
+    Ok(ciphertext)
+}
+
+/// Decodes `encryptedString` whichever way an app base64-encoded it: base64url or standard
+/// base64, with or without padding. Padding that arrives still percent-encoded (`%3D`), and `+`
+/// that became a space because the app didn't percent-encode it in the query, are put back.
+pub fn decode_encrypted_string(encrypted_string: &str) -> Option<Vec<u8>> {
+    let encoded = encrypted_string
+        .replace("%3D", "=")
+        .replace("%3d", "=")
+        .replace(' ', "+");
+    let encoded = encoded.trim().trim_end_matches('=');
+    URL_SAFE_NO_PAD
+        .decode(encoded)
+        .or_else(|_| STANDARD_NO_PAD.decode(encoded))
+        .ok()
+}
+
+/// Decrypts `encryptedString` with each of the advertised key pairs in turn, as an app may have
+/// encrypted it to any key that was in the feed when it fetched it.
+pub fn decrypt_with_any(
+    pairs: &[KeyPair],
+    padding: Padding,
+    encrypted_string: &str,
+) -> Result<String, DecryptError> {
+    let mut result = Err(DecryptError::Rsa);
+    for pair in pairs {
+        result = decrypt(&pair.private_key, padding, encrypted_string);
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+/// Encrypts a plaintext of any length to `public_key`. A fresh AES-256-GCM key encrypts the
+/// plaintext and is itself encrypted with RSA-OAEP-256. The envelope is the wrapped key, then the
+/// nonce, then the AES-GCM ciphertext and tag.
+pub fn seal_envelope(public_key: &RsaPublicKey, plaintext: &[u8]) -> Result<Vec<u8>, AppError> {
+    let data_key = Aes256Gcm::generate_key(OsRng);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(&data_key)
+        .encrypt(&nonce, plaintext)
+        .map_err(|e| AppError::CryptoFailure(format!("failed to encrypt envelope: {e}")))?;
+    let wrapped_key = public_key
+        .encrypt(&mut OsRng, Oaep::new::<Sha256>(), &data_key)
+        .map_err(|e| AppError::CryptoFailure(format!("failed to wrap envelope key: {e}")))?;
+
+    let mut envelope = wrapped_key;
+    envelope.extend_from_slice(&nonce);
+    envelope.extend_from_slice(&ciphertext);
+    Ok(envelope)
+}
+
+/// Decrypts an envelope made by [`seal_envelope`].
+pub fn open_envelope(
+    private_key: &RsaPrivateKey,
+    envelope: &[u8],
+) -> Result<Vec<u8>, DecryptError> {
+    let key_len = private_key.size();
+    if envelope.len() < key_len + NONCE_LEN + TAG_LEN {
+        return Err(DecryptError::Length {
+            len: envelope.len(),
+            key_len,
+        });
+    }
+    let (wrapped_key, rest) = envelope.split_at(key_len);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let data_key = private_key
+        .decrypt(Oaep::new::<Sha256>(), wrapped_key)
+        .map_err(|_| DecryptError::Rsa)?;
+    Aes256Gcm::new_from_slice(&data_key)
+        .map_err(|_| DecryptError::Envelope)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| DecryptError::Envelope)
+}
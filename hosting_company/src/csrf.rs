@@ -0,0 +1,63 @@
+//! Tokens for the forms of the dashboard and the admin page. Browsers send HTTP Basic
+//! credentials with every request to the host, including a form another site posts, so the
+//! credentials alone don't show that the owner or admin meant to submit the form. Other sites
+//! can't read our pages, so they can't copy the token off them.
+//!
+//! Tokens are signed rather than stored, with a key made at startup, so the forms of pages
+//! rendered before a restart stop working.
+
+use crate::credentials;
+use crate::error::AppError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::Sha256;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct CsrfTokens {
+    key: Arc<[u8; 32]>,
+}
+
+impl Default for CsrfTokens {
+    fn default() -> Self {
+        let mut key = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        CsrfTokens { key: Arc::new(key) }
+    }
+}
+
+impl CsrfTokens {
+    /// The token for forms acting for `scope`: a podcast's [`owner_scope`] or [`ADMIN_SCOPE`].
+    pub fn token(&self, scope: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(self.key.as_slice()).expect("HMAC accepts any key");
+        mac.update(scope.as_bytes());
+        URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+    }
+
+    /// Fails unless `token` was issued for `scope`.
+    pub fn check(&self, scope: &str, token: &str) -> Result<(), AppError> {
+        if credentials::matches(token, &self.token(scope)) {
+            Ok(())
+        } else {
+            Err(AppError::CsrfRejected)
+        }
+    }
+}
+
+/// The scope of the forms on a podcast's dashboard.
+pub fn owner_scope(slug: &str) -> String {
+    format!("owner/{slug}")
+}
+
+/// The scope of the forms on the admin page.
+pub const ADMIN_SCOPE: &str = "admin";
+
+/// A form with nothing to send but the token.
+#[derive(Deserialize)]
+pub struct CsrfForm {
+    pub csrf: String,
+}
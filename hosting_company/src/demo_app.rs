@@ -0,0 +1,217 @@
+//! A minimal app for the demo, verifying podcasts the way a directory would: it reads the
+//! `<podcast:verify>` tag from the feed, sends the owner to `verifyUrl` with a challenge encrypted
+//! to the feed's key, and checks the `decryptedString` the host sends back to `returnUrl`.
+
+use crate::crypto::Padding;
+use crate::fetch::{FeedFetcher, FetchConfig};
+use crate::handshake;
+use crate::tag::{feed_attr_to_public_key, verify_attributes};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::get;
+use axum::Router;
+use maud::{html, Markup, DOCTYPE};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// Verifications the app has started and not yet seen come back, at most this many.
+const MAX_PENDING: usize = 1000;
+
+#[derive(Clone)]
+struct DemoApp(Arc<Inner>);
+
+struct Inner {
+    /// Where the app is reachable, for its `returnUrl`.
+    base_url: Url,
+    /// The feed the form is filled in with.
+    default_feed_url: Url,
+    fetcher: FeedFetcher,
+    /// The challenge and feed of each verification in progress, by its `state`.
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+struct Pending {
+    feed_url: Url,
+    challenge: String,
+}
+
+/// The app's routes. `base_url` is where it is served, and `default_feed_url` the feed it offers
+/// to verify.
+pub fn router(base_url: Url, default_feed_url: Url) -> Router {
+    let fetcher = FeedFetcher::new(FetchConfig::default()).expect("failed to build HTTP client");
+    let app = DemoApp(Arc::new(Inner {
+        base_url,
+        default_feed_url,
+        fetcher,
+        pending: Mutex::new(HashMap::new()),
+    }));
+    Router::new()
+        .route("/", get(root))
+        .route("/verify", get(verify))
+        .route("/verified", get(verified))
+        .with_state(app)
+}
+
+async fn root(State(app): State<DemoApp>) -> Html<String> {
+    page(
+        "Demo App",
+        html! {
+            h1 { "Demo App" }
+            p { "Prove you own a podcast by approving this app on its host." }
+            form method="get" action="/verify" {
+                label for="feedUrl" { "Feed URL" }
+                " "
+                input type="url" id="feedUrl" name="feedUrl" size="50" required
+                    value=(app.0.default_feed_url);
+                " "
+                button type="submit" { "Verify" }
+            }
+        },
+    )
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyParams {
+    feed_url: Url,
+}
+
+/// Starts a verification: sends the owner to the feed's `verifyUrl` with a new challenge.
+async fn verify(
+    State(app): State<DemoApp>,
+    Query(params): Query<VerifyParams>,
+) -> Result<Redirect, Response> {
+    let feed = app
+        .0
+        .fetcher
+        .fetch(&params.feed_url)
+        .await
+        .map_err(|e| failure(StatusCode::BAD_GATEWAY, &format!("Can't fetch the feed: {e}")))?;
+    let tag = verify_attributes(&feed).ok_or_else(|| {
+        failure(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "The feed has no <podcast:verify> tag.",
+        )
+    })?;
+    let verify_url = tag
+        .get("verifyUrl")
+        .and_then(|url| Url::parse(url).ok())
+        .ok_or_else(|| failure(StatusCode::UNPROCESSABLE_ENTITY, "The verifyUrl is invalid."))?;
+    let public_key = tag
+        .get("publicKey")
+        .and_then(|key| feed_attr_to_public_key(key).ok())
+        .ok_or_else(|| failure(StatusCode::UNPROCESSABLE_ENTITY, "The publicKey is invalid."))?;
+    let padding = match tag.get("encryption").map(String::as_str) {
+        Some("RSA-OAEP-256") => Padding::OaepSha256,
+        _ => Padding::Pkcs1v15,
+    };
+
+    let challenge = random_hex();
+    let encrypted_string = handshake::encrypt(&public_key, padding, &challenge)
+        .map_err(|e| failure(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+    let state = random_hex();
+    {
+        let mut pending = app.0.pending.lock().unwrap();
+        if pending.len() >= MAX_PENDING {
+            pending.clear();
+        }
+        pending.insert(
+            state.clone(),
+            Pending {
+                feed_url: params.feed_url,
+                challenge,
+            },
+        );
+    }
+
+    let return_url = app.0.base_url.join("verified").unwrap();
+    let mut location = verify_url;
+    location
+        .query_pairs_mut()
+        .append_pair("encryptedString", &encrypted_string)
+        .append_pair("returnUrl", return_url.as_str())
+        .append_pair("state", &state);
+    Ok(Redirect::to(location.as_str()))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifiedParams {
+    decrypted_string: Option<String>,
+    error: Option<String>,
+    state: Option<String>,
+}
+
+/// Where the host sends the owner back: the verification succeeded if the challenge came back
+/// decrypted.
+async fn verified(State(app): State<DemoApp>, Query(params): Query<VerifiedParams>) -> Response {
+    let pending = params
+        .state
+        .and_then(|state| app.0.pending.lock().unwrap().remove(&state));
+    let Some(pending) = pending else {
+        return failure(
+            StatusCode::BAD_REQUEST,
+            "This app didn't start that verification, or it already finished.",
+        );
+    };
+    if let Some(error) = params.error {
+        return failure(
+            StatusCode::FORBIDDEN,
+            &format!("The host didn't verify {}: {error}", pending.feed_url),
+        );
+    }
+    if params.decrypted_string.as_deref() != Some(pending.challenge.as_str()) {
+        return failure(
+            StatusCode::FORBIDDEN,
+            &format!(
+                "The host returned the wrong challenge for {}.",
+                pending.feed_url
+            ),
+        );
+    }
+    page(
+        "Verified",
+        html! {
+            h1 { "Verified" }
+            p { "You own " a href=(pending.feed_url) { (pending.feed_url) } "." }
+            p { a href="/" { "Verify another podcast" } }
+        },
+    )
+    .into_response()
+}
+
+fn failure(code: StatusCode, message: &str) -> Response {
+    let page = page(
+        "Not verified",
+        html! {
+            h1 { "Not verified" }
+            p { (message) }
+            p { a href="/" { "Try again" } }
+        },
+    );
+    (code, page).into_response()
+}
+
+fn page(title: &str, main: Markup) -> Html<String> {
+    Html(
+        html! {
+            (DOCTYPE)
+            html lang="en" {
+                head {
+                    meta charset="utf-8";
+                    title { (title) }
+                }
+                body { main { (main) } }
+            }
+        }
+        .into_string(),
+    )
+}
+
+fn random_hex() -> String {
+    let bytes: [u8; 16] = rand::random();
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -0,0 +1,23 @@
+//! The OpenAPI description of the verification endpoints, served at `/openapi.json` and browsable
+//! at `/docs`.
+
+use crate::error::ErrorBody;
+use crate::{ConsentForm, Decision};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "podcast:verify host",
+        description = "The host side of the `<podcast:verify>` protocol: apps read the verify URL and public keys from the feed, send the owner to the verify URL with an encrypted challenge, and get the decrypted challenge back at `returnUrl` once the owner approves."
+    ),
+    paths(crate::feed, crate::verify, crate::consent, crate::events::events),
+    components(schemas(ConsentForm, Decision, ErrorBody)),
+    tags((name = "verification", description = "Verifying that an app's user owns a podcast"))
+)]
+struct ApiDoc;
+
+pub fn swagger_ui() -> SwaggerUi {
+    SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi())
+}
@@ -0,0 +1,361 @@
+use crate::config::DEFAULT_ERROR_REDIRECT_DELAY_SECS;
+use crate::i18n::{t_args, t_markup, Arg};
+use crate::{view, AppState, Podcast};
+use axum::extract::State;
+use axum::http::{header, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::Json;
+use maud::{html, Markup};
+use serde::Serialize;
+use url::Url;
+use utoipa::ToSchema;
+
+tokio::task_local! {
+    static RENDERING: Rendering;
+}
+
+/// How errors are rendered for the current request.
+#[derive(Clone, Copy)]
+struct Rendering {
+    /// Whether the client asked for JSON rather than HTML.
+    json: bool,
+    /// How long an error page is shown before the user is sent back to the app.
+    redirect_delay_secs: u64,
+}
+
+impl Rendering {
+    fn current() -> Rendering {
+        RENDERING
+            .try_with(|rendering| *rendering)
+            .unwrap_or(Rendering {
+                json: false,
+                redirect_delay_secs: DEFAULT_ERROR_REDIRECT_DELAY_SECS,
+            })
+    }
+}
+
+/// Notes whether the client asked for JSON rather than HTML in its `Accept` header, and how long
+/// error pages wait before redirecting, so that errors are rendered without handlers having to
+/// pass the request or configuration on.
+pub async fn negotiate_format<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let json = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(prefers_json);
+    let rendering = Rendering {
+        json,
+        redirect_delay_secs: state.config().error_redirect_delay_secs,
+    };
+    let mut response = RENDERING.scope(rendering, next.run(request)).await;
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+/// Whether the `Accept` header lists a JSON media type but not HTML, as API clients send and
+/// browsers don't. Quality values are ignored.
+fn prefers_json(accept: &str) -> bool {
+    let media_types: Vec<&str> = accept
+        .split(',')
+        .map(|media_type| media_type.split(';').next().unwrap_or_default().trim())
+        .collect();
+    media_types
+        .iter()
+        .any(|media_type| *media_type == "application/json" || media_type.ends_with("+json"))
+        && !media_types.contains(&"text/html")
+}
+
+/// An error as rendered for clients that asked for JSON.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorBody {
+    /// The error's [`reason`](AppError::reason).
+    error: &'static str,
+    /// The error page's message, as plain text.
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_after_secs: Option<u64>,
+}
+
+/// Everything a handler can fail with. The response is rendered once, here, rather than by each
+/// handler.
+pub enum AppError {
+    PodcastNotFound(String),
+    /// A required URL or form parameter, by name, was not given.
+    MissingParam(&'static str),
+    /// A URL or form parameter, by name, has an invalid value.
+    InvalidParam(&'static str),
+    InvalidReturnUrl,
+    /// The email and password do not belong to the podcast's owner.
+    InvalidCredentials,
+    /// A sign-in link that doesn't exist, has expired or has been used.
+    LinkExpired,
+    /// A dashboard or admin form without the token of the page it was on, as another site would
+    /// post it.
+    CsrfRejected,
+    /// A password reset link that doesn't exist, has expired or has been used.
+    ResetLinkExpired,
+    /// A new password that is too short or doesn't match its confirmation.
+    PasswordRejected,
+    CryptoFailure(String),
+    /// The requesting app may not verify; the user is sent back to it with `error=access_denied`.
+    AccessDenied(Url),
+    TooManyRequests {
+        retry_after_secs: u64,
+    },
+    /// Too many failed logins for the owner's email address or from the client.
+    LoginLocked {
+        retry_after_secs: u64,
+    },
+    /// The app asked to transfer a feed whose owner has locked it.
+    FeedLocked,
+    /// An external feed could not be fetched or read, with the details.
+    ImportFailed(String),
+    Storage(std::io::Error),
+    NotFound,
+    /// An error during a verification flow, shown together with the podcast being verified and a
+    /// redirect back to the requesting app if they are known.
+    Verify(Box<FlowError>),
+}
+
+/// The context of an [`AppError::Verify`], boxed to keep `AppError` small.
+pub struct FlowError {
+    podcast: Option<Podcast>,
+    return_url: Option<Url>,
+    error: AppError,
+}
+
+impl AppError {
+    /// Attaches the verification flow's context to the error.
+    pub fn in_flow(self, podcast: Option<Podcast>, return_url: Option<Url>) -> AppError {
+        AppError::Verify(Box::new(FlowError {
+            podcast,
+            return_url,
+            error: self,
+        }))
+    }
+
+    /// The OAuth 2.0 error code (RFC 6749, section 4.1.2.1) that covers the error, so that apps
+    /// can handle a failed verification the way they handle a failed authorization.
+    pub fn oauth_error(&self) -> &'static str {
+        match self {
+            AppError::AccessDenied(_)
+            | AppError::InvalidCredentials
+            | AppError::LoginLocked { .. }
+            | AppError::CsrfRejected
+            | AppError::FeedLocked => "access_denied",
+            AppError::PodcastNotFound(_)
+            | AppError::MissingParam(_)
+            | AppError::InvalidParam(_)
+            | AppError::InvalidReturnUrl
+            | AppError::LinkExpired
+            | AppError::ResetLinkExpired
+            | AppError::PasswordRejected
+            | AppError::NotFound => "invalid_request",
+            AppError::CryptoFailure(_) | AppError::ImportFailed(_) | AppError::Storage(_) => {
+                "server_error"
+            }
+            AppError::TooManyRequests { .. } => "temporarily_unavailable",
+            AppError::Verify(flow) => flow.error.oauth_error(),
+        }
+    }
+
+    /// `return_url` with `error` set to the [`oauth_error`](AppError::oauth_error) and
+    /// `error_description` to the [`reason`](AppError::reason), for sending the user back to the
+    /// app.
+    pub fn return_url_with_error(&self, mut return_url: Url) -> Url {
+        return_url
+            .query_pairs_mut()
+            .append_pair("error", self.oauth_error())
+            .append_pair("error_description", self.reason());
+        return_url
+    }
+
+    /// Short machine-readable reason, used in logs and metrics.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            AppError::PodcastNotFound(_) => "podcast_not_found",
+            AppError::MissingParam("returnUrl") => "missing_return_url",
+            AppError::MissingParam("encryptedString") => "missing_encrypted_string",
+            AppError::MissingParam(_) => "missing_param",
+            AppError::InvalidParam("encryptedString") => "invalid_encrypted_string",
+            AppError::InvalidParam(_) => "invalid_param",
+            AppError::InvalidReturnUrl => "invalid_return_url",
+            AppError::InvalidCredentials => "invalid_credentials",
+            AppError::LinkExpired => "link_expired",
+            AppError::CsrfRejected => "csrf_rejected",
+            AppError::ResetLinkExpired => "reset_link_expired",
+            AppError::PasswordRejected => "password_rejected",
+            AppError::CryptoFailure(_) => "crypto_failure",
+            AppError::AccessDenied(_) => "access_denied",
+            AppError::TooManyRequests { .. } => "too_many_requests",
+            AppError::LoginLocked { .. } => "login_locked",
+            AppError::FeedLocked => "feed_locked",
+            AppError::ImportFailed(_) => "import_failed",
+            AppError::Storage(_) => "storage",
+            AppError::NotFound => "not_found",
+            AppError::Verify(flow) => flow.error.reason(),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::PodcastNotFound(_) | AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::MissingParam(_)
+            | AppError::InvalidParam(_)
+            | AppError::InvalidReturnUrl
+            | AppError::LinkExpired
+            | AppError::ResetLinkExpired
+            | AppError::PasswordRejected => StatusCode::BAD_REQUEST,
+            AppError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            AppError::CsrfRejected | AppError::FeedLocked => StatusCode::FORBIDDEN,
+            AppError::ImportFailed(_) => StatusCode::BAD_GATEWAY,
+            AppError::CryptoFailure(_) | AppError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::AccessDenied(_) => StatusCode::SEE_OTHER,
+            AppError::TooManyRequests { .. } | AppError::LoginLocked { .. } => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            AppError::Verify(flow) => flow.error.status(),
+        }
+    }
+
+    fn message(&self) -> Markup {
+        let (id, args) = self.localized(code);
+        t_markup(id, &args)
+    }
+
+    /// The message as plain text, with names and slugs as they are rather than as `code`.
+    fn plain_message(&self) -> String {
+        let (id, args) = self.localized(|text| text.into());
+        t_args(id, &args)
+    }
+
+    /// The message's ID and arguments, with `code` rendering names and slugs.
+    fn localized(&self, code: fn(&str) -> Arg) -> (&'static str, Vec<(&'static str, Arg)>) {
+        match self {
+            AppError::PodcastNotFound(slug) => {
+                ("error-podcast-not-found", vec![("slug", code(slug))])
+            }
+            AppError::MissingParam(name) => ("error-missing-param", vec![("name", code(name))]),
+            AppError::InvalidParam(name) => ("error-invalid-param", vec![("name", code(name))]),
+            AppError::InvalidReturnUrl => {
+                ("error-invalid-param", vec![("name", code("returnUrl"))])
+            }
+            AppError::InvalidCredentials => ("error-invalid-credentials", vec![]),
+            AppError::LinkExpired => ("error-link-expired", vec![]),
+            AppError::CsrfRejected => ("error-csrf-rejected", vec![]),
+            AppError::ResetLinkExpired => ("error-reset-link-expired", vec![]),
+            AppError::PasswordRejected => (
+                "error-password-rejected",
+                vec![("min", (crate::passwords::MIN_PASSWORD_LEN as u64).into())],
+            ),
+            AppError::CryptoFailure(_) => ("error-crypto-failure", vec![]),
+            AppError::AccessDenied(_) => ("error-access-denied", vec![]),
+            AppError::TooManyRequests { retry_after_secs } => (
+                "error-too-many-requests",
+                vec![("seconds", (*retry_after_secs).into())],
+            ),
+            AppError::LoginLocked { retry_after_secs } => (
+                "error-login-locked",
+                vec![("minutes", retry_after_secs.div_ceil(60).into())],
+            ),
+            AppError::FeedLocked => ("error-feed-locked", vec![]),
+            AppError::ImportFailed(details) => {
+                ("error-import-failed", vec![("details", code(details))])
+            }
+            AppError::Storage(_) => ("error-storage", vec![]),
+            AppError::NotFound => ("error-not-found", vec![]),
+            AppError::Verify(flow) => flow.error.localized(code),
+        }
+    }
+
+    fn retry_after_secs(&self) -> Option<u64> {
+        match self {
+            AppError::TooManyRequests { retry_after_secs }
+            | AppError::LoginLocked { retry_after_secs } => Some(*retry_after_secs),
+            AppError::Verify(flow) => flow.error.retry_after_secs(),
+            _ => None,
+        }
+    }
+
+    fn into_json_response(self) -> Response {
+        let retry_after_secs = self.retry_after_secs();
+        let body = ErrorBody {
+            error: self.reason(),
+            message: self.plain_message(),
+            retry_after_secs,
+        };
+        let retry_after = retry_after_secs.map(|secs| [(header::RETRY_AFTER, secs.to_string())]);
+        (self.status(), retry_after, Json(body)).into_response()
+    }
+}
+
+fn code(text: &str) -> Arg {
+    html! { code { (text) } }.into()
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        match &self {
+            AppError::CryptoFailure(details) => tracing::error!("crypto failure: {details}"),
+            AppError::Storage(e) => tracing::error!("storage failure: {e}"),
+            _ => {}
+        }
+
+        let redirect = match &self {
+            AppError::AccessDenied(_) => true,
+            AppError::Verify(flow) => matches!(flow.error, AppError::AccessDenied(_)),
+            _ => false,
+        };
+        let rendering = Rendering::current();
+        if !redirect && rendering.json {
+            return self.into_json_response();
+        }
+
+        match self {
+            AppError::AccessDenied(ref return_url) => {
+                let return_url = self.return_url_with_error(return_url.clone());
+                Redirect::to(return_url.as_str()).into_response()
+            }
+            AppError::TooManyRequests { retry_after_secs } => (
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                view::too_many_requests(retry_after_secs),
+            )
+                .into_response(),
+            AppError::LoginLocked { retry_after_secs } => (
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                view::error_page(self.status(), self.message()),
+            )
+                .into_response(),
+            AppError::Verify(flow) => match flow.error {
+                error @ (AppError::AccessDenied(_) | AppError::TooManyRequests { .. }) => {
+                    error.into_response()
+                }
+                error => {
+                    let retry_after = match error {
+                        AppError::LoginLocked { retry_after_secs } => {
+                            Some([(header::RETRY_AFTER, retry_after_secs.to_string())])
+                        }
+                        _ => None,
+                    };
+                    let page = view::verify(view::VerifyState::Error {
+                        podcast: flow.podcast,
+                        return_url: flow.return_url.map(|url| error.return_url_with_error(url)),
+                        redirect_delay_secs: rendering.redirect_delay_secs,
+                        message: error.message(),
+                        code: error.status(),
+                    });
+                    (retry_after, page).into_response()
+                }
+            },
+            error => view::error_page(error.status(), error.message()).into_response(),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Server-sent events for apps that the `returnUrl` redirect can't reach, such as CLI and TV
+//! apps. The app subscribes to `/feed/:slug/verify/events` with the challenge it encrypted and
+//! shows the owner the verify URL to open on another device; once the owner approves, the signed
+//! result is pushed over the stream.
+
+use crate::error::AppError;
+use crate::{slug_to_podcast, AppState, Podcast};
+use axum::extract::{Path, Query, State};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+use url::Url;
+use utoipa::IntoParams;
+
+/// How long a subscription waits for the owner before the stream ends with an `expired` event.
+pub const SUBSCRIPTION_TTL: Duration = Duration::from_secs(15 * 60);
+/// Subscriptions waiting at once, across all feeds, so that open streams can't exhaust memory.
+const MAX_WAITING: usize = 10_000;
+
+/// What an app subscribed to a challenge receives in the `approved` event, the same as the
+/// parameters of the redirect after an approval.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Completion {
+    pub feed_url: Url,
+    pub decrypted_string: String,
+    pub grant_id: String,
+    pub signed_verification: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+}
+
+/// Apps waiting for challenges to be approved, keyed by a hash of the podcast's slug and the
+/// challenge, so that the store doesn't hold the challenges themselves.
+#[derive(Clone, Default)]
+pub struct Completions {
+    waiting: Arc<Mutex<HashMap<[u8; 32], Vec<Waiter>>>>,
+}
+
+type Waiter = oneshot::Sender<Completion>;
+
+impl Completions {
+    /// Waits for `challenge` to be approved for the podcast, or returns `None` if too many
+    /// subscriptions are waiting already.
+    fn subscribe(&self, slug: &str, challenge: &str) -> Option<oneshot::Receiver<Completion>> {
+        let mut waiting = self.waiting.lock().unwrap();
+        waiting.retain(|_, senders| {
+            senders.retain(|sender| !sender.is_closed());
+            !senders.is_empty()
+        });
+        if waiting.values().map(Vec::len).sum::<usize>() >= MAX_WAITING {
+            return None;
+        }
+        let (sender, receiver) = oneshot::channel();
+        waiting
+            .entry(key(slug, challenge))
+            .or_default()
+            .push(sender);
+        Some(receiver)
+    }
+
+    /// Pushes an approval to every app subscribed to the challenge and returns how many were.
+    pub fn complete(&self, slug: &str, challenge: &str, completion: Completion) -> usize {
+        let senders = self
+            .waiting
+            .lock()
+            .unwrap()
+            .remove(&key(slug, challenge))
+            .unwrap_or_default();
+        senders
+            .into_iter()
+            // The app may have gone away in the meantime.
+            .filter_map(|sender| sender.send(completion.clone()).ok())
+            .count()
+    }
+}
+
+fn key(slug: &str, challenge: &str) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(slug)
+        .chain_update([0])
+        .chain_update(challenge)
+        .finalize()
+        .into()
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventsParams {
+    /// The challenge the app encrypted for the verify URL, before encryption.
+    #[serde(default)]
+    challenge: Option<String>,
+}
+
+/// Streams a single `approved` event once the owner approves `challenge`, or an `expired` event
+/// after [`SUBSCRIPTION_TTL`], then ends.
+#[utoipa::path(
+    get,
+    path = "/feed/{slug}/verify/events",
+    tag = "verification",
+    params(("slug" = String, Path, description = "The podcast's slug"), EventsParams),
+    responses(
+        (
+            status = 200,
+            description = "An `approved` event with `feedUrl`, `decryptedString`, `grantId`, `signedVerification` and `state` once the owner approves, or an `expired` event",
+            content_type = "text/event-stream",
+            body = String
+        ),
+        (status = 400, description = "`challenge` is missing", body = ErrorBody),
+        (status = 404, description = "No such published podcast", body = ErrorBody),
+        (
+            status = 429,
+            description = "Too many requests, or too many apps waiting",
+            body = ErrorBody
+        ),
+    )
+)]
+pub async fn events(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    Query(params): Query<EventsParams>,
+) -> Result<Sse<ReceiverStream<Result<Event, Infallible>>>, AppError> {
+    let podcast = slug_to_podcast(state.podcasts.all(), &slug)
+        .filter(Podcast::is_published)
+        .ok_or(AppError::PodcastNotFound(slug))?;
+    let challenge = params
+        .challenge
+        .filter(|challenge| !challenge.is_empty())
+        .ok_or(AppError::MissingParam("challenge"))?;
+    let completion = state
+        .completions
+        .subscribe(&podcast.slug, &challenge)
+        .ok_or(AppError::TooManyRequests {
+            retry_after_secs: 60,
+        })?;
+
+    let (sender, receiver) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let event = tokio::select! {
+            result = tokio::time::timeout(SUBSCRIPTION_TTL, completion) => match result {
+                Ok(Ok(completion)) => Event::default()
+                    .event("approved")
+                    .json_data(completion)
+                    .expect("completions serialize to JSON"),
+                // Nothing can complete the subscription any more.
+                Ok(Err(_)) | Err(_) => Event::default().event("expired").data("{}"),
+            },
+            // The app disconnected, which drops its subscription too.
+            _ = sender.closed() => return,
+        };
+        let _ = sender.send(Ok(event)).await;
+    });
+    Ok(Sse::new(ReceiverStream::new(receiver)).keep_alive(KeepAlive::default()))
+}
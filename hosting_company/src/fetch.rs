@@ -0,0 +1,286 @@
+//! Fetching feeds the way an app does to read their `<podcast:verify>` tags: with bounded time,
+//! redirects and size, retrying transient failures, and without re-downloading a large feed
+//! every time it is checked.
+//!
+//! ```ignore
+//! let fetcher = FeedFetcher::new(FetchConfig::default())?;
+//! let cache = FeedCache::new(Duration::from_secs(60), 16 * 1024 * 1024);
+//! let feed = cache.fetch(&fetcher, &feed_url).await?;
+//! ```
+
+use reqwest::header::{self, HeaderValue};
+use reqwest::redirect::Policy;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Limits for [`FeedFetcher`]. The defaults suit feeds of a few megabytes on a slow host.
+#[derive(Clone, Debug)]
+pub struct FetchConfig {
+    /// How long establishing a connection may take.
+    pub connect_timeout: Duration,
+    /// How long a whole request may take, from connecting to reading the last byte of the body.
+    pub timeout: Duration,
+    /// Redirects followed before giving up, by the client [`FeedFetcher::new`] builds.
+    pub max_redirects: usize,
+    /// Largest body read. Longer responses are abandoned rather than buffered.
+    pub max_bytes: usize,
+    /// Attempts made after the first one fails with a timeout, a connection error, `429 Too Many
+    /// Requests` or a server error.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each one after it.
+    pub retry_backoff: Duration,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        FetchConfig {
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(30),
+            max_redirects: 5,
+            max_bytes: 16 * 1024 * 1024,
+            retries: 2,
+            retry_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// An HTTP client for fetching feeds from hosts that may be slow, broken or hostile.
+#[derive(Clone)]
+pub struct FeedFetcher {
+    client: reqwest::Client,
+    config: FetchConfig,
+}
+
+impl FeedFetcher {
+    pub fn new(config: FetchConfig) -> Result<Self, FetchError> {
+        let builder = reqwest::Client::builder().redirect(Policy::limited(config.max_redirects));
+        FeedFetcher::with_client(builder, config)
+    }
+
+    /// A fetcher with a client built from `builder`, for callers that resolve hosts or follow
+    /// redirects their own way, such as [`outbound::client_builder`](crate::outbound). The
+    /// timeouts and the user agent are set on top of it.
+    pub fn with_client(
+        builder: reqwest::ClientBuilder,
+        config: FetchConfig,
+    ) -> Result<Self, FetchError> {
+        let client = builder
+            .user_agent(concat!(
+                env!("CARGO_PKG_NAME"),
+                "/",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.timeout)
+            .build()?;
+        Ok(FeedFetcher { client, config })
+    }
+
+    /// The feed at `url`.
+    pub async fn fetch(&self, url: &Url) -> Result<String, FetchError> {
+        let response = self.send(self.client.get(url.clone())).await?;
+        if !response.status().is_success() {
+            return Err(FetchError::Status(response.status()));
+        }
+        self.read_body(response).await
+    }
+
+    /// Sends the request, retrying transient failures. The last response is returned whatever
+    /// its status.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, FetchError> {
+        let mut backoff = self.config.retry_backoff;
+        for _ in 0..self.config.retries {
+            let Some(attempt) = request.try_clone() else {
+                break;
+            };
+            match attempt.send().await {
+                Ok(response) if !is_transient(response.status()) => return Ok(response),
+                Err(e) if !(e.is_timeout() || e.is_connect()) => return Err(e.into()),
+                Ok(response) => {
+                    tracing::debug!(status = %response.status(), "retrying feed fetch");
+                }
+                Err(e) => tracing::debug!("retrying feed fetch: {e}"),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = backoff.saturating_mul(2);
+        }
+        Ok(request.send().await?)
+    }
+
+    async fn read_body(&self, mut response: Response) -> Result<String, FetchError> {
+        let max_bytes = self.config.max_bytes;
+        if response
+            .content_length()
+            .is_some_and(|length| length > max_bytes as u64)
+        {
+            return Err(FetchError::TooLarge { max_bytes });
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > max_bytes {
+                return Err(FetchError::TooLarge { max_bytes });
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Feeds by URL. A feed fetched less than `ttl` ago is returned without a request; an older one
+/// is revalidated with `If-None-Match` and `If-Modified-Since`, so that an unchanged feed costs
+/// a `304 Not Modified` rather than its whole body.
+///
+/// At most `max_bytes` of feed bodies are kept, evicting the least recently fetched first.
+#[derive(Clone)]
+pub struct FeedCache {
+    ttl: Duration,
+    max_bytes: usize,
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<Url, Entry>,
+    bytes: usize,
+}
+
+struct Entry {
+    body: Arc<str>,
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    fetched_at: Instant,
+}
+
+/// Why a feed couldn't be fetched.
+#[derive(Debug)]
+pub enum FetchError {
+    Http(reqwest::Error),
+    /// The server answered with a status other than success or `304 Not Modified`.
+    Status(StatusCode),
+    /// The body is longer than [`FetchConfig::max_bytes`].
+    TooLarge {
+        max_bytes: usize,
+    },
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Http(e) => write!(f, "{e}"),
+            FetchError::Status(status) => write!(f, "status {status}"),
+            FetchError::TooLarge { max_bytes } => {
+                write!(f, "response is larger than {max_bytes} bytes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        FetchError::Http(e)
+    }
+}
+
+impl FeedCache {
+    pub fn new(ttl: Duration, max_bytes: usize) -> Self {
+        FeedCache {
+            ttl,
+            max_bytes,
+            inner: Arc::default(),
+        }
+    }
+
+    /// The feed at `url`, from the cache if it is fresh or the server says it hasn't changed.
+    pub async fn fetch(&self, fetcher: &FeedFetcher, url: &Url) -> Result<Arc<str>, FetchError> {
+        let mut request = fetcher.client.get(url.clone());
+        {
+            let inner = self.inner.lock().unwrap();
+            if let Some(entry) = inner.entries.get(url) {
+                if entry.fetched_at.elapsed() < self.ttl {
+                    return Ok(entry.body.clone());
+                }
+                if let Some(etag) = &entry.etag {
+                    request = request.header(header::IF_NONE_MATCH, etag.clone());
+                }
+                if let Some(last_modified) = &entry.last_modified {
+                    request = request.header(header::IF_MODIFIED_SINCE, last_modified.clone());
+                }
+            }
+        }
+
+        let mut response = fetcher.send(request).await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(body) = self.revalidated(url) {
+                return Ok(body);
+            }
+            // The entry was evicted while the request was in flight.
+            response = fetcher.send(fetcher.client.get(url.clone())).await?;
+        }
+        if !response.status().is_success() {
+            return Err(FetchError::Status(response.status()));
+        }
+
+        let etag = response.headers().get(header::ETAG).cloned();
+        let last_modified = response.headers().get(header::LAST_MODIFIED).cloned();
+        let body: Arc<str> = fetcher.read_body(response).await?.into();
+        self.insert(
+            url,
+            Entry {
+                body: body.clone(),
+                etag,
+                last_modified,
+                fetched_at: Instant::now(),
+            },
+        );
+        Ok(body)
+    }
+
+    /// Total size of the cached feed bodies.
+    pub fn cached_bytes(&self) -> usize {
+        self.inner.lock().unwrap().bytes
+    }
+
+    fn revalidated(&self, url: &Url) -> Option<Arc<str>> {
+        let mut inner = self.inner.lock().unwrap();
+        let entry = inner.entries.get_mut(url)?;
+        entry.fetched_at = Instant::now();
+        Some(entry.body.clone())
+    }
+
+    fn insert(&self, url: &Url, entry: Entry) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(previous) = inner.entries.remove(url) {
+            inner.bytes -= previous.body.len();
+        }
+        if entry.body.len() > self.max_bytes {
+            return;
+        }
+
+        while inner.bytes + entry.body.len() > self.max_bytes {
+            let Some(oldest) = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.fetched_at)
+                .map(|(url, _)| url.clone())
+            else {
+                break;
+            };
+            if let Some(evicted) = inner.entries.remove(&oldest) {
+                inner.bytes -= evicted.body.len();
+            }
+        }
+        inner.bytes += entry.body.len();
+        inner.entries.insert(url.clone(), entry);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::unix_timestamp;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// A verification an owner approved, which they can later revoke.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Grant {
+    /// Random identifier, given to the app with the approval so that it can poll the status.
+    pub id: String,
+    pub slug: String,
+    /// Domain of the app's `returnUrl`.
+    pub domain: String,
+    pub state: Option<String>,
+    /// Where the app is notified if the grant is revoked.
+    pub callback_url: Option<Url>,
+    /// Seconds since the Unix epoch.
+    pub granted_at: u64,
+    pub revoked_at: Option<u64>,
+}
+
+impl Grant {
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+}
+
+/// Granted verifications, persisted as JSON.
+#[derive(Clone)]
+pub struct Grants {
+    path: PathBuf,
+    grants: Arc<Mutex<Vec<Grant>>>,
+}
+
+impl Grants {
+    /// Loads the grants from `path`, starting empty if the file does not exist yet.
+    pub fn load(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let grants = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Grants {
+            path,
+            grants: Arc::new(Mutex::new(grants)),
+        })
+    }
+
+    pub fn grant(
+        &self,
+        slug: &str,
+        domain: &str,
+        state: Option<String>,
+        callback_url: Option<Url>,
+    ) -> std::io::Result<Grant> {
+        let mut id = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut id);
+        let grant = Grant {
+            id: id.iter().map(|byte| format!("{byte:02x}")).collect(),
+            slug: slug.to_string(),
+            domain: domain.to_string(),
+            state,
+            callback_url,
+            granted_at: unix_timestamp(),
+            revoked_at: None,
+        };
+
+        let mut grants = self.grants.lock().unwrap();
+        grants.push(grant.clone());
+        self.save(&grants)?;
+        Ok(grant)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Grant> {
+        self.grants
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|grant| grant.id == id)
+            .cloned()
+    }
+
+    /// The podcast's grants, most recent first.
+    pub fn for_podcast(&self, slug: &str) -> Vec<Grant> {
+        self.grants
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|grant| grant.slug == slug)
+            .cloned()
+            .collect()
+    }
+
+    /// Revokes one of the podcast's grants. Returns `None` if it doesn't exist or was already
+    /// revoked.
+    pub fn revoke(&self, slug: &str, id: &str) -> std::io::Result<Option<Grant>> {
+        let mut grants = self.grants.lock().unwrap();
+        let Some(grant) = grants
+            .iter_mut()
+            .find(|grant| grant.slug == slug && grant.id == id && !grant.is_revoked())
+        else {
+            return Ok(None);
+        };
+        grant.revoked_at = Some(unix_timestamp());
+        let grant = grant.clone();
+        self.save(&grants)?;
+        Ok(Some(grant))
+    }
+
+    fn save(&self, grants: &[Grant]) -> std::io::Result<()> {
+        let contents = serde_json::to_string_pretty(grants)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        std::fs::write(&self.path, contents)
+    }
+}
@@ -0,0 +1,374 @@
+//! The whole `<podcast:verify>` handshake in one call: the app encrypting a challenge to the
+//! feed's public key, the host decrypting it, and a signature over the result, bound to the app
+//! it is for and to a validity period, that anyone with the feed's `signingKey` can check.
+//!
+//! ```ignore
+//! let verification =
+//!     validate_handshake(&key_pair, &signing_key, Padding::OaepSha256, "challenge", &return_url)?;
+//! // On the app's side, with the `signingKey`, `<podcast:guid>` and URL of the feed:
+//! let verification = SignedVerification::from_token(&token)?;
+//! verification.validate(&signing_keys, &return_url, now)?;
+//! assert!(verification.matches_feed(Some(&guid), &feed_url));
+//! ```
+//!
+//! Apps and the conformance checker encrypt with [`encrypt`], the host signs with
+//! [`SignedVerification::sign`] and apps check with [`SignedVerification::validate`], so there is
+//! one implementation of each step to test.
+
+use crate::crypto::{self, KeyPair, Padding};
+use crate::tag;
+use crate::unix_timestamp;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use rand::rngs::OsRng;
+use rsa::traits::PublicKeyParts;
+use rsa::{Oaep, Pkcs1v15Encrypt, Pss, RsaPrivateKey, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::time::Duration;
+use url::{Origin, Position, Url};
+
+/// Starts the [signed bytes](SignedVerification::signed_bytes), so that a signature from the
+/// handshake can't be passed off as one over anything else signed with the same key.
+pub const SIGNATURE_CONTEXT: &[u8] = b"podcast:verify signed verification v1\n";
+/// How long a signed verification is valid for once issued.
+pub const VERIFICATION_TTL: Duration = Duration::from_secs(10 * 60);
+/// How far in the future `issuedAt` may be, for clocks that are slightly off.
+const CLOCK_SKEW_SECS: u64 = 60;
+
+/// The feed a verification was issued for.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FeedIdentity {
+    /// The feed's `<podcast:guid>`.
+    pub guid: Option<String>,
+    pub url: Option<String>,
+}
+
+/// A decrypted challenge, signed with the host's signing key for the app it was issued to and
+/// for a limited time, so that it can't be replayed to another service or later on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedVerification {
+    /// The decrypted challenge, as `decryptedString` is sent back to the app.
+    pub challenge: String,
+    /// The [`audience`] of the `returnUrl` it was issued to.
+    pub audience: String,
+    pub feed: FeedIdentity,
+    /// Seconds since the Unix epoch.
+    pub issued_at: u64,
+    /// Seconds since the Unix epoch, after which it is no longer accepted.
+    pub expires_at: u64,
+    /// RSASSA-PSS with SHA-256 and a 32-byte salt over the
+    /// [signed bytes](SignedVerification::signed_bytes).
+    pub signature: Vec<u8>,
+}
+
+/// A [`SignedVerification`] as JSON, before it is base64url-encoded into a token.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Token {
+    challenge: String,
+    audience: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    feed_guid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    feed_url: Option<String>,
+    issued_at: u64,
+    expires_at: u64,
+    /// Base64url without padding.
+    signature: String,
+}
+
+impl SignedVerification {
+    /// Signs `challenge` from `feed` for the app at `return_url` with the signing key, valid for
+    /// [`VERIFICATION_TTL`] from `issued_at`.
+    pub fn sign(
+        signing_key: &RsaPrivateKey,
+        challenge: &str,
+        return_url: &Url,
+        feed: FeedIdentity,
+        issued_at: u64,
+    ) -> Result<SignedVerification, HandshakeError> {
+        let mut verification = SignedVerification {
+            challenge: challenge.to_string(),
+            audience: audience(return_url),
+            feed,
+            issued_at,
+            expires_at: issued_at + VERIFICATION_TTL.as_secs(),
+            signature: Vec::new(),
+        };
+        verification.signature = signing_key
+            .sign_with_rng(&mut OsRng, Pss::new::<Sha256>(), &verification.digest())
+            .map_err(|e| HandshakeError::Sign(e.to_string()))?;
+        Ok(verification)
+    }
+
+    /// Checks the signature against the feed's `signingKey`. See
+    /// [`validate`](SignedVerification::validate) for also checking who it was issued to and
+    /// when.
+    pub fn verify(&self, public_key: &RsaPublicKey) -> Result<(), HandshakeError> {
+        public_key
+            .verify(Pss::new::<Sha256>(), &self.digest(), &self.signature)
+            .map_err(|_| HandshakeError::InvalidSignature)
+    }
+
+    /// Checks that the verification was issued to the app at `return_url`, is valid at `now`
+    /// (seconds since the Unix epoch), and is signed with one of `signing_keys`, the `signingKey`s
+    /// of the feed's tags.
+    pub fn validate(
+        &self,
+        signing_keys: &[RsaPublicKey],
+        return_url: &Url,
+        now: u64,
+    ) -> Result<(), HandshakeError> {
+        if self.audience != audience(return_url) {
+            return Err(HandshakeError::WrongAudience);
+        }
+        if now >= self.expires_at || self.issued_at > now + CLOCK_SKEW_SECS {
+            return Err(HandshakeError::Expired);
+        }
+        if signing_keys
+            .iter()
+            .any(|signing_key| self.verify(signing_key).is_ok())
+        {
+            Ok(())
+        } else {
+            Err(HandshakeError::InvalidSignature)
+        }
+    }
+
+    /// Whether the verification is for the feed the app knows by `guid` and `feed_url`. GUIDs are
+    /// compared when both sides have one, as they survive the feed moving to another URL or host,
+    /// and feed URLs, regardless of scheme and trailing slashes, otherwise. Only meaningful once
+    /// the verification is [`validate`](SignedVerification::validate)d.
+    pub fn matches_feed(&self, guid: Option<&str>, feed_url: &Url) -> bool {
+        match (&self.feed.guid, guid) {
+            (Some(signed), Some(guid)) => signed.eq_ignore_ascii_case(guid),
+            _ => {
+                self.feed.url.as_deref().is_some_and(|signed| {
+                    tag::guid_name(signed) == tag::guid_name(feed_url.as_str())
+                })
+            }
+        }
+    }
+
+    /// The verification as a URL-safe token, for the `signedVerification` parameter.
+    pub fn to_token(&self) -> String {
+        let token = Token {
+            challenge: self.challenge.clone(),
+            audience: self.audience.clone(),
+            feed_guid: self.feed.guid.clone(),
+            feed_url: self.feed.url.clone(),
+            issued_at: self.issued_at,
+            expires_at: self.expires_at,
+            signature: URL_SAFE_NO_PAD.encode(&self.signature),
+        };
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&token).expect("token serializes"))
+    }
+
+    /// Parses a token made by [`to_token`](SignedVerification::to_token). The signature isn't
+    /// checked until it is [`validate`](SignedVerification::validate)d.
+    pub fn from_token(token: &str) -> Result<SignedVerification, HandshakeError> {
+        let token: Token = URL_SAFE_NO_PAD
+            .decode(token)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or(HandshakeError::InvalidToken)?;
+        Ok(SignedVerification {
+            signature: URL_SAFE_NO_PAD
+                .decode(&token.signature)
+                .map_err(|_| HandshakeError::InvalidToken)?,
+            challenge: token.challenge,
+            audience: token.audience,
+            feed: FeedIdentity {
+                guid: token.feed_guid,
+                url: token.feed_url,
+            },
+            issued_at: token.issued_at,
+            expires_at: token.expires_at,
+        })
+    }
+
+    /// The bytes that are signed, which other implementations build the same way to check the
+    /// signature: [`SIGNATURE_CONTEXT`], then the audience, the feed's GUID and URL (empty when
+    /// not known), `issuedAt`, `expiresAt` and the challenge, each as its length in bytes, a
+    /// colon, the field as UTF-8 and a newline. Numbers are written in decimal, so an audience of
+    /// `https://app.example` is `19:https://app.example\n` and `issuedAt` `10:1700000000\n`.
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        let fields = [
+            self.audience.as_str(),
+            self.feed.guid.as_deref().unwrap_or_default(),
+            self.feed.url.as_deref().unwrap_or_default(),
+            &self.issued_at.to_string(),
+            &self.expires_at.to_string(),
+            &self.challenge,
+        ];
+        let mut bytes = SIGNATURE_CONTEXT.to_vec();
+        for field in fields {
+            bytes.extend_from_slice(format!("{}:{field}\n", field.len()).as_bytes());
+        }
+        bytes
+    }
+
+    /// The SHA-256 hash of the signed bytes, which PSS signs.
+    fn digest(&self) -> Vec<u8> {
+        Sha256::digest(self.signed_bytes()).to_vec()
+    }
+}
+
+/// Who a verification for `return_url` is issued to: its origin, or for URLs without one, such
+/// as custom schemes of mobile apps, the URL without its query and fragment.
+pub fn audience(return_url: &Url) -> String {
+    match return_url.origin() {
+        origin @ Origin::Tuple(..) => origin.ascii_serialization(),
+        Origin::Opaque(_) => return_url[..Position::AfterPath].to_string(),
+    }
+}
+
+/// The step of the handshake that failed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum HandshakeError {
+    /// The challenge couldn't be encrypted to the public key.
+    Encrypt(String),
+    /// The ciphertext isn't base64, doesn't decrypt with the private key, or isn't UTF-8.
+    Decrypt,
+    /// The ciphertext decrypted to something other than the challenge.
+    Mismatch,
+    Sign(String),
+    /// The signature doesn't match the signed fields and public key.
+    InvalidSignature,
+    /// The verification was issued to another app.
+    WrongAudience,
+    /// The verification has expired, or claims to be issued in the future.
+    Expired,
+    /// The token isn't base64url-encoded JSON of a verification.
+    InvalidToken,
+}
+
+impl fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandshakeError::Encrypt(e) => write!(f, "failed to encrypt the challenge: {e}"),
+            HandshakeError::Decrypt => write!(f, "encryptedString doesn't decrypt"),
+            HandshakeError::Mismatch => {
+                write!(f, "encryptedString decrypts to a different challenge")
+            }
+            HandshakeError::Sign(e) => write!(f, "failed to sign the challenge: {e}"),
+            HandshakeError::InvalidSignature => write!(f, "the signature doesn't verify"),
+            HandshakeError::WrongAudience => write!(f, "the verification is for another app"),
+            HandshakeError::Expired => write!(f, "the verification has expired"),
+            HandshakeError::InvalidToken => write!(f, "the verification token is malformed"),
+        }
+    }
+}
+
+impl std::error::Error for HandshakeError {}
+
+/// Runs the whole handshake with `key_pair` for the app at `return_url`: encrypts `challenge` to
+/// the public key as an app would, then [`complete_handshake`]s it as the host would, signing
+/// with `signing_key`. The result isn't bound to a feed.
+pub fn validate_handshake(
+    key_pair: &KeyPair,
+    signing_key: &KeyPair,
+    padding: Padding,
+    challenge: &str,
+    return_url: &Url,
+) -> Result<SignedVerification, HandshakeError> {
+    let encrypted_string = encrypt(&key_pair.public_key, padding, challenge)?;
+    complete_handshake(
+        key_pair,
+        signing_key,
+        padding,
+        &encrypted_string,
+        challenge,
+        return_url,
+    )
+}
+
+/// The host's side of the handshake: decrypts `encrypted_string` with `key_pair`, checks that it
+/// is `challenge`, signs it with `signing_key` for the app at `return_url` and validates the
+/// result as the app would.
+pub fn complete_handshake(
+    key_pair: &KeyPair,
+    signing_key: &KeyPair,
+    padding: Padding,
+    encrypted_string: &str,
+    challenge: &str,
+    return_url: &Url,
+) -> Result<SignedVerification, HandshakeError> {
+    let decrypted = crypto::decrypt(&key_pair.private_key, padding, encrypted_string)
+        .map_err(|_| HandshakeError::Decrypt)?;
+    if decrypted != challenge {
+        return Err(HandshakeError::Mismatch);
+    }
+    let now = unix_timestamp();
+    let verification = SignedVerification::sign(
+        &signing_key.private_key,
+        &decrypted,
+        return_url,
+        FeedIdentity::default(),
+        now,
+    )?;
+    verification.validate(
+        std::slice::from_ref(&signing_key.public_key),
+        return_url,
+        now,
+    )?;
+    Ok(verification)
+}
+
+/// How [`encrypt`] base64-encodes `encryptedString`. Hosts should accept either, see
+/// [`crypto::decode_encrypted_string`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// Base64url without padding, which needs no percent-encoding in a URL.
+    #[default]
+    UrlSafe,
+    /// Standard, padded base64, for hosts that only accept that.
+    Standard,
+}
+
+/// Encrypts `challenge` to `public_key` as an app would for `encryptedString`, in base64url.
+/// See [`encrypt_as`].
+pub fn encrypt(
+    public_key: &RsaPublicKey,
+    padding: Padding,
+    challenge: &str,
+) -> Result<String, HandshakeError> {
+    encrypt_as(public_key, padding, challenge, Encoding::default())
+}
+
+/// Encrypts `challenge` to `public_key` with `padding` if it fits in one RSA block, or else as an
+/// envelope (see [`crypto::seal_envelope`]), and encodes it with `encoding`.
+pub fn encrypt_as(
+    public_key: &RsaPublicKey,
+    padding: Padding,
+    challenge: &str,
+    encoding: Encoding,
+) -> Result<String, HandshakeError> {
+    let plaintext = challenge.as_bytes();
+    let ciphertext = if plaintext.len() > max_plaintext_len(public_key, padding) {
+        crypto::seal_envelope(public_key, plaintext)
+            .map_err(|_| HandshakeError::Encrypt(String::from("failed to seal envelope")))?
+    } else {
+        match padding {
+            Padding::Pkcs1v15 => public_key.encrypt(&mut OsRng, Pkcs1v15Encrypt, plaintext),
+            Padding::OaepSha256 => public_key.encrypt(&mut OsRng, Oaep::new::<Sha256>(), plaintext),
+        }
+        .map_err(|e| HandshakeError::Encrypt(e.to_string()))?
+    };
+    Ok(match encoding {
+        Encoding::UrlSafe => URL_SAFE_NO_PAD.encode(ciphertext),
+        Encoding::Standard => STANDARD.encode(ciphertext),
+    })
+}
+
+/// The longest plaintext one RSA block holds with the padding (RFC 8017, sections 7.1.1 and
+/// 7.2.1).
+fn max_plaintext_len(public_key: &RsaPublicKey, padding: Padding) -> usize {
+    let overhead = match padding {
+        Padding::Pkcs1v15 => 11,
+        Padding::OaepSha256 => 2 * <Sha256 as Digest>::output_size() + 2,
+    };
+    public_key.size().saturating_sub(overhead)
+}
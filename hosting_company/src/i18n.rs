@@ -0,0 +1,148 @@
+use axum::http::header::{self, HeaderValue};
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use fluent_langneg::{accepted_languages, negotiate_languages, NegotiationStrategy};
+use fluent_templates::fluent_bundle::FluentValue;
+use fluent_templates::Loader;
+use maud::{html, Markup, PreEscaped};
+use std::collections::HashMap;
+use unic_langid::{langid, LanguageIdentifier};
+
+fluent_templates::static_loader! {
+    static LOCALES = {
+        locales: "./locales",
+        fallback_language: "en-US",
+        // Bidi isolation marks around arguments would end up inside URLs and `code` elements.
+        customise: |bundle| bundle.set_use_isolating(false),
+    };
+}
+
+const DEFAULT_LOCALE: LanguageIdentifier = langid!("en-US");
+
+tokio::task_local! {
+    static LOCALE: LanguageIdentifier;
+}
+
+/// Picks the best available locale from the request's `Accept-Language` header and renders the
+/// rest of the request in it, so that views and error pages need not be handed the locale.
+pub async fn negotiate<B>(request: Request<B>, next: Next<B>) -> Response {
+    let requested = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(accepted_languages::parse)
+        .unwrap_or_default();
+    let available: Vec<LanguageIdentifier> = LOCALES.locales().cloned().collect();
+    let locale = negotiate_languages(
+        &requested,
+        &available,
+        Some(&DEFAULT_LOCALE),
+        NegotiationStrategy::Filtering,
+    )
+    .first()
+    .map(|locale| (*locale).clone())
+    .unwrap_or(DEFAULT_LOCALE);
+
+    let content_language = HeaderValue::from_str(&locale.to_string()).ok();
+    let mut response = LOCALE.scope(locale, next.run(request)).await;
+    let headers = response.headers_mut();
+    headers.append(header::VARY, HeaderValue::from_static("accept-language"));
+    if let Some(content_language) = content_language {
+        headers.insert(header::CONTENT_LANGUAGE, content_language);
+    }
+    response
+}
+
+/// The locale the current request is rendered in.
+pub fn locale() -> LanguageIdentifier {
+    LOCALE
+        .try_with(|locale| locale.clone())
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// A message argument. Markup arguments are inserted into the translated text unescaped, so
+/// translators can move links and `code` elements around without writing HTML.
+#[derive(Clone)]
+pub enum Arg {
+    Value(FluentValue<'static>),
+    Markup(Markup),
+}
+
+impl From<&str> for Arg {
+    fn from(value: &str) -> Self {
+        Arg::Value(FluentValue::from(value.to_string()))
+    }
+}
+
+impl From<String> for Arg {
+    fn from(value: String) -> Self {
+        Arg::Value(FluentValue::from(value))
+    }
+}
+
+impl From<u64> for Arg {
+    fn from(value: u64) -> Self {
+        Arg::Value(FluentValue::from(value))
+    }
+}
+
+impl From<Markup> for Arg {
+    fn from(value: Markup) -> Self {
+        Arg::Markup(value)
+    }
+}
+
+/// The message `id` as plain text.
+pub fn t(id: &str) -> String {
+    t_args(id, &[])
+}
+
+/// The message `id` as plain text. Markup arguments are inserted as their HTML source.
+pub fn t_args(id: &str, args: &[(&str, Arg)]) -> String {
+    let args = args
+        .iter()
+        .map(|(name, arg)| {
+            let value = match arg {
+                Arg::Value(value) => value.clone(),
+                Arg::Markup(markup) => FluentValue::from(markup.clone().into_string()),
+            };
+            (name.to_string(), value)
+        })
+        .collect();
+    lookup(id, args)
+}
+
+/// The message `id` as escaped markup, with markup arguments left intact.
+pub fn t_markup(id: &str, args: &[(&str, Arg)]) -> Markup {
+    // Markup arguments are swapped for placeholders from the private use area, which escaping
+    // leaves alone, and put back once the translated text has been escaped.
+    let mut markups = Vec::new();
+    let args = args
+        .iter()
+        .map(|(name, arg)| {
+            let value = match arg {
+                Arg::Value(value) => value.clone(),
+                Arg::Markup(markup) => {
+                    let placeholder = format!("\u{E000}{}\u{E001}", markups.len());
+                    markups.push((placeholder.clone(), markup.clone().into_string()));
+                    FluentValue::from(placeholder)
+                }
+            };
+            (name.to_string(), value)
+        })
+        .collect();
+
+    let mut rendered = html! { (lookup(id, args)) }.into_string();
+    for (placeholder, markup) in markups {
+        rendered = rendered.replace(&placeholder, &markup);
+    }
+    PreEscaped(rendered)
+}
+
+fn lookup(id: &str, args: HashMap<String, FluentValue<'static>>) -> String {
+    let args = (!args.is_empty()).then_some(&args);
+    LOCALES
+        .lookup_complete(&locale(), id, args)
+        .unwrap_or_else(|| id.to_string())
+}
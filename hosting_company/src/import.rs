@@ -0,0 +1,321 @@
+//! Importing a podcast from a feed the host already serves elsewhere. The feed is kept as it is,
+//! with its episodes, `<podcast:guid>` and any extensions, and republished with the host's
+//! `<podcast:locked>` and `<podcast:verify>` tags injected at the top of the channel, replacing
+//! any it had.
+
+use crate::fetch::{FeedFetcher, FetchConfig, FetchError};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::reader::NsReader;
+use quick_xml::Writer;
+use serde::Serialize;
+use std::fmt;
+use url::Url;
+
+pub const PODCAST_NAMESPACE: &str = "https://podcastindex.org/namespace/1.0";
+/// Channel elements of the podcast namespace that the host writes itself.
+const MANAGED_ELEMENTS: [&[u8]; 2] = [b"verify", b"locked"];
+
+/// An imported feed, split where the host's tags go.
+pub struct ImportedFeed {
+    /// The feed up to and including the `<channel>` start tag, with the podcast namespace
+    /// declared.
+    head: String,
+    /// The rest of the feed, without the elements the host manages.
+    tail: String,
+    guid: Option<String>,
+}
+
+impl ImportedFeed {
+    /// The feed's own `<podcast:guid>`, if it has one.
+    pub fn guid(&self) -> Option<&str> {
+        self.guid.as_deref()
+    }
+
+    /// The feed with `tags` as the first children of the channel.
+    pub fn republish(&self, tags: &str) -> String {
+        format!("{}\n    {}{}", self.head, tags, self.tail)
+    }
+}
+
+/// What was read from an imported feed.
+pub struct Parsed {
+    pub title: String,
+    pub description: String,
+    pub episodes: Vec<Episode>,
+    pub feed: ImportedFeed,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Episode {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enclosure_url: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Fetch(FetchError),
+    Xml(quick_xml::Error),
+    /// The document isn't an RSS feed with a channel.
+    NotRss,
+    MissingTitle,
+    /// The feed binds the `podcast` prefix to another namespace, so the injected tags would
+    /// mean something else.
+    PrefixConflict,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Fetch(e) => write!(f, "failed to fetch feed: {e}"),
+            ImportError::Xml(e) => write!(f, "invalid XML: {e}"),
+            ImportError::NotRss => write!(f, "not an RSS feed with a channel"),
+            ImportError::MissingTitle => write!(f, "the channel has no title"),
+            ImportError::PrefixConflict => {
+                write!(f, "the podcast prefix is bound to another namespace")
+            }
+        }
+    }
+}
+
+impl From<FetchError> for ImportError {
+    fn from(e: FetchError) -> Self {
+        ImportError::Fetch(e)
+    }
+}
+
+impl From<quick_xml::Error> for ImportError {
+    fn from(e: quick_xml::Error) -> Self {
+        ImportError::Xml(e)
+    }
+}
+
+/// Where in the feed a text node is, for the parts that are read.
+enum Field {
+    Title,
+    Description,
+    Guid,
+    EpisodeTitle,
+    EpisodeGuid,
+}
+
+/// Fetches the feed at `url` and [`parse`]s it.
+pub async fn fetch(url: &Url) -> Result<Parsed, ImportError> {
+    let fetcher = FeedFetcher::new(FetchConfig::default())?;
+    parse(&fetcher.fetch(url).await?)
+}
+
+/// Reads the channel's title, description and episodes, and prepares the feed for republishing.
+pub fn parse(source: &str) -> Result<Parsed, ImportError> {
+    let mut reader = NsReader::from_str(source);
+    let mut writer = Writer::new(Vec::new());
+    // Local names of the open elements, prefixed with `podcast:` in the podcast namespace, or
+    // `None` for elements in other namespaces.
+    let mut path: Vec<Option<String>> = Vec::new();
+    let mut head_len = None;
+    let mut title = String::new();
+    let mut description = String::new();
+    let mut guid: Option<String> = None;
+    let mut episodes: Vec<Episode> = Vec::new();
+
+    loop {
+        let (namespace, event) = reader.read_resolved_event()?;
+        let in_namespace = !matches!(namespace, ResolveResult::Unbound);
+        let in_podcast_namespace =
+            namespace == ResolveResult::Bound(Namespace(PODCAST_NAMESPACE.as_bytes()));
+        match event {
+            Event::Start(start) if in_podcast_namespace && is_managed(&path, &start) => {
+                reader.read_to_end(start.name())?;
+            }
+            Event::Empty(start) if in_podcast_namespace && is_managed(&path, &start) => {}
+            Event::Start(start) => {
+                let name = local_name(&start, in_namespace, in_podcast_namespace);
+                match (path_of(&path).as_slice(), name.as_deref()) {
+                    ([], Some("rss")) => {
+                        writer.write_event(Event::Start(declare_namespace(start)?))?;
+                    }
+                    ([], _) => return Err(ImportError::NotRss),
+                    ([Some("rss")], Some("channel")) => {
+                        writer.write_event(Event::Start(start))?;
+                        head_len = Some(writer.get_ref().len());
+                    }
+                    ([Some("rss"), Some("channel")], Some("item")) => {
+                        episodes.push(Episode::default());
+                        writer.write_event(Event::Start(start))?;
+                    }
+                    ([Some("rss"), Some("channel"), Some("item")], Some("enclosure")) => {
+                        set_enclosure(&mut episodes, &start)?;
+                        writer.write_event(Event::Start(start))?;
+                    }
+                    _ => writer.write_event(Event::Start(start))?,
+                }
+                path.push(name);
+            }
+            Event::Empty(start) => {
+                let name = local_name(&start, in_namespace, in_podcast_namespace);
+                match (path_of(&path).as_slice(), name.as_deref()) {
+                    ([], _) | ([Some("rss")], Some("channel")) => return Err(ImportError::NotRss),
+                    ([Some("rss"), Some("channel"), Some("item")], Some("enclosure")) => {
+                        set_enclosure(&mut episodes, &start)?;
+                    }
+                    _ => {}
+                }
+                writer.write_event(Event::Empty(start))?;
+            }
+            Event::End(end) => {
+                path.pop();
+                writer.write_event(Event::End(end))?;
+            }
+            Event::Text(text) => {
+                if let Some(field) = field(&path) {
+                    let text = text.unescape()?.into_owned();
+                    append(
+                        field,
+                        &text,
+                        &mut title,
+                        &mut description,
+                        &mut guid,
+                        &mut episodes,
+                    );
+                }
+                writer.write_event(Event::Text(text))?;
+            }
+            Event::CData(cdata) => {
+                if let Some(field) = field(&path) {
+                    let text = String::from_utf8_lossy(&cdata).into_owned();
+                    append(
+                        field,
+                        &text,
+                        &mut title,
+                        &mut description,
+                        &mut guid,
+                        &mut episodes,
+                    );
+                }
+                writer.write_event(Event::CData(cdata))?;
+            }
+            Event::Eof => break,
+            event => writer.write_event(event)?,
+        }
+    }
+
+    let head_len = head_len.ok_or(ImportError::NotRss)?;
+    let title = title.trim().to_string();
+    if title.is_empty() {
+        return Err(ImportError::MissingTitle);
+    }
+    // The writer only copies what it read from a string, so the output is UTF-8 too.
+    let mut head = String::from_utf8(writer.into_inner()).expect("feed is UTF-8");
+    let tail = head.split_off(head_len);
+    for episode in &mut episodes {
+        episode.title = episode.title.trim().to_string();
+        episode.guid = episode.guid.take().map(|guid| guid.trim().to_string());
+    }
+    Ok(Parsed {
+        title,
+        description: description.trim().to_string(),
+        episodes,
+        feed: ImportedFeed {
+            head,
+            tail,
+            guid: guid
+                .map(|guid| guid.trim().to_string())
+                .filter(|guid| !guid.is_empty()),
+        },
+    })
+}
+
+/// Whether the element is one of the channel's that the host writes itself.
+fn is_managed(path: &[Option<String>], start: &BytesStart) -> bool {
+    path_of(path) == [Some("rss"), Some("channel")]
+        && MANAGED_ELEMENTS.contains(&start.local_name().as_ref())
+}
+
+fn local_name(
+    start: &BytesStart,
+    in_namespace: bool,
+    in_podcast_namespace: bool,
+) -> Option<String> {
+    let local_name = String::from_utf8_lossy(start.local_name().as_ref()).into_owned();
+    if in_podcast_namespace {
+        Some(format!("podcast:{local_name}"))
+    } else {
+        (!in_namespace).then_some(local_name)
+    }
+}
+
+fn path_of(path: &[Option<String>]) -> Vec<Option<&str>> {
+    path.iter().map(Option::as_deref).collect()
+}
+
+fn field(path: &[Option<String>]) -> Option<Field> {
+    match path_of(path).as_slice() {
+        [Some("rss"), Some("channel"), Some("title")] => Some(Field::Title),
+        [Some("rss"), Some("channel"), Some("description")] => Some(Field::Description),
+        [Some("rss"), Some("channel"), Some("podcast:guid")] => Some(Field::Guid),
+        [Some("rss"), Some("channel"), Some("item"), Some("title")] => Some(Field::EpisodeTitle),
+        [Some("rss"), Some("channel"), Some("item"), Some("guid")] => Some(Field::EpisodeGuid),
+        _ => None,
+    }
+}
+
+fn append(
+    field: Field,
+    text: &str,
+    title: &mut String,
+    description: &mut String,
+    guid: &mut Option<String>,
+    episodes: &mut [Episode],
+) {
+    let target = match field {
+        Field::Title => title,
+        Field::Description => description,
+        Field::Guid => guid.get_or_insert_with(String::new),
+        Field::EpisodeTitle => match episodes.last_mut() {
+            Some(episode) => &mut episode.title,
+            None => return,
+        },
+        Field::EpisodeGuid => match episodes.last_mut() {
+            Some(episode) => episode.guid.get_or_insert_with(String::new),
+            None => return,
+        },
+    };
+    target.push_str(text);
+}
+
+fn set_enclosure(episodes: &mut [Episode], enclosure: &BytesStart) -> Result<(), ImportError> {
+    let Some(episode) = episodes.last_mut() else {
+        return Ok(());
+    };
+    for attribute in enclosure.attributes() {
+        let attribute = attribute.map_err(quick_xml::Error::from)?;
+        if attribute.key.as_ref() == b"url" {
+            episode.enclosure_url = Some(attribute.unescape_value()?.into_owned());
+        }
+    }
+    Ok(())
+}
+
+/// The `<rss>` start tag, declaring the `podcast` prefix for the injected tags if the feed
+/// doesn't already.
+fn declare_namespace(rss: BytesStart) -> Result<BytesStart<'static>, ImportError> {
+    let mut declared = false;
+    for attribute in rss.attributes() {
+        let attribute = attribute.map_err(quick_xml::Error::from)?;
+        if attribute.key.as_ref() == b"xmlns:podcast" {
+            if attribute.value.as_ref() != PODCAST_NAMESPACE.as_bytes() {
+                return Err(ImportError::PrefixConflict);
+            }
+            declared = true;
+        }
+    }
+    let mut rss = rss.into_owned();
+    if !declared {
+        rss.push_attribute(("xmlns:podcast", PODCAST_NAMESPACE));
+    }
+    Ok(rss)
+}
@@ -0,0 +1,1832 @@
+use arc_swap::ArcSwap;
+use axum::body::Body;
+use axum::extract::{ConnectInfo, RawQuery, State};
+use axum::http::{header, HeaderMap, Method, Request};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::{
+    extract::Form,
+    extract::Path,
+    extract::Query,
+    headers::{ContentType, ETag, IfModifiedSince, IfNoneMatch, LastModified},
+    http::StatusCode,
+    middleware,
+    routing::{get, post},
+    Json, Router, TypedHeader,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use error::AppError;
+use rsa::RsaPublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tower_http::compression::CompressionLayer;
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use trace::StepKind;
+use tracing::{field, Level, Span};
+use url::Url;
+use utoipa::{IntoParams, ToSchema};
+
+mod abuse;
+mod admin;
+mod apps;
+mod attempts;
+mod batch;
+mod blocklist;
+mod callback;
+pub mod config;
+mod credentials;
+pub mod crypto;
+mod csrf;
+pub mod demo_app;
+mod docs;
+mod error;
+mod events;
+pub mod fetch;
+mod grants;
+pub mod handshake;
+mod i18n;
+mod import;
+mod lockout;
+mod magic;
+mod metrics;
+pub mod outbound;
+mod owner;
+mod passwords;
+mod ratelimit;
+mod reload;
+mod security;
+mod slugs;
+pub mod tag;
+mod trace;
+mod txt;
+mod view;
+mod xml;
+
+pub use magic::Mailer;
+
+#[derive(Deserialize, Debug, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct VerifyParams {
+    /// The challenge, base64-encoded and encrypted to a `publicKey` from the feed, or an
+    /// envelope.
+    #[serde(default, rename = "encryptedString")]
+    encrypted_string: Option<String>,
+    /// Where to send the owner back to, with the result appended.
+    #[serde(default, rename = "returnUrl")]
+    return_url: Option<String>,
+    /// Opaque value from the requesting app, handed back with the redirect so that it can match
+    /// the response to its request.
+    #[serde(default)]
+    state: Option<String>,
+    /// Where to post the result on approval, in addition to redirecting to `returnUrl`.
+    #[serde(default, rename = "callbackUrl")]
+    callback_url: Option<String>,
+    /// `transfer` if the app wants to move the feed to another host, which the owner can refuse
+    /// by locking it, rather than only to verify ownership.
+    #[serde(default)]
+    purpose: Option<String>,
+}
+
+#[derive(Clone)]
+struct Customer {
+    email: String,
+    /// The password as an Argon2id PHC string, see [`credentials::hash_password`].
+    password_hash: String,
+}
+
+#[derive(Clone)]
+pub struct Podcast {
+    title: String,
+    slug: String,
+    owner: Customer,
+    crypto: crypto::CryptoConfig,
+    status: PodcastStatus,
+    /// Whether the owner has opted out of transfers, published as `<podcast:locked>`.
+    locked: bool,
+    /// When the owner last changed the feed, if ever.
+    updated_at: Option<SystemTime>,
+    /// The external feed the podcast was imported from, served in place of the generated one.
+    imported: Option<Arc<import::ImportedFeed>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PodcastStatus {
+    /// Staged by the host: the feed is only served to its owner, and apps can't verify it.
+    Draft,
+    Published,
+}
+
+/// The hosted podcasts. Only their status and lock change at runtime, and imports and the config
+/// file add to them.
+#[derive(Clone)]
+pub struct Podcasts(Arc<RwLock<Vec<Podcast>>>);
+
+impl Podcasts {
+    pub fn new(podcasts: Vec<Podcast>) -> Self {
+        Podcasts(Arc::new(RwLock::new(podcasts)))
+    }
+
+    pub fn all(&self) -> Vec<Podcast> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn published(&self) -> Vec<Podcast> {
+        self.all()
+            .into_iter()
+            .filter(Podcast::is_published)
+            .collect()
+    }
+
+    /// Adds the podcast. Returns `false`, leaving the podcasts as they are, if its slug is taken.
+    fn add(&self, podcast: Podcast) -> bool {
+        let mut podcasts = self.0.write().unwrap();
+        let slug = slugs::normalize_slug(&podcast.slug);
+        if podcasts
+            .iter()
+            .any(|other| slugs::normalize_slug(&other.slug) == slug)
+        {
+            return false;
+        }
+        podcasts.push(podcast);
+        true
+    }
+
+    /// Adds the podcasts, or updates the title, owner and crypto settings of those already hosted
+    /// under the same slug. Their status, lock, imported feed and, unless the owner changes, the
+    /// owner's password are the owner's and the admin's to change, and stay as they are.
+    fn upsert(&self, updated: Vec<Podcast>) {
+        let mut podcasts = self.0.write().unwrap();
+        for podcast in updated {
+            let slug = slugs::normalize_slug(&podcast.slug);
+            match podcasts
+                .iter_mut()
+                .find(|other| slugs::normalize_slug(&other.slug) == slug)
+            {
+                Some(existing) => {
+                    existing.title = podcast.title;
+                    if existing.owner.email != podcast.owner.email {
+                        existing.owner = podcast.owner;
+                    }
+                    existing.crypto = podcast.crypto;
+                }
+                None => podcasts.push(podcast),
+            }
+        }
+    }
+
+    /// Publishes the podcast. Returns `false` if it already was.
+    fn publish(&self, slug: &str) -> bool {
+        let mut podcasts = self.0.write().unwrap();
+        match podcasts
+            .iter_mut()
+            .find(|podcast| podcast.slug == slug && !podcast.is_published())
+        {
+            Some(podcast) => {
+                podcast.status = PodcastStatus::Published;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Changes the password hash of the owner with the email address, for all their podcasts.
+    /// Returns how many podcasts they own.
+    fn set_password_hash(&self, email: &str, password_hash: &str) -> usize {
+        let mut podcasts = self.0.write().unwrap();
+        let mut owned = 0;
+        for podcast in podcasts
+            .iter_mut()
+            .filter(|podcast| podcast.owner.email == email)
+        {
+            podcast.owner.password_hash = password_hash.to_string();
+            owned += 1;
+        }
+        owned
+    }
+
+    /// Locks or unlocks the podcast. Returns `false` if it already was.
+    fn set_locked(&self, slug: &str, locked: bool) -> bool {
+        let mut podcasts = self.0.write().unwrap();
+        match podcasts
+            .iter_mut()
+            .find(|podcast| podcast.slug == slug && podcast.locked != locked)
+        {
+            Some(podcast) => {
+                podcast.locked = locked;
+                podcast.updated_at = Some(SystemTime::now());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Podcast {
+    /// A published podcast with the default crypto settings.
+    pub fn new(title: &str, slug: &str, owner_email: &str, owner_password: &str) -> Self {
+        Podcast {
+            title: title.to_string(),
+            slug: slug.to_string(),
+            owner: Customer::new(owner_email, owner_password),
+            crypto: crypto::CryptoConfig::default(),
+            status: PodcastStatus::Published,
+            locked: false,
+            updated_at: None,
+            imported: None,
+        }
+    }
+
+    fn is_published(&self) -> bool {
+        self.status == PodcastStatus::Published
+    }
+
+    fn feed_url(&self, base_url: &Url) -> Url {
+        base_url
+            .join(&format!("feed/{}", self.slug))
+            .expect("slug forms a valid URL path")
+    }
+
+    /// The podcast's `<podcast:guid>`: the imported feed's own, or else derived from the feed URL
+    /// at `base_url`, which should be the configured one rather than the request's so that the
+    /// GUID doesn't change.
+    fn guid(&self, base_url: &Url) -> String {
+        self.imported
+            .as_ref()
+            .and_then(|feed| feed.guid())
+            .map(str::to_string)
+            .unwrap_or_else(|| tag::podcast_guid(&self.feed_url(base_url)))
+    }
+
+    fn verify_url(&self, base_url: &Url) -> Url {
+        base_url
+            .join(&format!("feed/{}/verify", self.slug))
+            .expect("slug forms a valid URL path")
+    }
+
+    /// The podcast's RSS feed, or the feed it was imported from, with its `<podcast:guid>` unless
+    /// the imported feed has one, its `<podcast:locked>` status and a `<podcast:verify>` tag for
+    /// each of `public_keys`, all naming `signing_key`. The current key comes first, so apps that
+    /// only read the first tag use it.
+    fn feed(
+        &self,
+        public_keys: &[RsaPublicKey],
+        signing_key: &RsaPublicKey,
+        base_url: &Url,
+        guid: &str,
+        txt_tokens: &[String],
+        key_fingerprints: bool,
+    ) -> Result<String, AppError> {
+        let tags = public_keys
+            .iter()
+            .map(|public_key| {
+                tag::VerifyTag::builder()
+                    .verify_url(self.verify_url(base_url))
+                    .public_key(public_key)
+                    .signing_key(signing_key)
+                    .algorithm(self.crypto.padding)
+                    .fingerprint(key_fingerprints)
+                    .build()
+                    .map(|tag| tag.to_xml())
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::CryptoFailure(format!("invalid podcast:verify tag: {e}")))?;
+
+        let has_guid = self
+            .imported
+            .as_ref()
+            .is_some_and(|feed| feed.guid().is_some());
+        let host_tags = format!(
+            "{}<podcast:locked owner=\"{}\">{}</podcast:locked>
+    {}{}",
+            if has_guid {
+                String::new()
+            } else {
+                format!("<podcast:guid>{}</podcast:guid>\n    ", xml::escape(guid))
+            },
+            xml::escape(&self.owner.email),
+            if self.locked { "yes" } else { "no" },
+            tags.join("\n    "),
+            txt_tokens
+                .iter()
+                .map(|token| {
+                    format!(
+                        "\n    <podcast:txt purpose=\"verify\">{}</podcast:txt>",
+                        xml::escape(token)
+                    )
+                })
+                .collect::<String>(),
+        );
+
+        if let Some(imported) = &self.imported {
+            return Ok(imported.republish(&host_tags));
+        }
+        Ok(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<rss version=\"2.0\" xmlns:podcast=\"{}\">
+  <channel>
+    <title>{}</title>
+    {}
+  </channel>
+</rss>",
+            import::PODCAST_NAMESPACE,
+            xml::escape(&self.title),
+            host_tags,
+        ))
+    }
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    /// The settings requests are handled with, swapped whole when the config file is reloaded.
+    config: Arc<ArcSwap<config::Config>>,
+    /// The settings from the environment, which the config file overrides.
+    env_config: Arc<config::Config>,
+    podcasts: Podcasts,
+    keys: crypto::Keys,
+    key_pool: crypto::KeyPool,
+    txt_tokens: txt::TxtTokens,
+    abuse_reports: abuse::AbuseReports,
+    blocklist: blocklist::Blocklist,
+    csrf: csrf::CsrfTokens,
+    metrics: metrics::Metrics,
+    callbacks: callback::Callbacks,
+    magic_links: magic::MagicLinks,
+    password_resets: passwords::PasswordResets,
+    mailer: Arc<dyn magic::Mailer>,
+    traces: trace::Traces,
+    rate_limits: ratelimit::RateLimits,
+    login_throttle: lockout::LoginThrottle,
+    attempts: attempts::Attempts,
+    grants: grants::Grants,
+    slug_history: slugs::SlugHistory,
+    app_identities: apps::AppIdentities,
+    batch_logins: batch::BatchLogins,
+    completions: events::Completions,
+}
+
+/// The podcasts of the demo, with logins listed in the README.
+pub fn demo_podcasts() -> Vec<Podcast> {
+    let customer_alice = Customer::new("alice@example.com", "password123");
+    let customer_bob = Customer::new("bob@example.com", "password456");
+    let customer_carol = Customer::new("carol@example.com", "password789");
+
+    vec![
+        Podcast {
+            title: String::from("Alice's Podcast"),
+            slug: String::from("alice-podcast"),
+            owner: customer_alice,
+            crypto: crypto::CryptoConfig {
+                padding: crypto::Padding::OaepSha256,
+                ..Default::default()
+            },
+            status: PodcastStatus::Published,
+            locked: false,
+            updated_at: None,
+            imported: None,
+        },
+        Podcast {
+            title: String::from("Bob's Podcast"),
+            slug: String::from("bob-podcast"),
+            owner: customer_bob,
+            crypto: crypto::CryptoConfig::default(),
+            status: PodcastStatus::Published,
+            locked: false,
+            updated_at: None,
+            imported: None,
+        },
+        Podcast {
+            title: String::from("Carol's Podcast"),
+            slug: String::from("carol-podcast"),
+            owner: customer_carol,
+            crypto: crypto::CryptoConfig::default(),
+            status: PodcastStatus::Draft,
+            locked: false,
+            updated_at: None,
+            imported: None,
+        },
+    ]
+}
+
+/// Generates a key pair of every size the podcasts use.
+pub fn generate_keys(podcasts: &[Podcast]) -> crypto::Keys {
+    crypto::Keys::generate(podcasts.iter().map(|podcast| podcast.crypto.key_bits))
+}
+
+impl AppState {
+    /// Any key sizes the podcasts use that `keys` lacks must be loaded with
+    /// [`load_keys`](AppState::load_keys) before their feeds can be served. Must be called within
+    /// a Tokio runtime.
+    pub fn new(config: config::Config, podcasts: Vec<Podcast>, keys: crypto::Keys) -> AppState {
+        let env_config = Arc::new(config.clone());
+        let mut config = config;
+        let file = env_config
+            .config_path
+            .as_deref()
+            .map(|path| reload::read(path).expect("failed to load CONFIG_PATH"));
+        if let Some(file) = &file {
+            file.apply(&mut config);
+        }
+        let key_pool = crypto::KeyPool::start(
+            podcasts.iter().map(|podcast| podcast.crypto.key_bits),
+            config.key_pool_size,
+        );
+        let blocklist =
+            blocklist::Blocklist::load(&config.blocklist_path).expect("failed to load blocklist");
+        let attempts = attempts::Attempts::load(&config.attempts_path)
+            .expect("failed to load verification attempts");
+        let grants = grants::Grants::load(&config.grants_path).expect("failed to load grants");
+        let slug_history = slugs::SlugHistory::load(&config.slug_history_path)
+            .expect("failed to load slug history");
+        let metrics = metrics::Metrics::default();
+        let callbacks = callback::Callbacks::load(
+            &config.callbacks_path,
+            config.dev_mode,
+            config.outbound_proxy.as_ref(),
+            metrics.clone(),
+        )
+        .expect("failed to load callback registrations");
+
+        let state = AppState {
+            podcasts: Podcasts::new(podcasts),
+            keys,
+            key_pool,
+            txt_tokens: txt::TxtTokens::default(),
+            abuse_reports: abuse::AbuseReports::default(),
+            blocklist,
+            csrf: csrf::CsrfTokens::default(),
+            metrics,
+            callbacks,
+            magic_links: magic::MagicLinks::default(),
+            password_resets: passwords::PasswordResets::default(),
+            mailer: Arc::new(magic::LogMailer),
+            traces: trace::Traces::new(config.dev_mode),
+            rate_limits: ratelimit::RateLimits {
+                per_ip: ratelimit::RateLimiter::new(
+                    config.verify_rate_limit_per_ip,
+                    Duration::from_secs(60),
+                ),
+                per_slug: ratelimit::RateLimiter::new(
+                    config.verify_rate_limit_per_slug,
+                    Duration::from_secs(60),
+                ),
+            },
+            attempts,
+            grants,
+            slug_history,
+            app_identities: apps::AppIdentities::new(
+                config.fetch_app_identity,
+                config.dev_mode,
+                config.outbound_proxy.as_ref(),
+            ),
+            batch_logins: batch::BatchLogins::default(),
+            completions: events::Completions::default(),
+            login_throttle: lockout::LoginThrottle::new(
+                config.login_lockout_threshold,
+                Duration::from_secs(config.login_lockout_secs),
+            ),
+            config: Arc::new(ArcSwap::from_pointee(config)),
+            env_config,
+        };
+        if let Some(file) = file {
+            state.add_podcasts(file.podcasts());
+        }
+        state
+    }
+
+    /// Sends the owners' email with `mailer` instead of writing it to the log.
+    pub fn with_mailer(mut self, mailer: Arc<dyn Mailer>) -> AppState {
+        self.mailer = mailer;
+        self
+    }
+
+    /// The current settings. A request should use the same ones throughout, rather than call
+    /// this again and maybe see a reload halfway.
+    pub fn config(&self) -> Arc<config::Config> {
+        self.config.load_full()
+    }
+
+    /// Reads the config file again, adding or updating its podcasts and swapping in its settings
+    /// on top of the environment's. An invalid file is reported and changes nothing. Paths, TLS,
+    /// keys, secrets, logins and developer settings are only read at startup.
+    pub async fn reload(&self) -> Result<(), reload::ReloadError> {
+        let Some(path) = &self.env_config.config_path else {
+            return Ok(());
+        };
+        let file = reload::read(path)?;
+        let mut config = (*self.env_config).clone();
+        file.apply(&mut config);
+        self.add_podcasts(file.podcasts());
+        self.rate_limits
+            .per_ip
+            .set_limit(config.verify_rate_limit_per_ip);
+        self.rate_limits
+            .per_slug
+            .set_limit(config.verify_rate_limit_per_slug);
+        self.config.store(Arc::new(config));
+        self.load_keys().await;
+        tracing::info!(path, "config reloaded");
+        Ok(())
+    }
+
+    /// Adds or updates the config file's podcasts, except any that have since been renamed, which
+    /// would otherwise be back under their old slug.
+    fn add_podcasts(&self, podcasts: Vec<Podcast>) {
+        let renamed = self.slug_history.snapshot();
+        let (podcasts, skipped): (Vec<_>, Vec<_>) = podcasts
+            .into_iter()
+            .partition(|podcast| !renamed.contains_key(&podcast.slug));
+        for podcast in skipped {
+            tracing::warn!(
+                slug = podcast.slug,
+                "renamed podcast in config file skipped"
+            );
+        }
+        self.podcasts.upsert(podcasts);
+    }
+
+    /// Reloads the config file whenever it changes or the process gets `SIGHUP`, until the
+    /// server stops. Does nothing without a config file.
+    pub async fn watch_config(self) {
+        let Some(path) = self.env_config.config_path.clone() else {
+            return;
+        };
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .expect("failed to install SIGHUP handler");
+        let mut interval = tokio::time::interval(reload::POLL_INTERVAL);
+        let mut modified = reload::modified(&path);
+        loop {
+            #[cfg(unix)]
+            let hangup = hangup.recv();
+            #[cfg(not(unix))]
+            let hangup = std::future::pending::<Option<()>>();
+            tokio::select! {
+                _ = hangup => {}
+                _ = interval.tick() => {
+                    let now_modified = reload::modified(&path);
+                    if now_modified == modified {
+                        continue;
+                    }
+                    modified = now_modified;
+                }
+            }
+            if let Err(error) = self.reload().await {
+                tracing::error!(%error, "config not reloaded");
+            }
+        }
+    }
+
+    /// Generates a key of every size the podcasts use that isn't loaded yet, and the signing key
+    /// if it isn't. Until this is done, `/healthz` reports the server as unavailable.
+    pub async fn load_keys(&self) {
+        for bits in self.key_sizes() {
+            if !self.keys.has(bits) {
+                self.keys.install(self.key_pool.take(bits).await);
+                tracing::info!(bits, "key loaded");
+            }
+        }
+        if self.keys.signing().is_err() {
+            let pair = self.key_pool.take(crypto::SIGNING_KEY_BITS).await;
+            self.keys.install_signing(pair);
+            tracing::info!(bits = crypto::SIGNING_KEY_BITS, "signing key loaded");
+        }
+    }
+
+    fn key_sizes(&self) -> std::collections::BTreeSet<usize> {
+        self.podcasts
+            .all()
+            .iter()
+            .map(|podcast| podcast.crypto.key_bits)
+            .collect()
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    let rate_limit = middleware::from_fn_with_state(state.clone(), ratelimit::limit_verify);
+    let canonicalize_slugs = middleware::from_fn_with_state(state.clone(), slugs::canonicalize);
+    let negotiate_format = middleware::from_fn_with_state(state.clone(), error::negotiate_format);
+    let security_headers = middleware::from_fn_with_state(state.clone(), security::add_headers);
+
+    Router::new()
+        .merge(docs::swagger_ui())
+        .route("/", get(root))
+        .route("/healthz", get(healthz))
+        .route("/feed/:slug", get(feed))
+        .route("/feed/by-guid/:guid", get(feed_by_guid))
+        .route(
+            "/feed/by-guid/:guid/verify",
+            get(verify_by_guid)
+                .post(consent_by_guid)
+                .route_layer(rate_limit.clone()),
+        )
+        .route(
+            "/feed/:slug/verify",
+            get(verify).post(consent).route_layer(rate_limit.clone()),
+        )
+        .route(
+            "/feed/:slug/verify/events",
+            get(events::events).route_layer(rate_limit.clone()),
+        )
+        .route(
+            "/feed/:slug/verify/magic",
+            get(magic_link).route_layer(rate_limit.clone()),
+        )
+        .route(
+            "/verify/batch",
+            get(batch::request)
+                .post(batch::answer)
+                .route_layer(rate_limit.clone()),
+        )
+        .route(
+            "/password/forgot",
+            get(passwords::forgot_page)
+                .post(passwords::forgot)
+                .route_layer(rate_limit.clone()),
+        )
+        .route(
+            "/password/reset",
+            get(passwords::reset_page)
+                .post(passwords::reset)
+                .route_layer(rate_limit),
+        )
+        .route("/feed/:slug/verifications", get(owner::verifications))
+        .route("/feed/:slug/dashboard", get(owner::dashboard))
+        .route("/feed/:slug/publish", post(owner::publish))
+        .route("/feed/:slug/lock", post(owner::lock))
+        .route("/feed/:slug/password", post(passwords::change))
+        .route("/feed/:slug/grants/:id/revoke", post(owner::revoke))
+        .route("/api/feeds/:slug/attempts", get(owner::api_attempts))
+        .route("/api/feeds/:slug/grants", get(owner::api_grants))
+        .route("/api/feeds/:slug/publish", post(owner::api_publish))
+        .route("/api/feeds/:slug/lock", axum::routing::put(owner::api_lock))
+        .route(
+            "/api/feeds/:slug/password",
+            axum::routing::put(passwords::api_change),
+        )
+        .route(
+            "/api/feeds/:slug/grants/:id",
+            axum::routing::delete(owner::api_revoke),
+        )
+        .route("/api/grants/:id", get(owner::grant_status))
+        .route("/metrics", get(metrics::metrics))
+        .route("/dev/flows", get(trace::flows))
+        .route("/dev/flows/:id", get(trace::flow))
+        .route("/dev/flows/:id/sequence.mmd", get(trace::sequence_diagram))
+        .route("/admin", get(admin::page))
+        .route("/admin/blocklist", post(admin::block))
+        .route("/admin/blocklist/remove", post(admin::unblock))
+        .route("/api/admin/blocklist", get(admin::api_list))
+        .route(
+            "/api/admin/blocklist/:domain",
+            axum::routing::put(admin::api_block).delete(admin::api_unblock),
+        )
+        .route("/api/admin/callbacks", get(admin::api_list_callbacks))
+        .route(
+            "/api/admin/callbacks/:domain",
+            axum::routing::put(admin::api_register_callbacks)
+                .delete(admin::api_unregister_callbacks),
+        )
+        .route("/api/admin/keys/rotate", post(admin::api_rotate_keys))
+        .route("/api/admin/slugs", get(admin::api_list_renames))
+        .route(
+            "/api/admin/slugs/:old",
+            axum::routing::put(admin::api_rename).delete(admin::api_forget_rename),
+        )
+        .route(
+            "/api/admin/feeds/:slug",
+            axum::routing::put(admin::api_import),
+        )
+        .route("/api/admin/feeds/:slug/txt", get(admin::api_list_txt))
+        .route(
+            "/api/admin/feeds/:slug/txt/:token",
+            axum::routing::put(admin::api_add_txt).delete(admin::api_remove_txt),
+        )
+        .layer(canonicalize_slugs)
+        .with_state(state)
+        .layer(negotiate_format)
+        .layer(middleware::from_fn(i18n::negotiate))
+        .layer(security_headers)
+        .layer(CompressionLayer::new())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_span)
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+}
+
+/// Serves `router` on `listener` until `handle` shuts it down: over HTTPS when the config has a
+/// TLS certificate and key, over plain HTTP when it has neither.
+pub async fn serve(
+    listener: std::net::TcpListener,
+    router: Router,
+    config: &config::Config,
+    handle: Handle,
+) -> std::io::Result<()> {
+    let make_service = router.into_make_service_with_connect_info::<SocketAddr>();
+    match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let tls_config = RustlsConfig::from_pem_file(cert_path, key_path).await?;
+            axum_server::from_tcp_rustls(listener, tls_config)
+                .handle(handle)
+                .serve(make_service)
+                .await
+        }
+        (None, None) => {
+            axum_server::from_tcp(listener)
+                .handle(handle)
+                .serve(make_service)
+                .await
+        }
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "TLS_CERT_PATH and TLS_KEY_PATH must be set together",
+        )),
+    }
+}
+
+/// Runs the demo's host on `listener` until a shutdown signal, then gives in-flight requests the
+/// configured drain timeout to finish.
+pub async fn run(config: config::Config, listener: std::net::TcpListener) -> std::io::Result<()> {
+    let drain_timeout = Duration::from_secs(config.shutdown_drain_timeout_secs);
+
+    // Keys are generated in the background rather than delaying startup by seconds.
+    let state = AppState::new(config.clone(), demo_podcasts(), crypto::Keys::empty());
+    tokio::spawn({
+        let state = state.clone();
+        async move { state.load_keys().await }
+    });
+    // Podcasts and settings in the config file are reloaded when it changes or on SIGHUP.
+    tokio::spawn(state.clone().watch_config());
+
+    let handle = Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown_signal().await;
+            handle.graceful_shutdown(Some(drain_timeout));
+        }
+    });
+    serve(listener, router(state), &config, handle).await
+}
+
+/// Completes on Ctrl-C or, on Unix, `SIGTERM`.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("shutdown signal received, draining connections");
+}
+
+/// Creates the span every request is logged under. Handlers fill in `slug` and
+/// `return_url_domain` once they know them.
+fn request_span(request: &Request<Body>) -> Span {
+    let client_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = request.uri().path(),
+        client_ip,
+        slug = field::Empty,
+        return_url_domain = field::Empty,
+    )
+}
+
+/// Serves the podcast's feed. Drafts are only served to their owner, signed in with HTTP Basic
+/// credentials as on the dashboard.
+///
+/// This is how apps discover the verify URL and the keys to encrypt to: the feed has a
+/// `<podcast:verify>` tag for each advertised key, the current one first, with `verifyUrl`,
+/// `publicKey` (a base64 DER SubjectPublicKeyInfo) and `encryption` (`RSA-OAEP-256` or
+/// `RSA1_5`) attributes.
+#[utoipa::path(
+    get,
+    path = "/feed/{slug}",
+    tag = "verification",
+    params(("slug" = String, Path, description = "The podcast's slug")),
+    responses(
+        (
+            status = 200,
+            description = "The RSS feed",
+            content_type = "application/xml",
+            body = String
+        ),
+        (status = 304, description = "Unchanged since `If-None-Match` or `If-Modified-Since`"),
+        (status = 404, description = "No such published podcast", body = ErrorBody),
+    )
+)]
+async fn feed(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    owner: Option<owner::Owner>,
+    headers: HeaderMap,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
+) -> Result<Response, AppError> {
+    Span::current().record("slug", slug.as_str());
+    let podcast = slug_to_podcast(state.podcasts.all(), &slug)
+        .filter(|podcast| podcast.is_published() || owner.is_some());
+    let podcast = match podcast {
+        Some(podcast) => podcast,
+        None => {
+            tracing::info!(outcome = "podcast_not_found", "feed not served");
+            state
+                .metrics
+                .feed_requests
+                .with_label_values(&["podcast_not_found"])
+                .inc();
+            return Err(AppError::PodcastNotFound(slug));
+        }
+    };
+
+    let config = state.config();
+    let base_url = config.public_base_url(&headers);
+    let public_keys: Vec<RsaPublicKey> = state
+        .keys
+        .get(&podcast.crypto)?
+        .into_iter()
+        .map(|pair| pair.public_key)
+        .collect();
+    let signing_key = state.keys.signing()?.public_key;
+    let feed = podcast.feed(
+        &public_keys,
+        &signing_key,
+        &base_url,
+        &podcast.guid(&config.public_base_url),
+        &state.txt_tokens.tokens(&slug),
+        config.feed_key_fingerprints,
+    )?;
+    let etag = content_etag(&feed);
+    let keys_updated_at = state.keys.updated_at();
+    let updated_at = [state.txt_tokens.updated_at(), podcast.updated_at]
+        .into_iter()
+        .flatten()
+        .fold(keys_updated_at, SystemTime::max);
+    let last_modified = LastModified::from(updated_at);
+
+    // `If-None-Match` takes precedence over `If-Modified-Since` (RFC 7232, section 6). Its
+    // extractor accepts a missing header as an empty list, so check that it was sent.
+    let if_none_match = if_none_match.filter(|_| headers.contains_key(header::IF_NONE_MATCH));
+    let not_modified = match (if_none_match, if_modified_since) {
+        (Some(TypedHeader(if_none_match)), _) => !if_none_match.precondition_passes(&etag),
+        (None, Some(TypedHeader(if_modified_since))) => !if_modified_since.is_modified(updated_at),
+        (None, None) => false,
+    };
+    if not_modified {
+        tracing::info!(outcome = "not_modified", "feed not modified");
+        state
+            .metrics
+            .feed_requests
+            .with_label_values(&["not_modified"])
+            .inc();
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            TypedHeader(etag),
+            TypedHeader(last_modified),
+        )
+            .into_response());
+    }
+
+    tracing::info!(outcome = "served", "feed served");
+    state
+        .metrics
+        .feed_requests
+        .with_label_values(&["served"])
+        .inc();
+
+    Ok((
+        TypedHeader(etag),
+        TypedHeader(last_modified),
+        TypedHeader(ContentType::xml()),
+        feed,
+    )
+        .into_response())
+}
+
+/// The podcast whose `<podcast:guid>` is `guid`, regardless of case.
+fn guid_to_podcast(state: &AppState, guid: &str) -> Option<Podcast> {
+    state.podcasts.all().into_iter().find(|podcast| {
+        podcast
+            .guid(&state.config().public_base_url)
+            .eq_ignore_ascii_case(guid.trim())
+    })
+}
+
+/// [`feed`] for the podcast with the `<podcast:guid>`, as apps key podcasts by GUID rather than
+/// by the host's slug.
+async fn feed_by_guid(
+    State(state): State<AppState>,
+    Path(guid): Path<String>,
+    owner: Option<owner::Owner>,
+    headers: HeaderMap,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    if_modified_since: Option<TypedHeader<IfModifiedSince>>,
+) -> Result<Response, AppError> {
+    let podcast = guid_to_podcast(&state, &guid).ok_or(AppError::PodcastNotFound(guid))?;
+    feed(
+        State(state),
+        Path(podcast.slug),
+        owner,
+        headers,
+        if_none_match,
+        if_modified_since,
+    )
+    .await
+}
+
+/// Entity tag derived from the SHA-256 hash of the content. It is weak because the same tag is
+/// sent for the gzip and Brotli encodings produced by the compression layer.
+fn content_etag(content: &str) -> ETag {
+    let hash = Sha256::digest(content.as_bytes())
+        .iter()
+        .take(16)
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    format!("W/\"{hash}\"")
+        .parse()
+        .expect("hex digest is a valid entity tag")
+}
+
+/// Finds the podcast by its slug, ignoring case and percent-encoding.
+fn slug_to_podcast(podcasts: Vec<Podcast>, slug: &str) -> Option<Podcast> {
+    let slug = slugs::normalize_slug(slug);
+    podcasts
+        .into_iter()
+        .find(|podcast| slugs::normalize_slug(&podcast.slug) == slug)
+}
+
+async fn root(State(state): State<AppState>) -> impl IntoResponse {
+    view::root(state.podcasts.published())
+}
+
+#[derive(Serialize)]
+struct Health {
+    status: &'static str,
+    keys_loaded: bool,
+}
+
+async fn healthz(State(state): State<AppState>) -> (StatusCode, Json<Health>) {
+    let keys_loaded = state
+        .key_sizes()
+        .into_iter()
+        .all(|bits| state.keys.has(bits))
+        && state.keys.signing().is_ok()
+        && state.keys.valid();
+    if keys_loaded {
+        (
+            StatusCode::OK,
+            Json(Health {
+                status: "ok",
+                keys_loaded,
+            }),
+        )
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(Health {
+                status: "unavailable",
+                keys_loaded,
+            }),
+        )
+    }
+}
+
+/// Shows the owner the consent screen for an app's verification request.
+#[utoipa::path(
+    get,
+    path = "/feed/{slug}/verify",
+    tag = "verification",
+    params(("slug" = String, Path, description = "The podcast's slug"), VerifyParams),
+    responses(
+        (
+            status = 200,
+            description = "The consent screen",
+            content_type = "text/html",
+            body = String
+        ),
+        (
+            status = 303,
+            description = "The app's domain is blocked: back to `returnUrl` with `error=access_denied`"
+        ),
+        (status = 400, description = "A parameter is missing or invalid", body = ErrorBody),
+        (status = 404, description = "No such published podcast", body = ErrorBody),
+        (
+            status = 429,
+            description = "Too many requests from the client or for the podcast",
+            body = ErrorBody
+        ),
+    )
+)]
+async fn verify(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+    RawQuery(query): RawQuery,
+    params: Query<VerifyParams>,
+) -> Result<(StatusCode, Html<String>), AppError> {
+    let params: VerifyParams = params.0;
+    let flow = start_flow(&state, &Method::GET, &slug, query.as_deref(), &params);
+    let return_url = params.return_url.clone();
+
+    let result = validate_request(&state, &flow, &slug, params)
+        .map(|request| consent_screen(&state, &headers, &flow, request, false));
+    if let Err(error) = &result {
+        record_rejection(&state.metrics, &flow, error);
+    }
+    let outcome = result.as_ref().map(|_| attempts::Outcome::Requested);
+    record_attempt(&state, &slug, return_url.as_deref(), addr.ip(), outcome);
+    result
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum Decision {
+    Approve,
+    Deny,
+    /// Email the owner a sign-in link instead of checking a password.
+    Email,
+    /// Report the request as abuse instead of answering it.
+    Report,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ConsentForm {
+    decision: Decision,
+    /// The owner's email address, to approve, to report or to be sent a sign-in link.
+    #[serde(default)]
+    email: String,
+    /// The owner's password, to approve or to report.
+    #[serde(default)]
+    password: String,
+}
+
+/// Handles the owner's answer on the consent screen, which posts back to the verify URL.
+#[utoipa::path(
+    post,
+    path = "/feed/{slug}/verify",
+    tag = "verification",
+    params(("slug" = String, Path, description = "The podcast's slug"), VerifyParams),
+    request_body(content = ConsentForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (
+            status = 200,
+            description = "A sign-in link was sent, the request was reported, or with a redirect \
+                           delay configured, a confirmation that refreshes to `returnUrl` with \
+                           the result",
+            content_type = "text/html",
+            body = String
+        ),
+        (
+            status = 303,
+            description = "Back to `returnUrl` with `decryptedString`, `grantId` and `signedVerification`, or `error=access_denied`"
+        ),
+        (
+            status = 400,
+            description = "A parameter is missing or invalid, or the challenge can't be decrypted",
+            body = ErrorBody
+        ),
+        (
+            status = 403,
+            description = "The app asks to transfer a feed its owner has locked",
+            body = ErrorBody
+        ),
+        (status = 404, description = "No such published podcast", body = ErrorBody),
+        (
+            status = 429,
+            description = "Too many requests, or logins are locked after failed attempts",
+            body = ErrorBody
+        ),
+    )
+)]
+async fn consent(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+    RawQuery(query): RawQuery,
+    params: Query<VerifyParams>,
+    Form(form): Form<ConsentForm>,
+) -> Result<Response, AppError> {
+    let params: VerifyParams = params.0;
+    let flow = start_flow(&state, &Method::POST, &slug, query.as_deref(), &params);
+    let return_url = params.return_url.clone();
+
+    let result =
+        validate_request(&state, &flow, &slug, params.clone()).and_then(|request| match form {
+            ConsentForm {
+                decision: Decision::Email,
+                email,
+                ..
+            } => Ok(send_magic_link(
+                &state, &headers, &flow, request, params, query, &email,
+            )),
+            form => answer_consent(&state, &headers, &flow, addr.ip(), request, form),
+        });
+    if let Err(error) = &result {
+        record_rejection(&state.metrics, &flow, error);
+    }
+    let outcome = result.as_ref().map(|(outcome, _)| *outcome);
+    record_attempt(&state, &slug, return_url.as_deref(), addr.ip(), outcome);
+    result.map(|(_, response)| response)
+}
+
+/// [`verify`] for the podcast with the `<podcast:guid>`.
+async fn verify_by_guid(
+    State(state): State<AppState>,
+    connect_info: ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(guid): Path<String>,
+    query: RawQuery,
+    params: Query<VerifyParams>,
+) -> Result<(StatusCode, Html<String>), AppError> {
+    let podcast = guid_to_podcast(&state, &guid).ok_or(AppError::PodcastNotFound(guid))?;
+    verify(
+        State(state),
+        connect_info,
+        headers,
+        Path(podcast.slug),
+        query,
+        params,
+    )
+    .await
+}
+
+/// [`consent`] for the podcast with the `<podcast:guid>`, which the consent screen posts to when
+/// it was shown by GUID.
+async fn consent_by_guid(
+    State(state): State<AppState>,
+    connect_info: ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(guid): Path<String>,
+    query: RawQuery,
+    params: Query<VerifyParams>,
+    form: Form<ConsentForm>,
+) -> Result<Response, AppError> {
+    let podcast = guid_to_podcast(&state, &guid).ok_or(AppError::PodcastNotFound(guid))?;
+    consent(
+        State(state),
+        connect_info,
+        headers,
+        Path(podcast.slug),
+        query,
+        params,
+        form,
+    )
+    .await
+}
+
+#[derive(Deserialize)]
+struct MagicLinkParams {
+    token: String,
+}
+
+/// Completes a verification when the owner follows the sign-in link emailed to them.
+async fn magic_link(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+    Query(params): Query<MagicLinkParams>,
+) -> Result<Response, AppError> {
+    Span::current().record("slug", slug.as_str());
+    let Some(link) = state.magic_links.consume(&slug, &params.token) else {
+        let error = AppError::LinkExpired;
+        record_attempt(&state, &slug, None, addr.ip(), Err(&error));
+        return Err(error);
+    };
+    let return_url = link.params.return_url.clone();
+
+    let flow = state.traces.flow(&slug, link.query.as_deref());
+    flow.record(
+        StepKind::Request,
+        format!("GET /feed/{slug}/verify/magic, following the emailed sign-in link"),
+    );
+    let result = validate_request(&state, &flow, &slug, link.params).and_then(|request| {
+        flow.record(
+            StepKind::Validation,
+            "owner signed in with the emailed link",
+        );
+        approve(&state, &headers, &flow, request)
+    });
+    if let Err(error) = &result {
+        record_rejection(&state.metrics, &flow, error);
+    }
+    let outcome = result.as_ref().map(|_| attempts::Outcome::Approved);
+    record_attempt(&state, &slug, return_url.as_deref(), addr.ip(), outcome);
+    result
+}
+
+/// Counts, traces and adds to the request span a request to a verify URL.
+fn start_flow(
+    state: &AppState,
+    method: &Method,
+    slug: &str,
+    query: Option<&str>,
+    params: &VerifyParams,
+) -> trace::Flow {
+    Span::current().record("slug", slug);
+    state.metrics.verification_attempts.inc();
+
+    let flow = state.traces.flow(slug, query);
+    flow.record(
+        StepKind::Request,
+        format!(
+            "{method} /feed/{slug}/verify with returnUrl={:?} encryptedString={:?} state={:?} \
+             callbackUrl={:?} purpose={:?}",
+            params.return_url,
+            params.encrypted_string,
+            params.state,
+            params.callback_url,
+            params.purpose
+        ),
+    );
+    flow
+}
+
+/// A verification request whose parameters have been checked.
+struct VerifyRequest {
+    podcast: Podcast,
+    return_url: Url,
+    /// `return_url` with the app's `state`, where the user is sent back to whatever the outcome.
+    return_to: Url,
+    domain_name: String,
+    encrypted_string: String,
+    state: Option<String>,
+    callback_url: Option<Url>,
+    /// Whether the app wants to transfer the feed rather than only verify ownership.
+    transfer: bool,
+}
+
+fn validate_request(
+    state: &AppState,
+    flow: &trace::Flow,
+    slug: &str,
+    params: VerifyParams,
+) -> Result<VerifyRequest, AppError> {
+    let return_url = params
+        .return_url
+        .ok_or(AppError::MissingParam("returnUrl"))?;
+    let return_url = Url::parse(&return_url).map_err(|_| AppError::InvalidReturnUrl)?;
+    let domain_name = domain_name(&return_url).ok_or(AppError::InvalidReturnUrl)?;
+    let return_to = with_state(&return_url, params.state.as_deref());
+
+    Span::current().record("return_url_domain", domain_name.as_str());
+    flow.record(
+        StepKind::Validation,
+        format!("returnUrl is an http(s) URL on {domain_name}"),
+    );
+
+    if state.blocklist.is_blocked(&domain_name) {
+        flow.record(
+            StepKind::Redirect,
+            format!("{domain_name} is blocked, redirecting to returnUrl with error=access_denied"),
+        );
+        return Err(AppError::AccessDenied(return_to));
+    }
+    flow.record(
+        StepKind::Validation,
+        format!("{domain_name} is not on the blocklist"),
+    );
+    if !state.config().allows_return_to(&domain_name) {
+        flow.record(
+            StepKind::Redirect,
+            format!("{domain_name} is not on the allowlist, redirecting with error=access_denied"),
+        );
+        return Err(AppError::AccessDenied(return_to));
+    }
+
+    let podcast = slug_to_podcast(state.podcasts.all(), slug)
+        .filter(Podcast::is_published)
+        .ok_or_else(|| {
+            AppError::PodcastNotFound(slug.to_string()).in_flow(None, Some(return_to.clone()))
+        })?;
+    flow.record(
+        StepKind::Validation,
+        format!("podcast “{}” exists", podcast.title),
+    );
+
+    let encrypted_string = params.encrypted_string.ok_or_else(|| {
+        AppError::MissingParam("encryptedString")
+            .in_flow(Some(podcast.clone()), Some(return_to.clone()))
+    })?;
+    // Malformed strings are refused before the owner is asked to sign in, rather than after.
+    let key_len = podcast.crypto.key_bits / 8;
+    if encrypted_string.len() > crypto::MAX_ENCRYPTED_STRING_LEN
+        || crypto::decode_ciphertext(&encrypted_string, key_len).is_err()
+    {
+        return Err(AppError::InvalidParam("encryptedString")
+            .in_flow(Some(podcast.clone()), Some(return_to.clone())));
+    }
+    flow.record(
+        StepKind::Validation,
+        "encryptedString is base64 of a ciphertext for the podcast's key",
+    );
+
+    let callback_url = params
+        .callback_url
+        .map(|callback_url| {
+            validate_callback_url(state, &callback_url, &domain_name).ok_or_else(|| {
+                AppError::InvalidParam("callbackUrl")
+                    .in_flow(Some(podcast.clone()), Some(return_to.clone()))
+            })
+        })
+        .transpose()?;
+    if let Some(callback_url) = &callback_url {
+        flow.record(
+            StepKind::Validation,
+            format!("callbackUrl {callback_url} is on {domain_name}"),
+        );
+    }
+
+    let transfer = match params.purpose.as_deref() {
+        None | Some("verify") => false,
+        Some("transfer") => true,
+        Some(_) => {
+            return Err(AppError::InvalidParam("purpose")
+                .in_flow(Some(podcast.clone()), Some(return_to.clone())))
+        }
+    };
+    if transfer {
+        flow.record(StepKind::Validation, "the app asks to transfer the feed");
+    }
+
+    Ok(VerifyRequest {
+        podcast,
+        return_url,
+        return_to,
+        domain_name,
+        encrypted_string,
+        state: params.state,
+        callback_url,
+        transfer,
+    })
+}
+
+fn consent_screen(
+    state: &AppState,
+    headers: &HeaderMap,
+    flow: &trace::Flow,
+    request: VerifyRequest,
+    login_failed: bool,
+) -> (StatusCode, Html<String>) {
+    tracing::info!(outcome = "consent_shown", "verification started");
+    flow.record(StepKind::Render, "consent screen shown");
+    let base_url = state.config().public_base_url(headers);
+    view::verify(view::VerifyState::Neutral {
+        feed_url: request.podcast.feed_url(&base_url),
+        transfer_locked: request.transfer && request.podcast.locked,
+        key_fingerprints: key_fingerprints(state, &request.podcast),
+        podcast: Box::new(request.podcast),
+        return_url_scheme: request.return_url.scheme().to_string(),
+        app_identity: state.app_identities.lookup(&request.return_url),
+        domain_flagged: state.abuse_reports.is_flagged(&request.domain_name),
+        return_url_domain: request.domain_name,
+        login_failed,
+        trace_url: state
+            .config()
+            .dev_mode
+            .then(|| format!("/dev/flows/{}", flow.id())),
+    })
+}
+
+/// Fingerprints of the keys the podcast's feed advertises, current key first.
+fn key_fingerprints(state: &AppState, podcast: &Podcast) -> Vec<String> {
+    state
+        .keys
+        .get(&podcast.crypto)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|pair| tag::public_key_fingerprint(&pair.public_key).ok())
+        .collect()
+}
+
+fn answer_consent(
+    state: &AppState,
+    headers: &HeaderMap,
+    flow: &trace::Flow,
+    client_ip: IpAddr,
+    request: VerifyRequest,
+    form: ConsentForm,
+) -> Result<(attempts::Outcome, Response), AppError> {
+    if let Decision::Deny = form.decision {
+        tracing::info!(outcome = "denied", "verification denied");
+        flow.record(
+            StepKind::Redirect,
+            "owner denied, redirecting to returnUrl with error=access_denied",
+        );
+        state
+            .metrics
+            .verification_completions
+            .with_label_values(&["denied"])
+            .inc();
+        // Sent back the same way as an app that is blocked, so that it can't tell them apart.
+        let response = AppError::AccessDenied(request.return_to).into_response();
+        return Ok((attempts::Outcome::Denied, response));
+    }
+
+    // Checked before the password, so that guesses during a lockout reveal nothing.
+    if let Err(retry_after) = state.login_throttle.check(&form.email, client_ip) {
+        flow.record(StepKind::Validation, "too many failed logins, locked out");
+        // No redirect back to the app, so that the owner can go back and ask for a sign-in
+        // link instead.
+        return Err(AppError::LoginLocked {
+            retry_after_secs: retry_after.as_secs().max(1),
+        }
+        .in_flow(Some(request.podcast), None));
+    }
+
+    let owner = &request.podcast.owner;
+    if !state
+        .metrics
+        .time_login(|| owner.verify(&form.email, &form.password))
+    {
+        state.login_throttle.record_failure(&form.email, client_ip);
+        let error = AppError::InvalidCredentials;
+        record_rejection(&state.metrics, flow, &error);
+        let (_, page) = consent_screen(state, headers, flow, request, true);
+        let response = (error.status(), page).into_response();
+        return Ok((attempts::Outcome::InvalidCredentials, response));
+    }
+    state.login_throttle.record_success(&form.email, client_ip);
+    if let Decision::Report = form.decision {
+        flow.record(
+            StepKind::Validation,
+            "owner logged in and reported the request",
+        );
+        let response = report(state, flow, request)?;
+        return Ok((attempts::Outcome::Reported, response));
+    }
+    flow.record(StepKind::Validation, "owner logged in and approved");
+
+    let response = approve(state, headers, flow, request)?;
+    Ok((attempts::Outcome::Approved, response))
+}
+
+/// Emails the owner a link that approves the request, if `email` is the owner's. The page shown
+/// is the same either way.
+fn send_magic_link(
+    state: &AppState,
+    headers: &HeaderMap,
+    flow: &trace::Flow,
+    request: VerifyRequest,
+    params: VerifyParams,
+    query: Option<String>,
+    email: &str,
+) -> (attempts::Outcome, Response) {
+    let podcast = request.podcast;
+    let minutes = magic::LINK_TTL.as_secs() / 60;
+    let outcome = if credentials::matches(email, &podcast.owner.email) {
+        let token = state.magic_links.issue(&podcast.slug, params, query);
+        let mut link = state
+            .config()
+            .public_base_url(headers)
+            .join(&format!("feed/{}/verify/magic", podcast.slug))
+            .expect("slug forms a valid URL path");
+        link.query_pairs_mut().append_pair("token", &token);
+
+        state.mailer.send(
+            email,
+            &i18n::t_args(
+                "magic-link-subject",
+                &[("podcast", podcast.title.as_str().into())],
+            ),
+            &i18n::t_args(
+                "magic-link-body",
+                &[
+                    ("domain", request.domain_name.into()),
+                    ("podcast", podcast.title.as_str().into()),
+                    ("link", link.to_string().into()),
+                    ("minutes", minutes.into()),
+                ],
+            ),
+        );
+        flow.record(StepKind::Validation, "sign-in link emailed to the owner");
+        attempts::Outcome::SignInLinkSent
+    } else {
+        record_rejection(&state.metrics, flow, &AppError::InvalidCredentials);
+        attempts::Outcome::InvalidCredentials
+    };
+
+    flow.record(StepKind::Render, "sign-in link page shown");
+    let response = view::magic_link_sent(podcast, email, minutes).into_response();
+    (outcome, response)
+}
+
+/// Decrypts `encryptedString` for an owner who approved, and sends the result to the app.
+fn approve(
+    state: &AppState,
+    headers: &HeaderMap,
+    flow: &trace::Flow,
+    request: VerifyRequest,
+) -> Result<Response, AppError> {
+    let mut return_to = request.return_to.clone();
+    // Checked here rather than when the request is validated, so that the owner still sees the
+    // consent screen and why the app can't have the feed.
+    if request.transfer && request.podcast.locked {
+        flow.record(StepKind::Validation, "the feed is locked against transfers");
+        return Err(AppError::FeedLocked.in_flow(Some(request.podcast), Some(return_to)));
+    }
+    let padding = request.podcast.crypto.padding;
+    let pairs = state
+        .keys
+        .get(&request.podcast.crypto)
+        .map_err(|error| error.in_flow(Some(request.podcast.clone()), Some(return_to.clone())))?;
+    let decrypted_string = state
+        .metrics
+        .time_crypto("decrypt", || {
+            crypto::decrypt_with_any(&pairs, padding, &request.encrypted_string)
+        })
+        .map_err(|error| {
+            flow.record(
+                StepKind::Crypto,
+                format!("encryptedString doesn't decrypt: {error}"),
+            );
+            AppError::from(error).in_flow(Some(request.podcast.clone()), Some(return_to.clone()))
+        })?;
+    flow.record(
+        StepKind::Crypto,
+        format!("encryptedString decrypted with {}", padding.name()),
+    );
+    let signing_key = state
+        .keys
+        .signing()
+        .map_err(|error| error.in_flow(Some(request.podcast.clone()), Some(return_to.clone())))?;
+    let base_url = state.config().public_base_url(headers);
+    let signed_verification = sign_verification(
+        &state.metrics,
+        &signing_key,
+        feed_identity(state, &request.podcast, &base_url),
+        &decrypted_string,
+        &request.return_url,
+    )?;
+    flow.record(
+        StepKind::Crypto,
+        format!(
+            "decryptedString signed for {}",
+            signed_verification.audience
+        ),
+    );
+
+    let grant = state
+        .grants
+        .grant(
+            &request.podcast.slug,
+            &request.domain_name,
+            request.state.clone(),
+            request.callback_url.clone(),
+        )
+        .map_err(AppError::Storage)?;
+    flow.record(StepKind::Validation, format!("grant {} recorded", grant.id));
+
+    let feed_url = request.podcast.feed_url(&base_url);
+    let signed_verification = signed_verification.to_token();
+    let subscribers = state.completions.complete(
+        &request.podcast.slug,
+        &decrypted_string,
+        events::Completion {
+            feed_url: feed_url.clone(),
+            decrypted_string: decrypted_string.clone(),
+            grant_id: grant.id.clone(),
+            signed_verification: signed_verification.clone(),
+            state: request.state.clone(),
+        },
+    );
+    if subscribers > 0 {
+        flow.record(
+            StepKind::Callback,
+            format!("pushed decryptedString to {subscribers} event stream(s)"),
+        );
+    }
+
+    if let Some(callback_url) = request.callback_url.clone() {
+        state.callbacks.deliver(
+            callback_url.clone(),
+            "verification.approved",
+            &callback::VerificationResult {
+                grant_id: grant.id.clone(),
+                feed_url,
+                decrypted_string: decrypted_string.clone(),
+                state: request.state.clone(),
+                verified_at: unix_timestamp(),
+            },
+        );
+        flow.record(
+            StepKind::Callback,
+            format!("posting decryptedString to {callback_url}"),
+        );
+    }
+
+    tracing::info!(outcome = "approved", "verification approved");
+    flow.record(
+        StepKind::Redirect,
+        "redirecting to returnUrl with decryptedString",
+    );
+    state
+        .metrics
+        .verification_completions
+        .with_label_values(&["approved"])
+        .inc();
+    return_to
+        .query_pairs_mut()
+        .append_pair("decryptedString", &decrypted_string)
+        .append_pair("grantId", &grant.id)
+        .append_pair("signedVerification", &signed_verification);
+    let delay_secs = state.config().redirect_delay_secs;
+    if delay_secs == 0 {
+        return Ok(Redirect::to(return_to.as_str()).into_response());
+    }
+    let page = view::verified(
+        request.podcast,
+        &request.domain_name,
+        &return_to,
+        delay_secs,
+    );
+    Ok(page.into_response())
+}
+
+/// How signed verifications identify the podcast's feed.
+fn feed_identity(state: &AppState, podcast: &Podcast, base_url: &Url) -> handshake::FeedIdentity {
+    handshake::FeedIdentity {
+        guid: Some(podcast.guid(&state.config().public_base_url)),
+        url: Some(podcast.feed_url(base_url).to_string()),
+    }
+}
+
+/// Signs the decrypted challenge from `feed` for the app at `return_url` with the signing key,
+/// which the feed advertises in `signingKey`.
+fn sign_verification(
+    metrics: &metrics::Metrics,
+    signing_key: &crypto::KeyPair,
+    feed: handshake::FeedIdentity,
+    decrypted_string: &str,
+    return_url: &Url,
+) -> Result<handshake::SignedVerification, AppError> {
+    metrics
+        .time_crypto("sign", || {
+            handshake::SignedVerification::sign(
+                &signing_key.private_key,
+                decrypted_string,
+                return_url,
+                feed,
+                unix_timestamp(),
+            )
+        })
+        .map_err(|e| AppError::CryptoFailure(format!("failed to sign verification: {e}")))
+}
+
+/// Logs, counts and traces a verification request that could not proceed.
+fn record_rejection(metrics: &metrics::Metrics, flow: &trace::Flow, error: &AppError) {
+    let reason = error.reason();
+    tracing::info!(outcome = reason, "verification rejected");
+    flow.record(StepKind::Rejection, reason);
+    metrics
+        .verification_failures
+        .with_label_values(&[reason])
+        .inc();
+}
+
+/// Adds a request to a podcast's verify URL to the log its owner can review. Requests for
+/// podcasts that don't exist aren't logged, as there is no owner to show them to.
+fn record_attempt(
+    state: &AppState,
+    slug: &str,
+    return_url: Option<&str>,
+    client_ip: IpAddr,
+    outcome: Result<attempts::Outcome, &AppError>,
+) {
+    if slug_to_podcast(state.podcasts.all(), slug).is_none() {
+        return;
+    }
+    let (outcome, reason) = match outcome {
+        Ok(outcome) => (outcome, None),
+        Err(error) => (
+            attempts::Outcome::Rejected,
+            Some(error.reason().to_string()),
+        ),
+    };
+    state.attempts.record(attempts::Attempt {
+        timestamp: unix_timestamp(),
+        slug: slug.to_string(),
+        domain: return_url
+            .and_then(|return_url| Url::parse(return_url).ok())
+            .as_ref()
+            .and_then(domain_name),
+        outcome,
+        reason,
+        client_ip,
+    });
+}
+
+/// Accepts a `callbackUrl` only if it is on the same host as `returnUrl`, which is registered for
+/// callbacks, and on an address the server may post to, so that the server can't be used to post
+/// to arbitrary hosts.
+fn validate_callback_url(state: &AppState, callback_url: &str, domain_name: &str) -> Option<Url> {
+    if !state.callbacks.is_registered(domain_name) {
+        return None;
+    }
+    let callback_url = Url::parse(callback_url).ok()?;
+    let same_host = self::domain_name(&callback_url).as_deref() == Some(domain_name);
+    (same_host && state.callbacks.allows(&callback_url)).then_some(callback_url)
+}
+
+/// Appends the app's `state`, if it sent one, to its `returnUrl`.
+fn with_state(return_url: &Url, state: Option<&str>) -> Url {
+    let mut return_url = return_url.clone();
+    if let Some(state) = state {
+        return_url.query_pairs_mut().append_pair("state", state);
+    }
+    return_url
+}
+
+/// Returns the host of the URL, including the port if it is not the scheme's default. URLs that
+/// aren't `http` or `https` have none: a `javascript:` URL can have a host too, and would run
+/// script on our pages when the owner is sent back to it.
+fn domain_name(url: &Url) -> Option<String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    let domain_name = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", domain_name, port),
+        None => domain_name.to_string(),
+    })
+}
+
+/// Records an abuse report for a request its owner logged in to report, and with
+/// `auto_block_reported_domains`, blocks its directory domain. Phishing pages don't need a real
+/// challenge, so the report is taken whether or not it decrypts, and records which.
+fn report(
+    state: &AppState,
+    flow: &trace::Flow,
+    request: VerifyRequest,
+) -> Result<Response, AppError> {
+    let podcast = request.podcast;
+    let decrypted = state.keys.get(&podcast.crypto).is_ok_and(|pairs| {
+        crypto::decrypt_with_any(&pairs, podcast.crypto.padding, &request.encrypted_string).is_ok()
+    });
+    flow.record(
+        StepKind::Crypto,
+        if decrypted {
+            "encryptedString decrypts"
+        } else {
+            "encryptedString doesn't decrypt"
+        },
+    );
+
+    state.abuse_reports.report(abuse::AbuseReport {
+        slug: podcast.slug.clone(),
+        domain: request.domain_name.clone(),
+        return_url: request.return_url.to_string(),
+        encrypted_string: Some(request.encrypted_string),
+        decrypted,
+        reported_at: unix_timestamp(),
+    });
+
+    if state.config().auto_block_reported_domains {
+        let reason = format!("Reported by the owner of “{}”", podcast.slug);
+        state
+            .blocklist
+            .block(&request.domain_name, "abuse-report", Some(reason))
+            .map_err(AppError::Storage)?;
+        flow.record(
+            StepKind::Validation,
+            format!("{} blocked pending review", request.domain_name),
+        );
+    }
+    let blocked = state.blocklist.is_blocked(&request.domain_name);
+
+    flow.record(StepKind::Render, "report confirmation shown");
+    Ok(view::reported(podcast, &request.domain_name, blocked).into_response())
+}
+
+/// Seconds since the Unix epoch, as timestamps are persisted and signed.
+pub(crate) fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Lockouts never last longer than this, however many guesses were made.
+const MAX_LOCKOUT: Duration = Duration::from_secs(60 * 60);
+/// Failures are forgotten once there has been none for this long.
+const FORGET_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+/// Entries are pruned once the table grows past this many keys, as in the rate limiter.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Failed owner logins, counted per email address and per client IP so that guessing is slowed
+/// down both for one account and from one client.
+///
+/// After `threshold` consecutive failures the key is locked for `lockout`, doubled for every
+/// failure after that. A successful login clears the count for its email address and IP.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    failures: Arc<Mutex<HashMap<String, Failures>>>,
+    threshold: u32,
+    lockout: Duration,
+}
+
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+impl LoginThrottle {
+    pub fn new(threshold: u32, lockout: Duration) -> Self {
+        LoginThrottle {
+            failures: Arc::new(Mutex::new(HashMap::new())),
+            threshold,
+            lockout,
+        }
+    }
+
+    /// Returns how long to wait if logins for the email address or from the IP are locked.
+    pub fn check(&self, email: &str, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let failures = self.failures.lock().unwrap();
+        let retry_after = keys(email, ip)
+            .iter()
+            .filter_map(|key| failures.get(key)?.locked_until)
+            .filter_map(|locked_until| locked_until.checked_duration_since(now))
+            .max();
+        retry_after.map_or(Ok(()), Err)
+    }
+
+    pub fn record_failure(&self, email: &str, ip: IpAddr) {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+        if failures.len() > PRUNE_THRESHOLD {
+            failures.retain(|_, entry| now.duration_since(entry.last) < FORGET_AFTER);
+        }
+
+        for key in keys(email, ip) {
+            let entry = failures.entry(key).or_insert(Failures {
+                count: 0,
+                last: now,
+                locked_until: None,
+            });
+            if now.duration_since(entry.last) >= FORGET_AFTER {
+                entry.count = 0;
+            }
+            entry.count += 1;
+            entry.last = now;
+            if entry.count >= self.threshold {
+                entry.locked_until = Some(now + self.lockout_for(entry.count));
+            }
+        }
+    }
+
+    pub fn record_success(&self, email: &str, ip: IpAddr) {
+        let mut failures = self.failures.lock().unwrap();
+        for key in keys(email, ip) {
+            failures.remove(&key);
+        }
+    }
+
+    fn lockout_for(&self, count: u32) -> Duration {
+        let doublings = (count - self.threshold).min(16);
+        self.lockout
+            .checked_mul(1 << doublings)
+            .map_or(MAX_LOCKOUT, |lockout| lockout.min(MAX_LOCKOUT))
+    }
+}
+
+/// Email addresses are compared case-insensitively, so that changing case doesn't reset the
+/// count.
+fn keys(email: &str, ip: IpAddr) -> [String; 2] {
+    [
+        format!("email:{}", email.trim().to_lowercase()),
+        format!("ip:{ip}"),
+    ]
+}
@@ -0,0 +1,84 @@
+use crate::VerifyParams;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a sign-in link can be used for.
+pub const LINK_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Sends email to podcast owners.
+pub trait Mailer: Send + Sync {
+    fn send(&self, to: &str, subject: &str, body: &str);
+}
+
+/// Writes email to the log instead of sending it, for the demo.
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send(&self, to: &str, subject: &str, body: &str) {
+        tracing::info!(to, subject, "email not sent, logged instead:\n{body}");
+    }
+}
+
+/// A verification request waiting for the owner to follow the link emailed to them.
+pub struct PendingLink {
+    pub slug: String,
+    pub params: VerifyParams,
+    /// The verify URL's query string, which identifies the flow in protocol traces.
+    pub query: Option<String>,
+    expires_at: Instant,
+}
+
+/// One-time tokens for sign-in links. Only hashes of the tokens are kept, so the store doesn't
+/// hold anything that could be used to sign in.
+#[derive(Clone, Default)]
+pub struct MagicLinks {
+    pending: Arc<Mutex<HashMap<[u8; 32], PendingLink>>>,
+}
+
+impl MagicLinks {
+    /// Stores the request and returns the token for its link.
+    pub fn issue(&self, slug: &str, params: VerifyParams, query: Option<String>) -> String {
+        let token = new_token();
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, link| link.expires_at > now);
+        pending.insert(
+            hash(&token),
+            PendingLink {
+                slug: slug.to_string(),
+                params,
+                query,
+                expires_at: now + LINK_TTL,
+            },
+        );
+        token
+    }
+
+    /// Takes the request for a token if it exists, has not expired and belongs to the podcast.
+    /// A token can only be consumed once.
+    pub fn consume(&self, slug: &str, token: &str) -> Option<PendingLink> {
+        let mut pending = self.pending.lock().unwrap();
+        let key = hash(token);
+        let link = pending.get(&key)?;
+        if link.slug != slug {
+            return None;
+        }
+        pending
+            .remove(&key)
+            .filter(|link| link.expires_at > Instant::now())
+    }
+}
+
+/// A random token, to be given out and looked up by its [`hash`].
+pub fn new_token() -> String {
+    let mut token = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut token);
+    token.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn hash(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
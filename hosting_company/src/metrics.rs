@@ -0,0 +1,162 @@
+use crate::admin::ApiAdmin;
+use crate::AppState;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use prometheus::{
+    exponential_buckets, Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts,
+    Registry, TextEncoder,
+};
+use std::time::Instant;
+
+/// Counters and histograms exposed on `/metrics` in the Prometheus text format.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    /// Feed requests, labelled by `outcome`.
+    pub feed_requests: IntCounterVec,
+    pub verification_attempts: IntCounter,
+    /// Verification requests that could not proceed, labelled by `reason`.
+    pub verification_failures: IntCounterVec,
+    /// Verification requests the owner answered, labelled by `outcome` (`approved` or `denied`).
+    pub verification_completions: IntCounterVec,
+    /// Callbacks to apps, labelled by `outcome` (`delivered` or `abandoned`).
+    pub callback_deliveries: IntCounterVec,
+    /// Time spent in RSA operations, labelled by `operation` (`decrypt` or `sign`).
+    pub crypto_duration: HistogramVec,
+    /// Time spent checking owners' credentials, labelled by `outcome` (`valid` or `invalid`).
+    /// The two should look alike: a gap would tell attackers which email addresses are owners'.
+    pub login_check_duration: HistogramVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let registry = Registry::new();
+
+        let feed_requests = IntCounterVec::new(
+            Opts::new("feed_requests_total", "Feed requests by outcome."),
+            &["outcome"],
+        )
+        .unwrap();
+        let verification_attempts = IntCounter::new(
+            "verification_attempts_total",
+            "Requests to a verifyUrl, whatever their outcome.",
+        )
+        .unwrap();
+        let verification_failures = IntCounterVec::new(
+            Opts::new(
+                "verification_failures_total",
+                "Verification requests that could not proceed, by reason.",
+            ),
+            &["reason"],
+        )
+        .unwrap();
+        let verification_completions = IntCounterVec::new(
+            Opts::new(
+                "verification_completions_total",
+                "Verification requests the owner approved or denied.",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+        let callback_deliveries = IntCounterVec::new(
+            Opts::new(
+                "callback_deliveries_total",
+                "Verification results posted to apps' callback URLs, by outcome.",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+
+        // RSA operations take from a fraction of a millisecond to tens of milliseconds, and
+        // credential checks microseconds.
+        let crypto_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "crypto_duration_seconds",
+                "Time spent decrypting challenges and signing verifications.",
+            )
+            .buckets(exponential_buckets(0.0001, 2.0, 14).unwrap()),
+            &["operation"],
+        )
+        .unwrap();
+        let login_check_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "login_check_duration_seconds",
+                "Time spent checking owners' credentials, by outcome.",
+            )
+            .buckets(exponential_buckets(0.000_001, 4.0, 10).unwrap()),
+            &["outcome"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(feed_requests.clone())).unwrap();
+        registry
+            .register(Box::new(verification_attempts.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(verification_failures.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(verification_completions.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(callback_deliveries.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(crypto_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(login_check_duration.clone()))
+            .unwrap();
+
+        Metrics {
+            registry,
+            feed_requests,
+            verification_attempts,
+            verification_failures,
+            verification_completions,
+            callback_deliveries,
+            crypto_duration,
+            login_check_duration,
+        }
+    }
+}
+
+impl Metrics {
+    /// Runs the RSA `operation`, recording how long it took.
+    pub fn time_crypto<T>(&self, operation: &str, run: impl FnOnce() -> T) -> T {
+        let _timer = self
+            .crypto_duration
+            .with_label_values(&[operation])
+            .start_timer();
+        run()
+    }
+
+    /// Runs a credential check, recording how long it took by whether the credentials were valid.
+    pub fn time_login(&self, check: impl FnOnce() -> bool) -> bool {
+        let start = Instant::now();
+        let valid = check();
+        let outcome = if valid { "valid" } else { "invalid" };
+        self.login_check_duration
+            .with_label_values(&[outcome])
+            .observe(start.elapsed().as_secs_f64());
+        valid
+    }
+
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("failed to encode metrics");
+        String::from_utf8(buffer).expect("metrics are not valid UTF-8")
+    }
+}
+
+/// Serves the metrics to scrapers with the admin token as a Bearer token, as they reveal how
+/// often verifications fail and how long credential checks take.
+pub async fn metrics(_: ApiAdmin, State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        state.metrics.render(),
+    )
+}
@@ -0,0 +1,235 @@
+//! Requests the server sends to URLs that apps choose, such as their `callbackUrl`s. An app could
+//! point one at the server's own network, e.g. a cloud metadata service or an admin port, so
+//! requests only go to public addresses: [`allows`] checks the URL, [`PublicResolver`] drops
+//! every other address a host resolves to, and each redirect is checked before it is followed.
+//!
+//! ```ignore
+//! let client = outbound::client_builder(3, false, config.outbound_proxy.as_ref()).build()?;
+//! if outbound::allows(&url, false) {
+//!     client.get(url).send().await?;
+//! }
+//! ```
+//!
+//! Requests go through the [`Proxy`] configured for the server if there is one, and otherwise,
+//! like any reqwest client's, through the proxies in `HTTPS_PROXY`, `HTTP_PROXY` and `ALL_PROXY`
+//! (or their lowercase forms) except for hosts in `NO_PROXY`. Proxies are the operator's choice,
+//! so their own addresses aren't checked, but they resolve the hosts they connect to themselves
+//! and have to refuse internal addresses on their own.
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::NoProxy;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use url::{Host, Url};
+
+/// Environment variables reqwest reads proxies from.
+const PROXY_VARIABLES: [&str; 6] = [
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+];
+
+/// A proxy that requests go through instead of those in the environment.
+#[derive(Clone)]
+pub struct Proxy {
+    /// An `http` or `https` URL, with the proxy's credentials if it needs any.
+    pub url: Url,
+    /// Hosts requests go to directly, comma-separated as in `NO_PROXY`.
+    pub no_proxy: Option<String>,
+}
+
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut url = self.url.clone();
+        if url.password().is_some() {
+            let _ = url.set_password(Some("redacted"));
+        }
+        f.debug_struct("Proxy")
+            .field("url", &url.as_str())
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+/// Whether `ip` is on the public internet: not loopback, private, link-local, unique local,
+/// shared (carrier-grade NAT), unspecified, broadcast, multicast, reserved or set aside for
+/// documentation. IPv6 addresses that embed an IPv4 address are judged by it.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match embedded_v4(ip) {
+            Some(v4) => is_public_v4(v4),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "This network", shared address space (RFC 6598), IETF protocol assignments,
+        // benchmarking (RFC 2544) and reserved.
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local (fc00::/7), link-local (fe80::/10), the deprecated site-local
+        // (fec0::/10) and documentation (2001:db8::/32).
+        || first & 0xfe00 == 0xfc00
+        || first & 0xffc0 == 0xfe80
+        || first & 0xffc0 == 0xfec0
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+/// The IPv4 address in an IPv4-mapped (`::ffff:0:0/96`), IPv4-compatible or NAT64
+/// (`64:ff9b::/96`) address.
+fn embedded_v4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let [a, b, c, d, e, f, _, _] = ip.segments();
+    let prefix = (a, b, c, d, e, f);
+    let mapped = prefix == (0, 0, 0, 0, 0, 0xffff);
+    let nat64 = prefix == (0x64, 0xff9b, 0, 0, 0, 0);
+    let compatible = prefix == (0, 0, 0, 0, 0, 0) && !ip.is_loopback() && !ip.is_unspecified();
+    let [.., w, x, y, z] = ip.octets();
+    (mapped || nat64 || compatible).then(|| Ipv4Addr::new(w, x, y, z))
+}
+
+/// Whether requests may go to `ip`: public addresses, and with `allow_loopback`, loopback ones
+/// too, for local testing.
+fn allowed(ip: IpAddr, allow_loopback: bool) -> bool {
+    is_public(ip) || (allow_loopback && ip.is_loopback())
+}
+
+/// Whether a request may be sent to `url`: an `https` URL, or with `allow_loopback`, also an
+/// `http` one on a loopback host, that doesn't name an address requests may not go to. Hosts
+/// given by name are checked once [`PublicResolver`] resolves them.
+pub fn allows(url: &Url, allow_loopback: bool) -> bool {
+    let (loopback, address_allowed) = match url.host() {
+        Some(Host::Domain(domain)) => (domain.eq_ignore_ascii_case("localhost"), true),
+        Some(Host::Ipv4(ip)) => (ip.is_loopback(), allowed(ip.into(), allow_loopback)),
+        Some(Host::Ipv6(ip)) => (ip.is_loopback(), allowed(ip.into(), allow_loopback)),
+        None => return false,
+    };
+    let scheme_allowed = match url.scheme() {
+        "https" => true,
+        "http" => allow_loopback && loopback,
+        _ => false,
+    };
+    scheme_allowed && address_allowed
+}
+
+/// Resolves hosts with the system's resolver, keeping only the addresses requests may go to. A
+/// host with none left fails to resolve, so the request fails before anything is sent.
+pub struct PublicResolver {
+    allow_loopback: bool,
+    /// Hosts of the proxies requests go through, which are resolved as they are.
+    proxies: Vec<String>,
+}
+
+impl PublicResolver {
+    /// A resolver for requests through `proxy`, or without one, the proxies in the environment.
+    pub fn new(allow_loopback: bool, proxy: Option<&Proxy>) -> Self {
+        let proxies = match proxy {
+            Some(proxy) => proxy_host(proxy.url.as_str()).into_iter().collect(),
+            None => PROXY_VARIABLES
+                .iter()
+                .filter_map(|variable| std::env::var(variable).ok())
+                .filter_map(|proxy| proxy_host(&proxy))
+                .collect(),
+        };
+        PublicResolver {
+            allow_loopback,
+            proxies,
+        }
+    }
+}
+
+/// The host of a proxy as given in the environment or config, with or without a scheme.
+fn proxy_host(proxy: &str) -> Option<String> {
+    let proxy = proxy.trim();
+    let url = Url::parse(proxy)
+        .ok()
+        .filter(Url::has_host)
+        .or_else(|| Url::parse(&format!("http://{proxy}")).ok())?;
+    Some(
+        url.host_str()?
+            .trim_matches(['[', ']'])
+            .to_ascii_lowercase(),
+    )
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_ascii_lowercase();
+        let is_proxy = self.proxies.contains(&host);
+        let allow_loopback = self.allow_loopback;
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_proxy || allowed(addr.ip(), allow_loopback))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{host} has no public address").into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Follows at most `max_redirects` redirects, and only to URLs that [`allows`] accepts.
+fn redirect_policy(max_redirects: usize, allow_loopback: bool) -> Policy {
+    Policy::custom(move |attempt| {
+        if attempt.previous().len() >= max_redirects {
+            attempt.error("too many redirects")
+        } else if !allows(attempt.url(), allow_loopback) {
+            let error = format!("refused to follow a redirect to {}", attempt.url());
+            attempt.error(error)
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// A client builder that resolves hosts with [`PublicResolver`] and follows at most
+/// `max_redirects` redirects, each only if [`allows`] accepts where it leads. Requests go through
+/// `proxy` if given, and through the proxies in the environment otherwise.
+pub fn client_builder(
+    max_redirects: usize,
+    allow_loopback: bool,
+    proxy: Option<&Proxy>,
+) -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder()
+        .dns_resolver(Arc::new(PublicResolver::new(allow_loopback, proxy)))
+        .redirect(redirect_policy(max_redirects, allow_loopback));
+    match proxy {
+        // Setting a proxy also stops reqwest from reading them from the environment.
+        Some(proxy) => {
+            let no_proxy = proxy.no_proxy.as_deref().and_then(NoProxy::from_string);
+            let all = reqwest::Proxy::all(proxy.url.clone())
+                .expect("proxy URLs are http or https")
+                .no_proxy(no_proxy);
+            builder.proxy(all)
+        }
+        None => builder,
+    }
+}
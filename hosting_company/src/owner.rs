@@ -0,0 +1,313 @@
+use crate::attempts::Attempt;
+use crate::callback::GrantRevocation;
+use crate::csrf::CsrfForm;
+use crate::error::AppError;
+use crate::grants::Grant;
+use crate::{csrf, slug_to_podcast, view, AppState, Podcast};
+use axum::extract::{ConnectInfo, Form, FromRequestParts, Path, Query, State};
+use axum::headers::authorization::Basic;
+use axum::headers::{Authorization, HeaderMapExt};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::{async_trait, Json};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use url::Url;
+
+/// The owner of the podcast in the path, signed in with HTTP Basic credentials: their email
+/// address and password. Failed sign-ins count towards the same lockout as the consent screen.
+pub struct Owner(pub Podcast);
+
+#[async_trait]
+impl FromRequestParts<AppState> for Owner {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Response> {
+        let Path(params) = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let slug = params.get("slug").cloned().unwrap_or_default();
+        let ConnectInfo(addr) = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let podcast = slug_to_podcast(state.podcasts.all(), &slug)
+            .ok_or_else(|| AppError::PodcastNotFound(slug).into_response())?;
+
+        let unauthorized = || {
+            (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Basic realm=\"owner\"")],
+            )
+                .into_response()
+        };
+        let Some(credentials) = parts.headers.typed_get::<Authorization<Basic>>() else {
+            return Err(unauthorized());
+        };
+
+        let ip = addr.ip();
+        if let Err(retry_after) = state.login_throttle.check(credentials.username(), ip) {
+            return Err(AppError::LoginLocked {
+                retry_after_secs: retry_after.as_secs().max(1),
+            }
+            .into_response());
+        }
+        if !state.metrics.time_login(|| {
+            podcast
+                .owner
+                .verify(credentials.username(), credentials.password())
+        }) {
+            state
+                .login_throttle
+                .record_failure(credentials.username(), ip);
+            return Err(unauthorized());
+        }
+        state
+            .login_throttle
+            .record_success(credentials.username(), ip);
+        Ok(Owner(podcast))
+    }
+}
+
+pub async fn dashboard(Owner(podcast): Owner, State(state): State<AppState>) -> Html<String> {
+    let grants = state.grants.for_podcast(&podcast.slug);
+    let attempts = state.attempts.for_podcast(&podcast.slug);
+    let csrf_token = state.csrf.token(&csrf::owner_scope(&podcast.slug));
+    view::dashboard(podcast, grants, attempts, &csrf_token)
+}
+
+/// Takes the owner's draft live.
+pub async fn publish(
+    Owner(podcast): Owner,
+    State(state): State<AppState>,
+    Form(form): Form<CsrfForm>,
+) -> Result<Redirect, AppError> {
+    state
+        .csrf
+        .check(&csrf::owner_scope(&podcast.slug), &form.csrf)?;
+    publish_podcast(&state, &podcast);
+    Ok(Redirect::to(&format!("/feed/{}/dashboard", podcast.slug)))
+}
+
+/// The body of [`api_publish`]: an empty JSON object.
+#[derive(Deserialize)]
+pub struct PublishRequest {}
+
+/// Like [`publish`]. The body has to be JSON, which browsers only send to another site once it
+/// allows them to (CORS), so another site can't make a signed-in owner's browser publish.
+/// Publishing an already published podcast changes nothing.
+pub async fn api_publish(
+    Owner(podcast): Owner,
+    State(state): State<AppState>,
+    Json(_): Json<PublishRequest>,
+) -> StatusCode {
+    publish_podcast(&state, &podcast);
+    StatusCode::NO_CONTENT
+}
+
+fn publish_podcast(state: &AppState, podcast: &Podcast) {
+    if state.podcasts.publish(&podcast.slug) {
+        tracing::info!(slug = podcast.slug, "podcast published");
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LockForm {
+    locked: bool,
+    csrf: String,
+}
+
+#[derive(Deserialize)]
+pub struct LockRequest {
+    locked: bool,
+}
+
+/// Locks the feed against transfers to another host, or unlocks it.
+pub async fn lock(
+    Owner(podcast): Owner,
+    State(state): State<AppState>,
+    Form(form): Form<LockForm>,
+) -> Result<Redirect, AppError> {
+    state
+        .csrf
+        .check(&csrf::owner_scope(&podcast.slug), &form.csrf)?;
+    lock_podcast(&state, &podcast, form.locked);
+    Ok(Redirect::to(&format!("/feed/{}/dashboard", podcast.slug)))
+}
+
+/// Like [`lock`], with `{"locked": true}` or `{"locked": false}`.
+pub async fn api_lock(
+    Owner(podcast): Owner,
+    State(state): State<AppState>,
+    Json(request): Json<LockRequest>,
+) -> StatusCode {
+    lock_podcast(&state, &podcast, request.locked);
+    StatusCode::NO_CONTENT
+}
+
+fn lock_podcast(state: &AppState, podcast: &Podcast, locked: bool) {
+    if state.podcasts.set_locked(&podcast.slug, locked) {
+        tracing::info!(slug = podcast.slug, locked, "podcast lock changed");
+    }
+}
+
+pub async fn api_attempts(
+    Owner(podcast): Owner,
+    State(state): State<AppState>,
+) -> Json<Vec<Attempt>> {
+    Json(state.attempts.for_podcast(&podcast.slug))
+}
+
+#[derive(Deserialize)]
+pub struct RevokeParams {
+    /// Whether to tell the app through its `callbackUrl`.
+    #[serde(default)]
+    notify: bool,
+}
+
+#[derive(Deserialize)]
+pub struct RevokeForm {
+    /// Whether to tell the app through its `callbackUrl`.
+    #[serde(default)]
+    notify: bool,
+    csrf: String,
+}
+
+pub async fn revoke(
+    owner: Owner,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((_, id)): Path<(String, String)>,
+    Form(form): Form<RevokeForm>,
+) -> Result<Redirect, AppError> {
+    let slug = owner.0.slug.clone();
+    state.csrf.check(&csrf::owner_scope(&slug), &form.csrf)?;
+    revoke_grant(&state, &headers, owner, &id, form.notify)?;
+    Ok(Redirect::to(&format!("/feed/{slug}/dashboard")))
+}
+
+pub async fn api_grants(Owner(podcast): Owner, State(state): State<AppState>) -> Json<Vec<Grant>> {
+    Json(state.grants.for_podcast(&podcast.slug))
+}
+
+pub async fn api_revoke(
+    owner: Owner,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((_, id)): Path<(String, String)>,
+    Query(params): Query<RevokeParams>,
+) -> Result<StatusCode, AppError> {
+    revoke_grant(&state, &headers, owner, &id, params.notify)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn revoke_grant(
+    state: &AppState,
+    headers: &HeaderMap,
+    Owner(podcast): Owner,
+    id: &str,
+    notify: bool,
+) -> Result<(), AppError> {
+    let grant = state
+        .grants
+        .revoke(&podcast.slug, id)
+        .map_err(AppError::Storage)?
+        .ok_or(AppError::NotFound)?;
+    tracing::info!(slug = podcast.slug, grant = grant.id, "grant revoked");
+
+    if let (true, Some(callback_url)) = (notify, grant.callback_url) {
+        let base_url = state.config().public_base_url(headers);
+        state.callbacks.deliver(
+            callback_url,
+            "grant.revoked",
+            &GrantRevocation {
+                grant_id: grant.id,
+                feed_url: podcast.feed_url(&base_url),
+                state: grant.state,
+                revoked_at: grant.revoked_at.unwrap_or_default(),
+            },
+        );
+    }
+    Ok(())
+}
+
+/// A grant's status, as apps see it.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrantStatus {
+    id: String,
+    feed_url: Url,
+    status: &'static str,
+    granted_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revoked_at: Option<u64>,
+}
+
+/// An active grant, as listed publicly for its feed.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Verification {
+    grant_id: String,
+    domain: String,
+    verified_at: u64,
+}
+
+#[derive(Deserialize)]
+pub struct VerificationsParams {
+    /// Lists only the grants to this domain.
+    domain: Option<String>,
+}
+
+/// Lists the feed's active grants, so that apps and directories can check whether a domain is
+/// verified for a feed without sending its owner through the flow.
+pub async fn verifications(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    Query(params): Query<VerificationsParams>,
+) -> Result<Json<Vec<Verification>>, AppError> {
+    let podcast = slug_to_podcast(state.podcasts.all(), &slug)
+        .filter(Podcast::is_published)
+        .ok_or(AppError::PodcastNotFound(slug))?;
+    let verifications = state
+        .grants
+        .for_podcast(&podcast.slug)
+        .into_iter()
+        .filter(|grant| !grant.is_revoked())
+        .filter(|grant| {
+            params
+                .domain
+                .as_deref()
+                .is_none_or(|domain| grant.domain.eq_ignore_ascii_case(domain))
+        })
+        .map(|grant| Verification {
+            grant_id: grant.id,
+            domain: grant.domain,
+            verified_at: grant.granted_at,
+        })
+        .collect();
+    Ok(Json(verifications))
+}
+
+/// Lets apps poll whether a grant is still valid. No credentials are needed, as active grants
+/// are public anyway.
+pub async fn grant_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<GrantStatus>, AppError> {
+    let grant = state.grants.get(&id).ok_or(AppError::NotFound)?;
+    let podcast = slug_to_podcast(state.podcasts.all(), &grant.slug).ok_or(AppError::NotFound)?;
+    let base_url = state.config().public_base_url(&headers);
+    Ok(Json(GrantStatus {
+        id: grant.id.clone(),
+        feed_url: podcast.feed_url(&base_url),
+        status: if grant.is_revoked() {
+            "revoked"
+        } else {
+            "active"
+        },
+        granted_at: grant.granted_at,
+        revoked_at: grant.revoked_at,
+    }))
+}
@@ -0,0 +1,237 @@
+//! Owners changing their password: from the dashboard while they know it, or with a reset link
+//! emailed to them when they've forgotten it.
+//!
+//! Passwords are kept with the podcasts, in memory, so a changed password lasts until the
+//! server restarts.
+
+use crate::error::AppError;
+use crate::magic::{hash, new_token};
+use crate::owner::Owner;
+use crate::{credentials, csrf};
+use crate::{i18n, view, AppState, Podcast};
+use axum::extract::{ConnectInfo, Form, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Html;
+use axum::Json;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a reset link can be used for.
+pub const RESET_TTL: Duration = Duration::from_secs(30 * 60);
+/// The fewest characters a new password may have.
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+struct PendingReset {
+    email: String,
+    expires_at: Instant,
+}
+
+/// One-time tokens for reset links. As with sign-in links, only hashes of the tokens are kept.
+#[derive(Clone, Default)]
+pub struct PasswordResets {
+    pending: Arc<Mutex<HashMap<[u8; 32], PendingReset>>>,
+}
+
+impl PasswordResets {
+    /// Returns the token for a link that resets the password of the owner with the email address.
+    pub fn issue(&self, email: &str) -> String {
+        let token = new_token();
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, reset| reset.expires_at > now);
+        pending.insert(
+            hash(&token),
+            PendingReset {
+                email: email.to_string(),
+                expires_at: now + RESET_TTL,
+            },
+        );
+        token
+    }
+
+    /// Whether the token can still be used, without using it up.
+    pub fn is_valid(&self, token: &str) -> bool {
+        self.pending
+            .lock()
+            .unwrap()
+            .get(&hash(token))
+            .is_some_and(|reset| reset.expires_at > Instant::now())
+    }
+
+    /// Takes the email address a token was issued for, if it has not expired. A token can only be
+    /// consumed once.
+    pub fn consume(&self, token: &str) -> Option<String> {
+        self.pending
+            .lock()
+            .unwrap()
+            .remove(&hash(token))
+            .filter(|reset| reset.expires_at > Instant::now())
+            .map(|reset| reset.email)
+    }
+
+    /// Drops the links still out for the email address, once its password has changed.
+    pub fn revoke(&self, email: &str) {
+        self.pending
+            .lock()
+            .unwrap()
+            .retain(|_, reset| reset.email != email);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PasswordForm {
+    current_password: String,
+    password: String,
+    /// The password typed again.
+    confirmation: String,
+    csrf: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewPassword {
+    current_password: String,
+    password: String,
+}
+
+/// Changes the signed-in owner's password, for all their podcasts. The current password is
+/// asked for again, as the browser would send the credentials it has on its own.
+pub async fn change(
+    Owner(podcast): Owner,
+    State(state): State<AppState>,
+    Form(form): Form<PasswordForm>,
+) -> Result<Html<String>, AppError> {
+    state
+        .csrf
+        .check(&csrf::owner_scope(&podcast.slug), &form.csrf)?;
+    check_current_password(&podcast, &form.current_password)?;
+    check_password(&form.password, Some(&form.confirmation))?;
+    set_password(&state, &podcast.owner.email, &form.password);
+    Ok(view::password_changed())
+}
+
+/// Like [`change`], with `{"currentPassword": "...", "password": "..."}`.
+pub async fn api_change(
+    Owner(podcast): Owner,
+    State(state): State<AppState>,
+    Json(form): Json<NewPassword>,
+) -> Result<StatusCode, AppError> {
+    check_current_password(&podcast, &form.current_password)?;
+    check_password(&form.password, None)?;
+    set_password(&state, &podcast.owner.email, &form.password);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn forgot_page() -> Html<String> {
+    view::forgot_password()
+}
+
+#[derive(Deserialize)]
+pub struct ForgotForm {
+    email: String,
+}
+
+/// Emails a reset link if `email` belongs to an owner. The page shown is the same either way, so
+/// that it doesn't tell who has an account.
+pub async fn forgot(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(form): Form<ForgotForm>,
+) -> Html<String> {
+    let email = form.email.trim();
+    let minutes = RESET_TTL.as_secs() / 60;
+    let owner = state.podcasts.all().iter().fold(false, |found, podcast| {
+        found | credentials::matches(email, &podcast.owner.email)
+    });
+    if owner {
+        let token = state.password_resets.issue(email);
+        let mut link = state
+            .config()
+            .public_base_url(&headers)
+            .join("password/reset")
+            .expect("path forms a valid URL");
+        link.query_pairs_mut().append_pair("token", &token);
+
+        state.mailer.send(
+            email,
+            &i18n::t("password-reset-subject"),
+            &i18n::t_args(
+                "password-reset-body",
+                &[
+                    ("link", link.to_string().into()),
+                    ("minutes", minutes.into()),
+                ],
+            ),
+        );
+        tracing::info!("password reset link emailed");
+    }
+    view::password_reset_sent(email, minutes)
+}
+
+#[derive(Deserialize)]
+pub struct ResetParams {
+    token: String,
+}
+
+/// The form for a new password. Opening it doesn't use up the link, as mail scanners open links
+/// too; only setting the password does.
+pub async fn reset_page(
+    State(state): State<AppState>,
+    Query(params): Query<ResetParams>,
+) -> Result<Html<String>, AppError> {
+    if !state.password_resets.is_valid(&params.token) {
+        return Err(AppError::ResetLinkExpired);
+    }
+    Ok(view::reset_password(&params.token))
+}
+
+#[derive(Deserialize)]
+pub struct ResetForm {
+    token: String,
+    password: String,
+    confirmation: String,
+}
+
+/// Sets the password of the owner the link was sent to, and lifts any lockout of their email
+/// address from failed sign-ins.
+pub async fn reset(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Form(form): Form<ResetForm>,
+) -> Result<Html<String>, AppError> {
+    // Checked first, so that a typo doesn't use up the link.
+    check_password(&form.password, Some(&form.confirmation))?;
+    let email = state
+        .password_resets
+        .consume(&form.token)
+        .ok_or(AppError::ResetLinkExpired)?;
+    set_password(&state, &email, &form.password);
+    state.login_throttle.record_success(&email, addr.ip());
+    Ok(view::password_changed())
+}
+
+fn check_current_password(podcast: &Podcast, current_password: &str) -> Result<(), AppError> {
+    if !credentials::verify_password(current_password, &podcast.owner.password_hash) {
+        return Err(AppError::InvalidCredentials);
+    }
+    Ok(())
+}
+
+fn check_password(password: &str, confirmation: Option<&str>) -> Result<(), AppError> {
+    if password.chars().count() < MIN_PASSWORD_LEN
+        || confirmation.is_some_and(|confirmation| confirmation != password)
+    {
+        return Err(AppError::PasswordRejected);
+    }
+    Ok(())
+}
+
+fn set_password(state: &AppState, email: &str, password: &str) {
+    let password_hash = credentials::hash_password(password);
+    let podcasts = state.podcasts.set_password_hash(email, &password_hash);
+    state.password_resets.revoke(email);
+    tracing::info!(podcasts, "owner password changed");
+}
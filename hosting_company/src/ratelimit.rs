@@ -0,0 +1,115 @@
+use crate::error::AppError;
+use crate::{guid_to_podcast, slug_to_podcast, AppState};
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Windows are pruned once the table grows past this many keys, so that a flood of distinct
+/// clients cannot grow it without bound.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Fixed-window request counter keyed by an arbitrary string (client IP, podcast slug, ...).
+#[derive(Clone)]
+pub struct RateLimiter {
+    windows: Arc<Mutex<HashMap<String, Window>>>,
+    /// Shared between clones, so that a reloaded limit applies everywhere.
+    limit: Arc<AtomicU32>,
+    period: Duration,
+}
+
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, period: Duration) -> Self {
+        RateLimiter {
+            windows: Arc::new(Mutex::new(HashMap::new())),
+            limit: Arc::new(AtomicU32::new(limit)),
+            period,
+        }
+    }
+
+    /// Changes the limit, for windows already started too.
+    pub fn set_limit(&self, limit: u32) {
+        self.limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// Counts a request against `key`. Returns how long to wait if the limit has been reached.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+
+        if windows.len() > PRUNE_THRESHOLD {
+            windows.retain(|_, window| now.duration_since(window.started) < self.period);
+        }
+
+        let window = windows.entry(key.to_string()).or_insert(Window {
+            started: now,
+            count: 0,
+        });
+        if now.duration_since(window.started) >= self.period {
+            window.started = now;
+            window.count = 0;
+        }
+
+        if window.count >= self.limit.load(Ordering::Relaxed) {
+            return Err(self.period - now.duration_since(window.started));
+        }
+        window.count += 1;
+        Ok(())
+    }
+}
+
+/// Limits applied to the verification endpoints.
+#[derive(Clone)]
+pub struct RateLimits {
+    pub per_ip: RateLimiter,
+    pub per_slug: RateLimiter,
+}
+
+/// Middleware rejecting verification requests with `429 Too Many Requests` once either the
+/// client IP or the podcast, for paths naming one, exceeds its limit.
+pub async fn limit_verify<B>(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    params: Option<Path<HashMap<String, String>>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let slug = params.and_then(|Path(params)| podcast_slug(&state, &params));
+    let result = state
+        .rate_limits
+        .per_ip
+        .check(&addr.ip().to_string())
+        .and_then(|_| match &slug {
+            Some(slug) => state.rate_limits.per_slug.check(slug),
+            None => Ok(()),
+        });
+
+    match result {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => AppError::TooManyRequests {
+            retry_after_secs: retry_after.as_secs().max(1),
+        }
+        .into_response(),
+    }
+}
+
+/// The slug of the podcast the path names by slug or by GUID, so that every way of naming a
+/// podcast counts against the same limit. Paths naming no podcast are only limited per client.
+fn podcast_slug(state: &AppState, params: &HashMap<String, String>) -> Option<String> {
+    let podcast = match (params.get("slug"), params.get("guid")) {
+        (Some(slug), _) => slug_to_podcast(state.podcasts.all(), slug),
+        (None, Some(guid)) => guid_to_podcast(state, guid),
+        (None, None) => None,
+    };
+    podcast.map(|podcast| podcast.slug)
+}
@@ -0,0 +1,231 @@
+//! The config file at `CONFIG_PATH`: more podcasts, and settings that override the environment's,
+//! reloaded while the server runs when the file changes or the process gets `SIGHUP`.
+//!
+//! A reload swaps in the new settings at once, and requests already under way finish with the
+//! ones they started with. Podcasts are only ever added or updated, never removed, so that
+//! owners in the middle of a verification can still approve it.
+
+use crate::config::{with_trailing_slash, Config};
+use crate::{crypto, slugs, Customer, Podcast, PodcastStatus};
+use axum::http::HeaderValue;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+/// How often the file is checked for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Key sizes podcasts in the file may use.
+const KEY_BITS: [usize; 3] = [2048, 3072, 4096];
+
+/// The contents of the config file. Settings left out keep the environment's value.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    podcasts: Vec<PodcastEntry>,
+    public_base_url: Option<String>,
+    return_url_allowlist: Option<Vec<String>>,
+    auto_block_reported_domains: Option<bool>,
+    verify_rate_limit_per_ip: Option<u32>,
+    verify_rate_limit_per_slug: Option<u32>,
+    redirect_delay_secs: Option<u64>,
+    error_redirect_delay_secs: Option<u64>,
+    trust_forwarded_headers: Option<bool>,
+    feed_key_fingerprints: Option<bool>,
+    content_security_policy: Option<String>,
+    frame_options: Option<String>,
+    referrer_policy: Option<String>,
+    hsts_max_age_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct PodcastEntry {
+    slug: String,
+    title: String,
+    owner_email: String,
+    owner_password: String,
+    #[serde(default = "default_key_bits")]
+    key_bits: usize,
+    /// `RSA-OAEP-256` or `RSA1_5`, as in the `encryption` attribute of `<podcast:verify>`.
+    #[serde(default)]
+    encryption: Option<String>,
+    /// Whether the podcast starts out as a draft, to be published by its owner.
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    locked: bool,
+}
+
+fn default_key_bits() -> usize {
+    crypto::CryptoConfig::default().key_bits
+}
+
+#[derive(Debug)]
+pub enum ReloadError {
+    Read(std::io::Error),
+    Parse(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReloadError::Read(e) => write!(f, "failed to read config file: {e}"),
+            ReloadError::Parse(e) => write!(f, "invalid config file: {e}"),
+            ReloadError::Invalid(reason) => write!(f, "invalid config file: {reason}"),
+        }
+    }
+}
+
+/// Reads and checks the config file, so that a mistake in it leaves the running settings as
+/// they are.
+pub fn read(path: &str) -> Result<ConfigFile, ReloadError> {
+    let contents = std::fs::read_to_string(path).map_err(ReloadError::Read)?;
+    let file: ConfigFile = serde_json::from_str(&contents).map_err(ReloadError::Parse)?;
+    if let Some(base_url) = &file.public_base_url {
+        parse_base_url(base_url)?;
+    }
+    for (name, value) in [
+        ("contentSecurityPolicy", &file.content_security_policy),
+        ("frameOptions", &file.frame_options),
+        ("referrerPolicy", &file.referrer_policy),
+    ] {
+        if let Some(value) = value {
+            if HeaderValue::from_str(value).is_err() {
+                return Err(ReloadError::Invalid(format!("{name} isn't a header value")));
+            }
+        }
+    }
+    let mut seen = HashSet::new();
+    for podcast in &file.podcasts {
+        if !slugs::is_valid_slug(&podcast.slug) || !seen.insert(&podcast.slug) {
+            return Err(ReloadError::Invalid(format!(
+                "slug {:?} is invalid or listed twice",
+                podcast.slug
+            )));
+        }
+        if podcast.title.trim().is_empty()
+            || podcast.owner_email.trim().is_empty()
+            || podcast.owner_password.is_empty()
+        {
+            return Err(ReloadError::Invalid(format!(
+                "{} needs a title, ownerEmail and ownerPassword",
+                podcast.slug
+            )));
+        }
+        if !KEY_BITS.contains(&podcast.key_bits) {
+            return Err(ReloadError::Invalid(format!(
+                "{} has an unsupported key size: {}",
+                podcast.slug, podcast.key_bits
+            )));
+        }
+        podcast.padding()?;
+    }
+    Ok(file)
+}
+
+fn parse_base_url(base_url: &str) -> Result<Url, ReloadError> {
+    Url::parse(base_url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map(with_trailing_slash)
+        .ok_or_else(|| ReloadError::Invalid(format!("publicBaseUrl {base_url:?} isn't a URL")))
+}
+
+impl ConfigFile {
+    /// Overrides the settings the file has.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(base_url) = &self.public_base_url {
+            config.public_base_url = parse_base_url(base_url).expect("checked when read");
+        }
+        if let Some(allowlist) = &self.return_url_allowlist {
+            config.return_url_allowlist = allowlist.clone();
+        }
+        set(
+            &mut config.auto_block_reported_domains,
+            self.auto_block_reported_domains,
+        );
+        set(
+            &mut config.verify_rate_limit_per_ip,
+            self.verify_rate_limit_per_ip,
+        );
+        set(
+            &mut config.verify_rate_limit_per_slug,
+            self.verify_rate_limit_per_slug,
+        );
+        set(&mut config.redirect_delay_secs, self.redirect_delay_secs);
+        set(
+            &mut config.error_redirect_delay_secs,
+            self.error_redirect_delay_secs,
+        );
+        set(
+            &mut config.trust_forwarded_headers,
+            self.trust_forwarded_headers,
+        );
+        set(
+            &mut config.feed_key_fingerprints,
+            self.feed_key_fingerprints,
+        );
+        set(
+            &mut config.content_security_policy,
+            self.content_security_policy.clone(),
+        );
+        set(&mut config.frame_options, self.frame_options.clone());
+        set(&mut config.referrer_policy, self.referrer_policy.clone());
+        set(&mut config.hsts_max_age_secs, self.hsts_max_age_secs);
+    }
+
+    /// The podcasts the file lists.
+    pub fn podcasts(&self) -> Vec<Podcast> {
+        self.podcasts
+            .iter()
+            .map(|entry| Podcast {
+                title: entry.title.trim().to_string(),
+                slug: entry.slug.clone(),
+                owner: Customer::new(entry.owner_email.trim(), &entry.owner_password),
+                crypto: crypto::CryptoConfig {
+                    key_bits: entry.key_bits,
+                    padding: entry.padding().expect("checked when read"),
+                },
+                status: if entry.draft {
+                    PodcastStatus::Draft
+                } else {
+                    PodcastStatus::Published
+                },
+                locked: entry.locked,
+                updated_at: None,
+                imported: None,
+            })
+            .collect()
+    }
+}
+
+impl PodcastEntry {
+    fn padding(&self) -> Result<crypto::Padding, ReloadError> {
+        match self.encryption.as_deref() {
+            None => Ok(crypto::CryptoConfig::default().padding),
+            Some("RSA-OAEP-256") => Ok(crypto::Padding::OaepSha256),
+            Some("RSA1_5") => Ok(crypto::Padding::Pkcs1v15),
+            Some(other) => Err(ReloadError::Invalid(format!(
+                "{} has an unsupported encryption: {other}",
+                self.slug
+            ))),
+        }
+    }
+}
+
+fn set<T>(setting: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *setting = value;
+    }
+}
+
+/// When the file was last modified, to tell whether it changed since.
+pub fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
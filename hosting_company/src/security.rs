@@ -0,0 +1,63 @@
+//! Security headers on the server's responses. HTML pages get a `Content-Security-Policy` that
+//! allows no inline scripts, and can't be framed, so that the consent screen can't be overlaid
+//! for clickjacking. Their `Referrer-Policy` keeps the `encryptedString` in the verify URL from
+//! the sites they link and redirect to. Over HTTPS, every response also carries
+//! `Strict-Transport-Security`.
+
+use crate::AppState;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+
+/// Pages load nothing but the stylesheet from unpkg, app icons from any HTTPS site and the API
+/// docs' own scripts. Inline styles are allowed for the `style` attributes the pages use.
+pub const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'none'; script-src 'self'; \
+    style-src 'self' 'unsafe-inline' https://unpkg.com; img-src 'self' https: data:; \
+    connect-src 'self'; frame-ancestors 'none'; base-uri 'none'";
+
+/// Adds the configured security headers. Settings left empty, or zero for HSTS, aren't sent.
+pub async fn add_headers<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let mut response = next.run(request).await;
+    let config = state.config();
+    let html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/html"));
+    let headers = response.headers_mut();
+    if html {
+        for (name, value) in [
+            (
+                header::CONTENT_SECURITY_POLICY,
+                &config.content_security_policy,
+            ),
+            (header::X_FRAME_OPTIONS, &config.frame_options),
+            (header::REFERRER_POLICY, &config.referrer_policy),
+        ] {
+            insert(headers, name, value);
+        }
+    }
+    if config.tls_cert_path.is_some() && config.hsts_max_age_secs > 0 {
+        insert(
+            headers,
+            header::STRICT_TRANSPORT_SECURITY,
+            &format!("max-age={}", config.hsts_max_age_secs),
+        );
+    }
+    response
+}
+
+fn insert(headers: &mut HeaderMap, name: HeaderName, value: &str) {
+    if value.is_empty() {
+        return;
+    }
+    // Values are checked when the configuration is read.
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.insert(name, value);
+    }
+}
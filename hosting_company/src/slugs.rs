@@ -0,0 +1,161 @@
+use crate::{slug_to_podcast, AppState};
+use axum::extract::State;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::percent_decode_str;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Paths that start with a podcast's slug.
+const SLUG_PREFIXES: [&str; 3] = ["/feed/", "/api/feeds/", "/api/admin/feeds/"];
+/// Renames followed before giving up, so that a cycle in the history can't loop forever.
+const MAX_RENAMES: usize = 8;
+
+/// The form slugs are compared in: percent-decoded, trimmed and lowercase.
+pub fn normalize_slug(slug: &str) -> String {
+    percent_decode_str(slug)
+        .decode_utf8_lossy()
+        .trim()
+        .to_lowercase()
+}
+
+/// Whether a new podcast may be given the slug: lowercase letters, digits and dashes, and not
+/// `by-guid`, which would be taken for the routes by GUID.
+pub fn is_valid_slug(slug: &str) -> bool {
+    !slug.is_empty()
+        && slug != "by-guid"
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Former slugs of renamed podcasts, mapped to the slug that replaced them and persisted as
+/// JSON, so that subscribers to an old feed URL are redirected instead of losing the feed.
+#[derive(Clone)]
+pub struct SlugHistory {
+    path: PathBuf,
+    renames: Arc<Mutex<BTreeMap<String, String>>>,
+}
+
+impl SlugHistory {
+    /// Loads the history from `path`, starting empty if the file does not exist yet.
+    pub fn load(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let renames = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str::<BTreeMap<String, String>>(&contents)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+                .into_iter()
+                .map(|(old, new)| (normalize_slug(&old), normalize_slug(&new)))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(SlugHistory {
+            path,
+            renames: Arc::new(Mutex::new(renames)),
+        })
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, String> {
+        self.renames.lock().unwrap().clone()
+    }
+
+    /// Records that `old` was renamed to `new`. Returns `false` if it already was.
+    pub fn rename(&self, old: &str, new: &str) -> std::io::Result<bool> {
+        let (old, new) = (normalize_slug(old), normalize_slug(new));
+        let mut renames = self.renames.lock().unwrap();
+        if renames.get(&old) == Some(&new) {
+            return Ok(false);
+        }
+        renames.insert(old, new);
+        self.save(&renames)?;
+        Ok(true)
+    }
+
+    /// Forgets a former slug. Returns `false` if it wasn't in the history.
+    pub fn forget(&self, old: &str) -> std::io::Result<bool> {
+        let mut renames = self.renames.lock().unwrap();
+        if renames.remove(&normalize_slug(old)).is_none() {
+            return Ok(false);
+        }
+        self.save(&renames)?;
+        Ok(true)
+    }
+
+    /// The slug `slug` was last renamed to, following renames of renamed slugs.
+    fn resolve(&self, slug: &str) -> Option<String> {
+        let renames = self.renames.lock().unwrap();
+        let mut current = renames.get(slug)?;
+        for _ in 1..MAX_RENAMES {
+            match renames.get(current) {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+        Some(current.clone())
+    }
+
+    fn save(&self, renames: &BTreeMap<String, String>) -> std::io::Result<()> {
+        let contents = serde_json::to_string_pretty(renames)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        std::fs::write(&self.path, contents)
+    }
+}
+
+/// Middleware redirecting paths with a non-canonical or former slug to the podcast's current
+/// one, so that handlers only ever see slugs exactly as the podcasts define them.
+///
+/// `GET` and `HEAD` get a `301 Moved Permanently`, which feed readers know to update their
+/// subscription from. Other methods get a `308 Permanent Redirect`, which keeps the method and
+/// body of e.g. a submitted consent form.
+pub async fn canonicalize<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let path = request.uri().path();
+    let Some((prefix, rest)) = SLUG_PREFIXES
+        .iter()
+        .find_map(|prefix| Some((*prefix, path.strip_prefix(*prefix)?)))
+    else {
+        return next.run(request).await;
+    };
+    let (slug, suffix) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if slug.is_empty()
+        || state
+            .podcasts
+            .all()
+            .iter()
+            .any(|podcast| podcast.slug == slug)
+    {
+        return next.run(request).await;
+    }
+
+    let normalized = normalize_slug(slug);
+    let current = slug_to_podcast(state.podcasts.all(), &normalized).or_else(|| {
+        let renamed = state.slug_history.resolve(&normalized)?;
+        slug_to_podcast(state.podcasts.all(), &renamed)
+    });
+    let Some(podcast) = current else {
+        return next.run(request).await;
+    };
+
+    let mut location = format!("{prefix}{}{suffix}", podcast.slug);
+    if let Some(query) = request.uri().query() {
+        location.push('?');
+        location.push_str(query);
+    }
+    tracing::info!(
+        from = slug,
+        to = podcast.slug,
+        "redirecting to current slug"
+    );
+    let status = if request.method() == Method::GET || request.method() == Method::HEAD {
+        StatusCode::MOVED_PERMANENTLY
+    } else {
+        StatusCode::PERMANENT_REDIRECT
+    };
+    (status, [(header::LOCATION, location)]).into_response()
+}
@@ -0,0 +1,293 @@
+//! The `<podcast:verify>` tag, for hosts that only need to emit it and apps that only need to
+//! read it, and the encoding of its `publicKey` attribute.
+//!
+//! ```ignore
+//! let tag = VerifyTag::builder()
+//!     .verify_url(Url::parse("https://host.example/feed/show/verify")?)
+//!     .public_key(&public_key)
+//!     .signing_key(&signing_key)
+//!     .algorithm(Padding::OaepSha256)
+//!     .build()?;
+//! let xml = tag.to_xml();
+//! ```
+
+use crate::crypto::Padding;
+use crate::xml;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rsa::pkcs8::{DecodePublicKey, EncodePublicKey};
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPublicKey;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use url::Url;
+use uuid::Uuid;
+
+/// Smallest key accepted in a tag. Apps can't be expected to trust anything weaker.
+pub const MIN_KEY_BITS: usize = 2048;
+
+/// A validated `<podcast:verify>` tag.
+#[derive(Clone, Debug)]
+pub struct VerifyTag {
+    verify_url: Url,
+    /// The `publicKey` attribute, see [`public_key_to_feed_attr`].
+    public_key: String,
+    /// The `signingKey` attribute, encoded like `publicKey`, if included.
+    signing_key: Option<String>,
+    algorithm: Padding,
+    /// The `keyFingerprint` attribute, see [`public_key_fingerprint`], if included.
+    fingerprint: Option<String>,
+}
+
+/// Why a [`VerifyTag`] couldn't be built.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TagError {
+    MissingVerifyUrl,
+    MissingPublicKey,
+    /// `verifyUrl` must use HTTPS, except on loopback hosts for local testing, since the owner
+    /// logs in there.
+    InsecureVerifyUrl,
+    InvalidPublicKey(PublicKeyError),
+    WeakPublicKey {
+        bits: usize,
+    },
+}
+
+impl fmt::Display for TagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagError::MissingVerifyUrl => write!(f, "verifyUrl is missing"),
+            TagError::MissingPublicKey => write!(f, "publicKey is missing"),
+            TagError::InsecureVerifyUrl => write!(f, "verifyUrl must be an https URL"),
+            TagError::InvalidPublicKey(e) => write!(f, "{e}"),
+            TagError::WeakPublicKey { bits } => write!(
+                f,
+                "publicKey has {bits} bits, at least {MIN_KEY_BITS} are needed"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TagError {}
+
+impl VerifyTag {
+    pub fn builder() -> VerifyTagBuilder {
+        VerifyTagBuilder::default()
+    }
+
+    pub fn verify_url(&self) -> &Url {
+        &self.verify_url
+    }
+
+    pub fn algorithm(&self) -> Padding {
+        self.algorithm
+    }
+
+    pub fn fingerprint(&self) -> Option<&str> {
+        self.fingerprint.as_deref()
+    }
+
+    /// The tag as a self-closing XML element, with attribute values escaped.
+    pub fn to_xml(&self) -> String {
+        format!(
+            "<podcast:verify verifyUrl=\"{}\" publicKey=\"{}\" encryption=\"{}\"{}{}/>",
+            xml::escape(self.verify_url.as_str()),
+            self.public_key,
+            self.algorithm.name(),
+            self.signing_key
+                .as_ref()
+                .map(|signing_key| format!(" signingKey=\"{signing_key}\""))
+                .unwrap_or_default(),
+            self.fingerprint
+                .as_ref()
+                .map(|fingerprint| format!(" keyFingerprint=\"{fingerprint}\""))
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// Builds a [`VerifyTag`], checking it in [`build`](VerifyTagBuilder::build).
+#[derive(Default)]
+pub struct VerifyTagBuilder {
+    verify_url: Option<Url>,
+    public_key: Option<RsaPublicKey>,
+    signing_key: Option<RsaPublicKey>,
+    algorithm: Option<Padding>,
+    fingerprint: bool,
+}
+
+impl VerifyTagBuilder {
+    pub fn verify_url(mut self, verify_url: Url) -> Self {
+        self.verify_url = Some(verify_url);
+        self
+    }
+
+    pub fn public_key(mut self, public_key: &RsaPublicKey) -> Self {
+        self.public_key = Some(public_key.clone());
+        self
+    }
+
+    /// The key signed verifications are checked with, as a `signingKey` attribute, which isn't
+    /// part of the namespace. Left out by default.
+    pub fn signing_key(mut self, signing_key: &RsaPublicKey) -> Self {
+        self.signing_key = Some(signing_key.clone());
+        self
+    }
+
+    /// Defaults to [`Padding::Pkcs1v15`], which apps assume when the tag doesn't name one.
+    pub fn algorithm(mut self, algorithm: Padding) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Whether to add the key's fingerprint as a `keyFingerprint` attribute, which isn't part of
+    /// the namespace but lets people compare keys at a glance. Off by default.
+    pub fn fingerprint(mut self, fingerprint: bool) -> Self {
+        self.fingerprint = fingerprint;
+        self
+    }
+
+    pub fn build(self) -> Result<VerifyTag, TagError> {
+        let verify_url = self.verify_url.ok_or(TagError::MissingVerifyUrl)?;
+        let secure = match verify_url.scheme() {
+            "https" => true,
+            "http" => is_loopback(&verify_url),
+            _ => false,
+        };
+        if !secure {
+            return Err(TagError::InsecureVerifyUrl);
+        }
+
+        let public_key = self.public_key.ok_or(TagError::MissingPublicKey)?;
+        for key in std::iter::once(&public_key).chain(&self.signing_key) {
+            let bits = key.size() * 8;
+            if bits < MIN_KEY_BITS {
+                return Err(TagError::WeakPublicKey { bits });
+            }
+        }
+        let fingerprint = self
+            .fingerprint
+            .then(|| public_key_fingerprint(&public_key))
+            .transpose()
+            .map_err(TagError::InvalidPublicKey)?;
+        let public_key =
+            public_key_to_feed_attr(&public_key).map_err(TagError::InvalidPublicKey)?;
+        let signing_key = self
+            .signing_key
+            .as_ref()
+            .map(public_key_to_feed_attr)
+            .transpose()
+            .map_err(TagError::InvalidPublicKey)?;
+
+        Ok(VerifyTag {
+            verify_url,
+            public_key,
+            signing_key,
+            algorithm: self.algorithm.unwrap_or(Padding::Pkcs1v15),
+            fingerprint,
+        })
+    }
+}
+
+/// Why a `publicKey` attribute couldn't be encoded or decoded.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PublicKeyError {
+    /// The attribute isn't valid base64.
+    Base64(String),
+    /// The decoded bytes aren't a DER-encoded SubjectPublicKeyInfo of an RSA key.
+    Spki(String),
+}
+
+impl fmt::Display for PublicKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublicKeyError::Base64(e) => write!(f, "publicKey isn't valid base64: {e}"),
+            PublicKeyError::Spki(e) => {
+                write!(f, "publicKey isn't an RSA SubjectPublicKeyInfo: {e}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PublicKeyError {}
+
+/// Encodes a key for the `publicKey` attribute: its DER-encoded SubjectPublicKeyInfo in
+/// standard, padded base64.
+pub fn public_key_to_feed_attr(public_key: &RsaPublicKey) -> Result<String, PublicKeyError> {
+    let der = public_key
+        .to_public_key_der()
+        .map_err(|e| PublicKeyError::Spki(e.to_string()))?;
+    Ok(STANDARD.encode(der.as_bytes()))
+}
+
+/// Decodes a `publicKey` attribute. The inverse of [`public_key_to_feed_attr`].
+pub fn feed_attr_to_public_key(attr: &str) -> Result<RsaPublicKey, PublicKeyError> {
+    let der = STANDARD
+        .decode(attr)
+        .map_err(|e| PublicKeyError::Base64(e.to_string()))?;
+    RsaPublicKey::from_public_key_der(&der).map_err(|e| PublicKeyError::Spki(e.to_string()))
+}
+
+/// The attributes of the first `<podcast:verify>` element in `feed`, as an app reads them, or
+/// `None` if there is none or it is malformed. Values are returned as they appear, unescaped.
+pub fn verify_attributes(feed: &str) -> Option<HashMap<String, String>> {
+    let start = feed.find("<podcast:verify")? + "<podcast:verify".len();
+    let end = start + feed[start..].find('>')?;
+    let mut attributes = HashMap::new();
+    let mut rest = &feed[start..end];
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim().to_string();
+        let after = rest[eq + 1..].trim_start();
+        let quote = after.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+        let value_end = after[1..].find(quote)? + 1;
+        attributes.insert(name, after[1..value_end].to_string());
+        rest = &after[value_end + 1..];
+    }
+    Some(attributes)
+}
+
+/// A short fingerprint of a key, for comparing the key an app encrypted to with the one in the
+/// feed: the first 8 bytes of the SHA-256 hash of its DER-encoded SubjectPublicKeyInfo, as
+/// colon-separated hex, e.g. `3f:a2:07:9c:51:e8:0b:d4`.
+pub fn public_key_fingerprint(public_key: &RsaPublicKey) -> Result<String, PublicKeyError> {
+    let der = public_key
+        .to_public_key_der()
+        .map_err(|e| PublicKeyError::Spki(e.to_string()))?;
+    Ok(Sha256::digest(der.as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(":"))
+}
+
+/// The UUID namespace the podcast namespace specification defines for `<podcast:guid>`.
+const GUID_NAMESPACE: Uuid = Uuid::from_u128(0xead4c236_bf58_58c6_a2c6_a6b28d128cb6);
+
+/// The `<podcast:guid>` for a feed first published at `feed_url`: the UUIDv5 of the URL without
+/// its scheme and trailing slashes, e.g. `9b024349-ccf0-5f69-a609-6b82873eab3c` for
+/// `https://podnews.net/rss`. The GUID stays the same when the feed moves.
+pub fn podcast_guid(feed_url: &Url) -> String {
+    Uuid::new_v5(&GUID_NAMESPACE, guid_name(feed_url.as_str()).as_bytes()).to_string()
+}
+
+/// A feed URL as it is hashed for [`podcast_guid`], which is also how two feed URLs are compared.
+pub(crate) fn guid_name(feed_url: &str) -> &str {
+    feed_url
+        .split_once("://")
+        .map_or(feed_url, |(_, rest)| rest)
+        .trim_end_matches('/')
+}
+
+fn is_loopback(url: &Url) -> bool {
+    match url.host() {
+        Some(url::Host::Domain(domain)) => domain == "localhost",
+        Some(url::Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(url::Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    }
+}
@@ -0,0 +1,229 @@
+use crate::error::AppError;
+use crate::{view, AppState};
+use axum::extract::{Path, State};
+use axum::http::header;
+use axum::response::{Html, IntoResponse};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// How many flows are kept for the trace viewer.
+const MAX_FLOWS: usize = 100;
+
+/// Recorded steps of recent verification flows, shown by the dev-mode trace viewer.
+#[derive(Clone)]
+pub struct Traces {
+    flows: Arc<Mutex<VecDeque<FlowTrace>>>,
+    enabled: bool,
+}
+
+#[derive(Clone)]
+pub struct FlowTrace {
+    pub id: String,
+    pub slug: String,
+    pub started_at: SystemTime,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Clone)]
+pub struct Step {
+    pub at: SystemTime,
+    pub kind: StepKind,
+    pub description: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    /// A request and the parameters it carried.
+    Request,
+    /// A check that passed.
+    Validation,
+    /// A check that failed and ended the flow.
+    Rejection,
+    /// Decryption or signing with the host's keys.
+    Crypto,
+    /// A result posted straight to the app's `callbackUrl` or pushed over its event stream.
+    Callback,
+    Redirect,
+    /// A page shown to the user.
+    Render,
+}
+
+impl StepKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            StepKind::Request => "request",
+            StepKind::Validation => "validation",
+            StepKind::Rejection => "rejection",
+            StepKind::Crypto => "crypto",
+            StepKind::Callback => "callback",
+            StepKind::Redirect => "redirect",
+            StepKind::Render => "render",
+        }
+    }
+}
+
+/// Handle for recording steps of one flow.
+pub struct Flow {
+    id: String,
+    traces: Traces,
+}
+
+impl Traces {
+    pub fn new(enabled: bool) -> Self {
+        Traces {
+            flows: Arc::new(Mutex::new(VecDeque::new())),
+            enabled,
+        }
+    }
+
+    /// Returns the flow for a verification request. The ID is derived from the slug and the query
+    /// string, so the login form posted back to the same URL continues the flow it was shown in.
+    pub fn flow(&self, slug: &str, query: Option<&str>) -> Flow {
+        let mut hasher = Sha256::new();
+        hasher.update(slug.as_bytes());
+        hasher.update(b"?");
+        hasher.update(query.unwrap_or("").as_bytes());
+        let id = hasher
+            .finalize()
+            .iter()
+            .take(8)
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+
+        if self.enabled {
+            let mut flows = self.flows.lock().unwrap();
+            let existing = flows.iter().position(|flow| flow.id == id);
+            let trace = match existing.and_then(|index| flows.remove(index)) {
+                Some(trace) => trace,
+                None => FlowTrace {
+                    id: id.clone(),
+                    slug: slug.to_string(),
+                    started_at: SystemTime::now(),
+                    steps: vec![],
+                },
+            };
+            flows.push_front(trace);
+            flows.truncate(MAX_FLOWS);
+        }
+
+        Flow {
+            id,
+            traces: self.clone(),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<FlowTrace> {
+        let flows = self.flows.lock().unwrap();
+        flows.iter().find(|flow| flow.id == id).cloned()
+    }
+
+    /// Flows, most recently active first.
+    pub fn recent(&self) -> Vec<FlowTrace> {
+        self.flows.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl FlowTrace {
+    /// Renders the flow as a Mermaid sequence diagram.
+    pub fn to_mermaid(&self) -> String {
+        let mut lines = vec![
+            String::from("sequenceDiagram"),
+            String::from("    participant User as Podcast owner's browser"),
+            String::from("    participant Host as Hosting Company"),
+            String::from("    participant App as Requesting app"),
+        ];
+        for step in &self.steps {
+            let text = mermaid_text(&step.description);
+            lines.push(match step.kind {
+                StepKind::Request => format!("    User->>Host: {text}"),
+                StepKind::Validation => format!("    Note over Host: {text}"),
+                StepKind::Rejection => format!("    Host-->>User: rejected ({text})"),
+                StepKind::Crypto => format!("    Host->>Host: {text}"),
+                StepKind::Callback => format!("    Host-)App: {text}"),
+                StepKind::Redirect => {
+                    format!("    Host-->>User: {text}\n    User->>App: follows redirect")
+                }
+                StepKind::Render => format!("    Host-->>User: {text}"),
+            });
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+/// Escapes characters that end a message or start an entity in Mermaid, in one pass so that the
+/// `;` of an entity isn't escaped again.
+fn mermaid_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '#' => escaped.push_str("#35;"),
+            ';' => escaped.push_str("#59;"),
+            '\n' | '\r' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Flow {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn record(&self, kind: StepKind, description: impl Into<String>) {
+        if !self.traces.enabled {
+            return;
+        }
+        let mut flows = self.traces.flows.lock().unwrap();
+        if let Some(flow) = flows.iter_mut().find(|flow| flow.id == self.id) {
+            flow.steps.push(Step {
+                at: SystemTime::now(),
+                kind,
+                description: description.into(),
+            });
+        }
+    }
+}
+
+pub async fn flows(State(state): State<AppState>) -> Result<Html<String>, AppError> {
+    if !state.config().dev_mode {
+        return Err(AppError::NotFound);
+    }
+    Ok(view::flows(state.traces.recent()))
+}
+
+pub async fn flow(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, AppError> {
+    if !state.config().dev_mode {
+        return Err(AppError::NotFound);
+    }
+    let flow = state.traces.get(&id).ok_or(AppError::NotFound)?;
+    Ok(view::flow(flow))
+}
+
+pub async fn sequence_diagram(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    if !state.config().dev_mode {
+        return Err(AppError::NotFound);
+    }
+    let flow = state.traces.get(&id).ok_or(AppError::NotFound)?;
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                String::from("text/plain; charset=utf-8"),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"flow-{}.mmd\"", flow.id),
+            ),
+        ],
+        flow.to_mermaid(),
+    ))
+}
@@ -0,0 +1,74 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Longest token accepted, well above what apps generate.
+const MAX_TOKEN_LEN: usize = 128;
+
+/// Verification tokens published in feeds as `<podcast:txt purpose="verify">`.
+///
+/// This is the non-interactive alternative to the verify URL: the app gives the owner a token,
+/// the host adds it to the feed, and the app checks the feed for it.
+#[derive(Clone, Default)]
+pub struct TxtTokens {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    tokens: BTreeMap<String, BTreeSet<String>>,
+    updated_at: Option<SystemTime>,
+}
+
+impl TxtTokens {
+    pub fn tokens(&self, slug: &str) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .tokens
+            .get(slug)
+            .map(|tokens| tokens.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns whether the token was newly added.
+    pub fn add(&self, slug: &str, token: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let added = inner
+            .tokens
+            .entry(slug.to_string())
+            .or_default()
+            .insert(token.to_string());
+        if added {
+            inner.updated_at = Some(SystemTime::now());
+        }
+        added
+    }
+
+    /// Returns whether the token was present.
+    pub fn remove(&self, slug: &str, token: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let removed = inner
+            .tokens
+            .get_mut(slug)
+            .is_some_and(|tokens| tokens.remove(token));
+        if removed {
+            inner.updated_at = Some(SystemTime::now());
+        }
+        removed
+    }
+
+    /// When tokens were last added or removed, if ever.
+    pub fn updated_at(&self) -> Option<SystemTime> {
+        self.inner.lock().unwrap().updated_at
+    }
+}
+
+/// Whether `token` can be published as is: printable ASCII without spaces or characters that
+/// would need escaping in XML.
+pub fn is_valid_token(token: &str) -> bool {
+    !token.is_empty()
+        && token.len() <= MAX_TOKEN_LEN
+        && token
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !matches!(c, '<' | '>' | '&' | '"' | '\''))
+}
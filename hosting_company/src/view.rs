@@ -1,7 +1,15 @@
+use crate::abuse::AbuseReport;
+use crate::apps::AppIdentity;
+use crate::attempts::{Attempt, Outcome};
+use crate::blocklist::{AuditAction, BlocklistData};
+use crate::grants::Grant;
+use crate::i18n::{self, t, t_args, t_markup};
+use crate::trace::{FlowTrace, StepKind};
 use crate::Podcast;
 use axum::http::StatusCode;
 use axum::response::Html;
-use html_to_string_macro::html;
+use maud::{html, Markup, DOCTYPE};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use url::Url;
 
 pub fn root(podcasts: Vec<Podcast>) -> Html<String> {
@@ -9,42 +17,45 @@ pub fn root(podcasts: Vec<Podcast>) -> Html<String> {
     base_html(
         title,
         html! {
-            <h1>{title}</h1>
-            <p>"Podcasts we host:"</p>
-            <ul>
-            {
-                let mut my_html = vec![];
-                for podcast in podcasts {
-                    my_html.push(html! {
-                        <li>
-                            <a
-                                href=format!("/feed/{}", podcast.slug)
-                                rel="noreferrer"
-                                target="_blank"
-                                >
-                                {podcast.title}
-                            </a>
-                        </li>
-                    });
-                }
-                my_html.join("")
-            }
-            </ul>
+            h1 { (title) }
+            p { "Podcasts we host:" }
+            ul {
+                @for podcast in podcasts {
+                    li {
+                        a href=(format!("/feed/{}", podcast.slug)) rel="noreferrer" target="_blank" {
+                            (podcast.title)
+                        }
+                    }
+                }
+            }
         },
     )
 }
 
 pub enum VerifyState {
     Neutral {
-        podcasts: Vec<Podcast>,
-        podcast: Podcast,
+        podcast: Box<Podcast>,
+        feed_url: Url,
         return_url_scheme: String,
+        /// The app's name and icon from its website, once they have been looked up.
+        app_identity: Option<AppIdentity>,
+        /// Fingerprints of the keys in the feed, current key first.
+        key_fingerprints: Vec<String>,
         return_url_domain: String,
+        domain_flagged: bool,
+        /// Whether the app asks to transfer the feed, and its owner has locked it.
+        transfer_locked: bool,
+        /// Whether the owner already tried to approve with the wrong email or password.
+        login_failed: bool,
+        /// Link to the flow in the trace viewer, in dev mode.
+        trace_url: Option<String>,
     },
     Error {
         podcast: Option<Podcast>,
         return_url: Option<Url>,
-        message: String,
+        /// How long the page is shown before the user is sent back to `return_url`.
+        redirect_delay_secs: u64,
+        message: Markup,
         code: StatusCode,
     },
 }
@@ -52,41 +63,139 @@ pub enum VerifyState {
 pub fn verify(state: VerifyState) -> (StatusCode, Html<String>) {
     match state {
         VerifyState::Neutral {
-            podcasts,
             podcast,
+            feed_url,
             return_url_scheme,
+            app_identity,
+            key_fingerprints,
             return_url_domain,
+            domain_flagged,
+            transfer_locked,
+            login_failed,
+            trace_url,
         } => {
-            let title = html! {
-                "Log in to verify ownership of “" {podcast.title} "” to " <a href={format!("{return_url_scheme}://{return_url_domain}")} rel="noreferrer" target="_blank">{return_url_domain}</a>
+            let title = t_args(
+                "consent-title",
+                &[
+                    ("podcast", podcast.title.as_str().into()),
+                    ("domain", return_url_domain.as_str().into()),
+                ],
+            );
+            let domain_link = html! {
+                a href=(format!("{return_url_scheme}://{return_url_domain}")) rel="noreferrer" target="_blank" {
+                    (return_url_domain)
+                }
+            };
+            let domain_code = html! { code { (return_url_domain) } };
+            let app_identity = app_identity
+                .filter(|identity| identity.name.is_some() || identity.icon_url.is_some());
+
+            let code = if login_failed {
+                StatusCode::UNAUTHORIZED
+            } else {
+                StatusCode::OK
             };
 
             (
-                StatusCode::OK,
+                code,
                 base_html(
                     &title,
                     html! {
-                        <h1>{&title}</h1>
-                        <form method="POST" autocomplete="off">
-                            <input autocomplete="false" name="hidden" type="text" style="display:none;" />
-
-                            <label for="email">"Email"</label>
-                            <input type="email" list="email-list" id="email" name="email" autocomplete="off"/>
-                            <datalist id="email-list">
-                            {
-                                podcasts.iter().map(|podcast| {
+                        h1 {
+                            (t_markup("consent-title", &[
+                                ("podcast", podcast.title.as_str().into()),
+                                ("domain", domain_code.clone().into()),
+                            ]))
+                        }
+                        dl {
+                            dt { (t("consent-app")) }
+                            dd {
+                                @if let Some(identity) = &app_identity {
+                                    @if let Some(icon_url) = &identity.icon_url {
+                                        img src=(icon_url) alt="" width="32" height="32" referrerpolicy="no-referrer" style="vertical-align: middle;";
+                                        " "
+                                    }
+                                    @if let Some(name) = &identity.name {
+                                        strong { (name) }
+                                    }
+                                    br;
+                                }
+                                (domain_link)
+                                @if app_identity.is_some() {
+                                    br;
+                                    small { (t_markup("consent-app-claimed", &[("domain", domain_code.clone().into())])) }
+                                }
+                            }
+                            dt { (t("consent-feed")) }
+                            dd { (podcast.title) br; a href=(feed_url) rel="noreferrer" target="_blank" { (feed_url) } }
+                            @if !key_fingerprints.is_empty() {
+                                dt { (t("consent-key-fingerprint")) }
+                                dd {
+                                    @for fingerprint in &key_fingerprints {
+                                        code { (fingerprint) }
+                                        br;
+                                    }
+                                    small { (t("consent-key-fingerprint-details")) }
+                                }
+                            }
+                            dt { (t("consent-claim")) }
+                            dd { (t_markup("consent-claim-details", &[("feed", html! { code { (feed_url) } }.into())])) }
+                        }
+                        @if domain_flagged {
+                            p style="color: crimson;" {
+                                strong {
+                                    (t_markup("verify-flagged-warning", &[("domain", domain_code.clone().into())]))
+                                }
+                            }
+                        }
+                        @if transfer_locked {
+                            p style="color: crimson;" {
+                                strong {
+                                    (t_markup("consent-transfer-locked", &[("domain", domain_code.clone().into())]))
+                                }
+                            }
+                        }
+                        form method="POST" autocomplete="off" {
+                            input autocomplete="false" name="hidden" type="text" style="display:none;";
+
+                            p { (t("consent-login")) }
+                            @if login_failed {
+                                p style="color: crimson;" { (t("error-invalid-credentials")) }
+                            }
+
+                            label for="email" { (t("verify-email")) }
+                            input type="email" id="email" name="email" autocomplete="off";
+
+                            label for="password" {
+                                (t_markup("verify-password", &[(
+                                    "hint",
                                     html! {
-                                        <option value={podcast.owner.email.to_string()} />
+                                        a href="https://github.com/rssblue/podcast_verify_example#login" rel="noreferrer" target="_blank" {
+                                            (t("verify-password-hint"))
+                                        }
                                     }
-                                }).collect::<Vec<_>>().join("")
+                                    .into(),
+                                )]))
                             }
-                            </datalist>
+                            input type="password" id="password" name="password" autocomplete="off";
+                            p { small { a href="/password/forgot" target="_blank" { (t("consent-forgot-password")) } } }
 
-                            <label for="password">"Password ("<a href="https://github.com/rssblue/podcast_verify_example#login" rel="noreferrer" target="_blank">"hint"</a>")"</label>
-                            <input type="password" id="password" name="password" autocomplete="off"/>
+                            button type="submit" name="decision" value="approve" { (t("consent-approve")) }
+                            " "
+                            button type="submit" name="decision" value="deny" formnovalidate { (t("consent-deny")) }
 
-                            <button type="submit">"Log in"</button>
-                        </form>
+                            p { (t_markup("verify-report-prompt", &[("domain", domain_code.into())])) }
+                            button type="submit" name="decision" value="report" { (t("verify-report")) }
+                        }
+                        form method="POST" {
+                            p { (t("consent-magic-link-prompt")) }
+                            label for="magic-email" { (t("verify-email")) }
+                            input type="email" id="magic-email" name="email" required;
+                            button type="submit" name="decision" value="email" { (t("consent-magic-link")) }
+                        }
+                        @if let Some(trace_url) = trace_url {
+                            p { small { "Dev mode: " a href=(trace_url) { "view protocol trace" } } }
+                        }
                     },
                 ),
             )
@@ -94,41 +203,30 @@ pub fn verify(state: VerifyState) -> (StatusCode, Html<String>) {
         VerifyState::Error {
             podcast,
             return_url,
+            redirect_delay_secs,
             message,
             code,
         } => {
             let title = match podcast {
-                Some(podcast) => format!("Verify ownership of “{}”", podcast.title),
-                None => "Verify ownership".to_string(),
+                Some(podcast) => t_args("verify-ownership", &[("podcast", podcast.title.into())]),
+                None => t("verify-ownership-generic"),
             };
 
-            let error_title = format!("Error: {}", StatusCode::to_string(&code));
+            let error_title = t_args("error-title", &[("status", code.to_string().into())]);
+            let head = match &return_url {
+                Some(return_url) => meta_refresh(return_url, redirect_delay_secs),
+                None => html! {},
+            };
             (
                 code,
-                base_html(
+                base_html_with_head(
                     &error_title,
+                    head,
                     html! {
-                        <h1>{&title}</h1>
-                        {error(message)}
-                        {
-                            match return_url {
-                                Some(return_url) => html! {
-                                    <strong>"Redirecting to "<a href={return_url.to_string()} rel="noreferrer">{return_url.to_string()}</a>" in "<span id="countdown">"10"</span>" seconds..."</strong>
-                                    <script>
-                                        "let seconds = 10;"
-                                        "let countdown = document.getElementById('countdown');"
-                                        "let interval = setInterval(() => {"
-                                            "seconds -= 1;"
-                                            "countdown.innerText = seconds;"
-                                            "if (seconds <= 0) {"
-                                                "clearInterval(interval);"
-                                                "window.location.href = '" {return_url.to_string()} "';"
-                                            "}"
-                                        "}, 1000);"
-                                    </script>
-                                },
-                                None => html! {}
-                            }
+                        h1 { (title) }
+                        (error(message))
+                        @if let Some(return_url) = &return_url {
+                            (redirect_notice(return_url, redirect_delay_secs))
                         }
                     },
                 ),
@@ -137,37 +235,579 @@ pub fn verify(state: VerifyState) -> (StatusCode, Html<String>) {
     }
 }
 
-fn base_html(title: &str, main: String) -> Html<String> {
-    Html(html! {
-        <!DOCTYPE html>
-        <html>
-            <head>
-                <meta charset="UTF-8"/>
-                <meta name="viewport" content="width=device-width, initial-scale=1.0" />
-                <link rel="stylesheet" href="https://unpkg.com/mvp.css" />
-
-                <title>{dissolve::strip_html_tags(title).join("")}</title>
-            </head>
-            <body>
-                <header>
-                    <nav>
-                        <span>"🔵 Hosting Company"</span>
-                        <ul>
-                            <li><a href="/">"Home"</a></li>
-                        </ul>
-                    </nav>
-                </header>
-                <main>
-                    {main}
-                </main>
-            </body>
-        </html>
-    })
-}
-
-fn error(message: String) -> String {
+/// Shown to an owner who approved, before they are sent back to the app with the result.
+pub fn verified(
+    podcast: Podcast,
+    domain: &str,
+    return_url: &Url,
+    redirect_delay_secs: u64,
+) -> Html<String> {
+    let title = t_args("verify-ownership", &[("podcast", podcast.title.into())]);
+    base_html_with_head(
+        &title,
+        meta_refresh(return_url, redirect_delay_secs),
+        html! {
+            h1 { (title) }
+            p { (t_markup("verify-approved", &[("domain", html! { code { (domain) } }.into())])) }
+            (redirect_notice(return_url, redirect_delay_secs))
+        },
+    )
+}
+
+/// Sends the browser to `url` after `delay_secs`, without script. The URL is escaped like any
+/// other attribute value, so it can't break out of the tag.
+fn meta_refresh(url: &Url, delay_secs: u64) -> Markup {
+    html! {
+        meta http-equiv="refresh" content=(format!("{delay_secs}; url={url}"));
+    }
+}
+
+/// Tells the user where a [`meta_refresh`] is about to send them, with a link in case their
+/// browser doesn't follow it.
+fn redirect_notice(url: &Url, delay_secs: u64) -> Markup {
+    html! {
+        strong {
+            (t_markup("redirect-countdown", &[
+                ("url", html! { a href=(url) rel="noreferrer" { (url) } }.into()),
+                ("seconds", delay_secs.into()),
+                ("count", delay_secs.into()),
+            ]))
+        }
+    }
+}
+
+/// The login form for an app's request to verify several podcasts at once.
+pub fn batch_login(
+    domain: &str,
+    podcasts: &[Podcast],
+    login_failed: bool,
+) -> (StatusCode, Html<String>) {
+    let count = podcasts.len() as u64;
+    let title = t_args(
+        "batch-title",
+        &[("domain", domain.into()), ("count", count.into())],
+    );
+    let domain_code = html! { code { (domain) } };
+    let code = if login_failed {
+        StatusCode::UNAUTHORIZED
+    } else {
+        StatusCode::OK
+    };
+
+    (
+        code,
+        base_html(
+            &title,
+            html! {
+                h1 { (t_markup("batch-title", &[("domain", domain_code.into()), ("count", count.into())])) }
+                ul {
+                    @for podcast in podcasts {
+                        li { (podcast.title) }
+                    }
+                }
+                form method="POST" autocomplete="off" {
+                    p { (t("batch-login")) }
+                    @if login_failed {
+                        p style="color: crimson;" { (t("error-invalid-credentials")) }
+                    }
+
+                    label for="email" { (t("verify-email")) }
+                    input type="email" id="email" name="email" autocomplete="off";
+                    label for="password" { (t("batch-password")) }
+                    input type="password" id="password" name="password" autocomplete="off";
+
+                    button type="submit" name="decision" value="login" { (t("batch-log-in")) }
+                    " "
+                    button type="submit" name="decision" value="deny" formnovalidate { (t("consent-deny")) }
+                }
+            },
+        ),
+    )
+}
+
+/// Lets an owner who logged in to a batch request choose which of their podcasts to verify.
+pub fn batch_choose(domain: &str, podcasts: &[Podcast], token: &str) -> Html<String> {
+    let count = podcasts.len() as u64;
+    let title = t_args(
+        "batch-title",
+        &[("domain", domain.into()), ("count", count.into())],
+    );
+    let domain_code = html! { code { (domain) } };
+
+    base_html(
+        &title,
+        html! {
+            h1 { (t_markup("batch-title", &[("domain", domain_code.clone().into()), ("count", count.into())])) }
+            form method="POST" {
+                p { (t_markup("batch-choose", &[("domain", domain_code.into())])) }
+                input type="hidden" name="token" value=(token);
+                @for podcast in podcasts {
+                    label {
+                        input type="checkbox" name="slug" value=(podcast.slug) checked;
+                        " " (podcast.title)
+                    }
+                }
+                button type="submit" name="decision" value="approve" { (t("batch-approve")) }
+                " "
+                button type="submit" name="decision" value="deny" { (t("consent-deny")) }
+            }
+        },
+    )
+}
+
+pub fn magic_link_sent(podcast: Podcast, email: &str, minutes: u64) -> (StatusCode, Html<String>) {
+    let title = t("magic-link-sent-title");
+    (
+        StatusCode::OK,
+        base_html(
+            &title,
+            html! {
+                h1 { (title) }
+                p {
+                    (t_markup("magic-link-sent", &[
+                        ("email", html! { code { (email) } }.into()),
+                        ("podcast", podcast.title.into()),
+                        ("minutes", minutes.into()),
+                    ]))
+                }
+            },
+        ),
+    )
+}
+
+pub fn forgot_password() -> Html<String> {
+    let title = t("forgot-password-title");
+    base_html(
+        &title,
+        html! {
+            h1 { (title) }
+            form method="POST" {
+                p { (t("forgot-password-prompt")) }
+                label for="email" { (t("verify-email")) }
+                input type="email" id="email" name="email" required;
+                button type="submit" { (t("forgot-password-send")) }
+            }
+        },
+    )
+}
+
+pub fn password_reset_sent(email: &str, minutes: u64) -> Html<String> {
+    let title = t("magic-link-sent-title");
+    base_html(
+        &title,
+        html! {
+            h1 { (title) }
+            p {
+                (t_markup("password-reset-sent", &[
+                    ("email", html! { code { (email) } }.into()),
+                    ("minutes", minutes.into()),
+                ]))
+            }
+        },
+    )
+}
+
+pub fn reset_password(token: &str) -> Html<String> {
+    let title = t("reset-password-title");
+    base_html(
+        &title,
+        html! {
+            h1 { (title) }
+            form method="POST" {
+                input type="hidden" name="token" value=(token);
+                (new_password_fields())
+                button type="submit" { (t("reset-password-save")) }
+            }
+        },
+    )
+}
+
+/// The new password and its confirmation, for the dashboard and the reset form.
+fn new_password_fields() -> Markup {
+    let min = crate::passwords::MIN_PASSWORD_LEN;
+    html! {
+        label for="new-password" {
+            (t_args("new-password", &[("min", (min as u64).into())]))
+        }
+        input type="password" id="new-password" name="password" minlength=(min) autocomplete="new-password" required;
+        label for="new-password-confirmation" { (t("new-password-confirmation")) }
+        input type="password" id="new-password-confirmation" name="confirmation" minlength=(min) autocomplete="new-password" required;
+    }
+}
+
+/// The token that shows a form was posted from the page it is on.
+fn csrf_field(token: &str) -> Markup {
+    html! {
+        input type="hidden" name="csrf" value=(token);
+    }
+}
+
+pub fn password_changed() -> Html<String> {
+    let title = t("password-changed-title");
+    base_html(
+        &title,
+        html! {
+            h1 { (title) }
+            p { (t("password-changed")) }
+        },
+    )
+}
+
+pub fn reported(podcast: Podcast, domain: &str, blocked: bool) -> (StatusCode, Html<String>) {
+    let title = t_args("verify-ownership", &[("podcast", podcast.title.into())]);
+    let reported_title = t("reported-title");
+    let domain = html! { code { (domain) } };
+    (
+        StatusCode::OK,
+        base_html(
+            &reported_title,
+            html! {
+                h1 { (title) }
+                h2 { (reported_title) }
+                p { (t_markup("reported-thanks", &[("domain", domain.clone().into())])) }
+                @if blocked {
+                    p { (t_markup("reported-blocked", &[("domain", domain.into())])) }
+                }
+            },
+        ),
+    )
+}
+
+pub fn too_many_requests(retry_after_secs: u64) -> (StatusCode, Html<String>) {
+    let title = t("too-many-requests-title");
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        base_html(
+            &title,
+            html! {
+                h1 { (title) }
+                p { (t_args("too-many-requests-message", &[("seconds", retry_after_secs.into())])) }
+            },
+        ),
+    )
+}
+
+/// The owner's overview of the verifications they granted and every verification request for
+/// their podcast.
+pub fn dashboard(
+    podcast: Podcast,
+    grants: Vec<Grant>,
+    attempts: Vec<Attempt>,
+    csrf_token: &str,
+) -> Html<String> {
+    let title = t_args(
+        "dashboard-title",
+        &[("podcast", podcast.title.as_str().into())],
+    );
+    base_html(
+        &title,
+        html! {
+            h1 { (title) }
+
+            @if !podcast.is_published() {
+                form method="POST" action=(format!("/feed/{}/publish", podcast.slug)) {
+                    (csrf_field(csrf_token))
+                    p { (t("dashboard-draft")) }
+                    button type="submit" { (t("dashboard-publish")) }
+                }
+            }
+
+            form method="POST" action=(format!("/feed/{}/lock", podcast.slug)) {
+                (csrf_field(csrf_token))
+                @if podcast.locked {
+                    p { (t("dashboard-locked")) }
+                    input type="hidden" name="locked" value="false";
+                    button type="submit" { (t("dashboard-unlock")) }
+                } @else {
+                    p { (t("dashboard-unlocked")) }
+                    input type="hidden" name="locked" value="true";
+                    button type="submit" { (t("dashboard-lock")) }
+                }
+            }
+
+            h2 { (t("dashboard-password-heading")) }
+            form method="POST" action=(format!("/feed/{}/password", podcast.slug)) {
+                (csrf_field(csrf_token))
+                label for="current-password" { (t("current-password")) }
+                input type="password" id="current-password" name="current_password" autocomplete="current-password" required;
+                (new_password_fields())
+                button type="submit" { (t("dashboard-change-password")) }
+            }
+
+            h2 { (t("grants-heading")) }
+            @if grants.is_empty() {
+                p { (t("grants-empty")) }
+            } @else {
+                table {
+                    tr {
+                        th { (t("grants-granted")) }
+                        th { (t("attempts-app")) }
+                        th { (t("grants-status")) }
+                    }
+                    @for grant in &grants {
+                        tr {
+                            td { (format_timestamp(grant.granted_at)) }
+                            td { code { (grant.domain) } }
+                            td {
+                                @if let Some(revoked_at) = grant.revoked_at {
+                                    (t_args("grants-revoked", &[("time", format_timestamp(revoked_at).into())]))
+                                } @else {
+                                    form method="POST" action=(format!("/feed/{}/grants/{}/revoke", podcast.slug, grant.id)) {
+                                        (csrf_field(csrf_token))
+                                        @if grant.callback_url.is_some() {
+                                            label {
+                                                input type="checkbox" name="notify" value="true" checked;
+                                                " " (t("grants-notify"))
+                                            }
+                                        }
+                                        button type="submit" { (t("grants-revoke")) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            h2 { (t("attempts-heading")) }
+            @if attempts.is_empty() {
+                p { (t("attempts-empty")) }
+            } @else {
+                p { (t("attempts-intro")) }
+                table {
+                    tr {
+                        th { (t("attempts-time")) }
+                        th { (t("attempts-app")) }
+                        th { (t("attempts-outcome")) }
+                        th { (t("attempts-ip")) }
+                    }
+                    @for attempt in &attempts {
+                        tr {
+                            td { (format_timestamp(attempt.timestamp)) }
+                            td {
+                                @match &attempt.domain {
+                                    Some(domain) => { code { (domain) } }
+                                    None => { (t("attempts-unknown-app")) }
+                                }
+                            }
+                            td { (outcome(attempt)) }
+                            td { code { (attempt.client_ip) } }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+fn outcome(attempt: &Attempt) -> String {
+    match attempt.outcome {
+        Outcome::Requested => t("outcome-requested"),
+        Outcome::Approved => t("outcome-approved"),
+        Outcome::Denied => t("outcome-denied"),
+        Outcome::SignInLinkSent => t("outcome-sign-in-link-sent"),
+        Outcome::Reported => t("outcome-reported"),
+        Outcome::InvalidCredentials => t("outcome-invalid-credentials"),
+        Outcome::Rejected => t_args(
+            "outcome-rejected",
+            &[("reason", attempt.reason.as_deref().unwrap_or("").into())],
+        ),
+    }
+}
+
+pub fn admin(
+    blocklist: BlocklistData,
+    reports: Vec<AbuseReport>,
+    csrf_token: &str,
+) -> Html<String> {
+    let title = "Admin";
+    base_html(
+        title,
+        html! {
+            h1 { (title) }
+
+            h2 { "Blocked directory domains" }
+            ul {
+                @for domain in &blocklist.domains {
+                    li {
+                        form method="POST" action="/admin/blocklist/remove" {
+                            code { (domain) } " "
+                            (csrf_field(csrf_token))
+                            input type="hidden" name="domain" value=(domain);
+                            button type="submit" { "Unblock" }
+                        }
+                    }
+                }
+            }
+            form method="POST" action="/admin/blocklist" {
+                (csrf_field(csrf_token))
+                label for="domain" { "Domain" }
+                input type="text" id="domain" name="domain" placeholder="example.com";
+                label for="reason" { "Reason" }
+                input type="text" id="reason" name="reason";
+                button type="submit" { "Block" }
+            }
+
+            h2 { "Abuse reports" }
+            table {
+                tr { th { "Reported" } th { "Podcast" } th { "Domain" } th { "Return URL" } th { "Encrypted string" } th { "Decrypts" } }
+                @for report in reports.iter().rev() {
+                    tr {
+                        td { (format_timestamp(report.reported_at)) }
+                        td { (report.slug) }
+                        td { code { (report.domain) } }
+                        td { (report.return_url) }
+                        td { code { (report.encrypted_string.as_deref().unwrap_or("")) } }
+                        td { @if report.decrypted { "Yes" } @else { "No" } }
+                    }
+                }
+            }
+
+            h2 { "Audit log" }
+            table {
+                tr { th { "Time" } th { "Action" } th { "Domain" } th { "By" } th { "Reason" } }
+                @for entry in blocklist.audit.iter().rev() {
+                    tr {
+                        td { (format_timestamp(entry.timestamp)) }
+                        td {
+                            @match entry.action {
+                                AuditAction::Block => { "Block" }
+                                AuditAction::Unblock => { "Unblock" }
+                            }
+                        }
+                        td { code { (entry.domain) } }
+                        td { (entry.actor) }
+                        td { (entry.reason.as_deref().unwrap_or("")) }
+                    }
+                }
+            }
+        },
+    )
+}
+
+pub fn flows(flows: Vec<FlowTrace>) -> Html<String> {
+    let title = "Protocol traces";
+    base_html(
+        title,
+        html! {
+            h1 { (title) }
+            p { "Recent verification flows, most recently active first." }
+            table {
+                tr { th { "Flow" } th { "Podcast" } th { "Started" } th { "Steps" } th { "Outcome" } }
+                @for flow in &flows {
+                    tr {
+                        td { a href=(format!("/dev/flows/{}", flow.id)) { code { (flow.id) } } }
+                        td { (flow.slug) }
+                        td { (httpdate::fmt_http_date(flow.started_at)) }
+                        td { (flow.steps.len()) }
+                        td { (flow.steps.last().map(|step| step.kind.label()).unwrap_or("")) }
+                    }
+                }
+            }
+        },
+    )
+}
+
+pub fn flow(flow: FlowTrace) -> Html<String> {
+    let title = format!("Flow {}", flow.id);
+    base_html(
+        &title,
+        html! {
+            h1 { (title) }
+            p { "Podcast " code { (flow.slug) } ", started " (httpdate::fmt_http_date(flow.started_at)) "." }
+            table {
+                tr { th { "+ms" } th { "Step" } th { "Details" } }
+                @for step in &flow.steps {
+                    @let color = match step.kind {
+                        StepKind::Rejection => "crimson",
+                        StepKind::Redirect | StepKind::Render | StepKind::Callback => "royalblue",
+                        StepKind::Crypto => "darkorange",
+                        StepKind::Request | StepKind::Validation => "inherit",
+                    };
+                    tr {
+                        td { (elapsed_ms(flow.started_at, step.at)) }
+                        td style=(format!("color: {color};")) { strong { (step.kind.label()) } }
+                        td { code { (step.description) } }
+                    }
+                }
+            }
+            h2 { "Sequence diagram" }
+            p {
+                "Paste into a "
+                a href="https://mermaid.js.org/syntax/sequenceDiagram.html" rel="noreferrer" target="_blank" { "Mermaid" }
+                " renderer, or "
+                a href=(format!("/dev/flows/{}/sequence.mmd", flow.id)) { "download it" }
+                "."
+            }
+            pre { code { (flow.to_mermaid()) } }
+            p { a href="/dev/flows" { "All flows" } }
+        },
+    )
+}
+
+fn elapsed_ms(since: SystemTime, at: SystemTime) -> u128 {
+    at.duration_since(since).unwrap_or_default().as_millis()
+}
+
+fn format_timestamp(unix_timestamp: u64) -> String {
+    httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(unix_timestamp))
+}
+
+pub fn error_page(code: StatusCode, message: Markup) -> (StatusCode, Html<String>) {
+    let title = t_args("error-title", &[("status", code.to_string().into())]);
+    (
+        code,
+        base_html(
+            &title,
+            html! {
+                h1 { (title) }
+                (error(message))
+            },
+        ),
+    )
+}
+
+/// Wraps the page content in the site layout. `title` is plain text; `main` is escaped markup.
+fn base_html(title: &str, main: Markup) -> Html<String> {
+    base_html_with_head(title, html! {}, main)
+}
+
+/// Like [`base_html`], with `head` added to the page's `<head>`.
+fn base_html_with_head(title: &str, head: Markup, main: Markup) -> Html<String> {
+    Html(
+        html! {
+            (DOCTYPE)
+            html lang=(i18n::locale().to_string()) {
+                head {
+                    meta charset="UTF-8";
+                    meta name="viewport" content="width=device-width, initial-scale=1.0";
+                    link rel="stylesheet" href="https://unpkg.com/mvp.css";
+                    (head)
+
+                    title { (title) }
+                }
+                body {
+                    header {
+                        nav {
+                            span { "🔵 Hosting Company" }
+                            ul {
+                                li { a href="/" { (t("nav-home")) } }
+                            }
+                        }
+                    }
+                    main {
+                        (main)
+                    }
+                }
+            }
+        }
+        .into_string(),
+    )
+}
+
+fn error(message: Markup) -> Markup {
     html! {
-        <h2 style="color: crimson;">"Error"</h2>
-        <p>{message}</p>
+        h2 style="color: crimson;" { (t("error")) }
+        p { (message) }
     }
 }
@@ -0,0 +1,18 @@
+//! Escaping for text and attribute values in the XML the server writes. HTML pages don't need
+//! it, as maud escapes everything it renders that isn't explicitly `PreEscaped`.
+
+/// Escapes `value` for use as XML character data or inside a quoted attribute value.
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,250 @@
+//! The admin page and API, which manage the blocklist of directory domains, and who may use them.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::response::Response;
+use axum::Router;
+use common::*;
+use serde_json::Value;
+use tower::ServiceExt;
+
+const ADMIN: &str = "admin:hunter2";
+
+fn admin_app() -> Router {
+    app_with(|config| {
+        config.admin_token = Some(secrecy::SecretString::new(String::from("hunter2")));
+    })
+}
+
+async fn api_get(app: &Router, uri: &str, token: &str) -> Response {
+    api_request(app, Method::GET, uri, token).await
+}
+
+async fn api_request(app: &Router, method: Method, uri: &str, token: &str) -> Response {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::empty())
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap()
+}
+
+/// The status of a request to verify with a `returnUrl` of `return_url`, and whether it was sent
+/// back with `error=access_denied`.
+async fn verify_from(app: &Router, return_url: &str) -> (StatusCode, bool) {
+    let encrypted = encrypt_for(app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", return_url)],
+    );
+    let response = get(app, &uri).await;
+    let denied = response
+        .headers()
+        .get(header::LOCATION)
+        .is_some_and(|location| location.to_str().unwrap().contains("error=access_denied"));
+    (response.status(), denied)
+}
+
+async fn blocked_domains(app: &Router) -> Value {
+    let response = api_get(app, "/api/admin/blocklist", "hunter2").await;
+    let blocklist: Value = serde_json::from_str(&body_text(response).await).unwrap();
+    blocklist["domains"].clone()
+}
+
+/// The browser UI takes the token as the password of `admin`, and the API as a Bearer token,
+/// which browsers don't send on their own.
+#[tokio::test]
+async fn admin_routes_take_the_token() {
+    let app = admin_app();
+    let uri = "/api/admin/blocklist";
+    assert_eq!(api_get(&app, uri, "hunter2").await.status(), StatusCode::OK);
+    for response in [
+        api_get(&app, uri, "hunter3").await,
+        request_as(&app, Method::GET, uri, ADMIN).await,
+        get(&app, uri).await,
+    ] {
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[header::WWW_AUTHENTICATE],
+            "Bearer realm=\"admin\""
+        );
+    }
+
+    let uri = "/admin";
+    assert_eq!(
+        request_as(&app, Method::GET, uri, ADMIN).await.status(),
+        StatusCode::OK
+    );
+    for response in [
+        request_as(&app, Method::GET, uri, "admin:hunter3").await,
+        request_as(&app, Method::GET, uri, "alice@example.com:hunter2").await,
+        request_as(&app, Method::GET, uri, ":hunter2").await,
+        api_get(&app, uri, "hunter2").await,
+        get(&app, uri).await,
+    ] {
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[header::WWW_AUTHENTICATE],
+            "Basic realm=\"admin\""
+        );
+    }
+}
+
+/// Without a token configured, the admin routes don't exist.
+#[tokio::test]
+async fn admin_routes_are_gone_without_a_token() {
+    let app = app();
+    for uri in ["/admin", "/api/admin/blocklist"] {
+        assert_eq!(
+            get(&app, uri).await.status(),
+            StatusCode::NOT_FOUND,
+            "{uri}"
+        );
+        let response = request_as(&app, Method::GET, uri, ADMIN).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{uri}");
+    }
+}
+
+/// The browser sends the admin's credentials with a form another site posts, but that site
+/// can't read the page's token.
+#[tokio::test]
+async fn blocklist_forms_take_the_pages_token() {
+    let app = admin_app();
+    let csrf = csrf_token(&app, "/admin", ADMIN).await;
+
+    for form in [
+        vec![("domain", "evil.example")],
+        vec![("domain", "evil.example"), ("csrf", "forged")],
+    ] {
+        let response = post_form_as(&app, "/admin/blocklist", ADMIN, &form).await;
+        assert!(response.status().is_client_error(), "{form:?}");
+    }
+    assert_eq!(blocked_domains(&app).await, serde_json::json!([]));
+
+    let form = [("domain", "evil.example"), ("csrf", &csrf)];
+    let response = post_form_as(&app, "/admin/blocklist", ADMIN, &form).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()[header::LOCATION], "/admin");
+    assert_eq!(
+        blocked_domains(&app).await,
+        serde_json::json!(["evil.example"])
+    );
+
+    let form = [("domain", "evil.example"), ("csrf", "forged")];
+    let response = post_form_as(&app, "/admin/blocklist/remove", ADMIN, &form).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let form = [("domain", "evil.example"), ("csrf", &csrf)];
+    let response = post_form_as(&app, "/admin/blocklist/remove", ADMIN, &form).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(blocked_domains(&app).await, serde_json::json!([]));
+}
+
+/// A block covers the host on every port and all of its subdomains, however the admin or the
+/// app capitalizes it, so that a phishing app can't get around it by moving.
+#[tokio::test]
+async fn blocks_cover_every_port_and_subdomain() {
+    let app = admin_app();
+    let response = api_request(
+        &app,
+        Method::PUT,
+        "/api/admin/blocklist/Evil.Example",
+        "hunter2",
+    );
+    assert_eq!(response.await.status(), StatusCode::CREATED);
+    assert_eq!(
+        blocked_domains(&app).await,
+        serde_json::json!(["evil.example"])
+    );
+
+    for return_url in [
+        "https://evil.example/verified",
+        "https://evil.example:8443/verified",
+        "https://EVIL.example./verified",
+        "https://login.evil.example/verified",
+        "https://a.b.evil.example:8443/verified",
+    ] {
+        let (status, denied) = verify_from(&app, return_url).await;
+        assert_eq!(status, StatusCode::SEE_OTHER, "{return_url}");
+        assert!(denied, "{return_url}");
+    }
+    for return_url in [
+        "https://notevil.example/verified",
+        "https://evil.example.com/verified",
+    ] {
+        let (status, _) = verify_from(&app, return_url).await;
+        assert_eq!(status, StatusCode::OK, "{return_url}");
+    }
+
+    // Unblocking takes the domain as the admin typed it to block it.
+    let csrf = csrf_token(&app, "/admin", ADMIN).await;
+    let form = [("domain", " Evil.Example "), ("csrf", &csrf)];
+    let response = post_form_as(&app, "/admin/blocklist/remove", ADMIN, &form).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(blocked_domains(&app).await, serde_json::json!([]));
+    let (status, _) = verify_from(&app, "https://evil.example:8443/verified").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+/// A change that can't be saved doesn't take effect either, so the blocklist in use is always
+/// the one that a restart would load.
+#[tokio::test]
+async fn unsaved_changes_take_no_effect() {
+    let app = app_with(|config| {
+        config.admin_token = Some(secrecy::SecretString::new(String::from("hunter2")));
+        config.blocklist_path = format!("{}/missing/blocklist.json", temp_path("d"));
+    });
+    let response = api_request(
+        &app,
+        Method::PUT,
+        "/api/admin/blocklist/evil.example",
+        "hunter2",
+    );
+    assert_eq!(response.await.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(blocked_domains(&app).await, serde_json::json!([]));
+    let (status, _) = verify_from(&app, "https://evil.example/verified").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+/// Tokens apps ask owners to publish go in the feed as `<podcast:txt purpose="verify">`, until
+/// they are withdrawn.
+#[tokio::test]
+async fn verification_tokens_are_published_in_the_feed() {
+    let app = admin_app();
+    let uri = "/api/admin/feeds/alice-podcast/txt/app-token-123";
+    let txt = "<podcast:txt purpose=\"verify\">app-token-123</podcast:txt>";
+    let etag = get(&app, "/feed/alice-podcast").await.headers()[header::ETAG].clone();
+
+    let response = api_request(&app, Method::PUT, uri, "hunter2").await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let response = api_request(&app, Method::PUT, uri, "hunter2").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = get(&app, "/feed/alice-podcast").await;
+    assert_ne!(response.headers()[header::ETAG], etag);
+    assert_eq!(body_text(response).await.matches(txt).count(), 1);
+    let tokens = api_get(&app, "/api/admin/feeds/alice-podcast/txt", "hunter2").await;
+    assert_eq!(body_text(tokens).await, "[\"app-token-123\"]");
+
+    for (uri, status) in [
+        (
+            "/api/admin/feeds/alice-podcast/txt/a%3Cb",
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            "/api/admin/feeds/nobody/txt/app-token-123",
+            StatusCode::NOT_FOUND,
+        ),
+    ] {
+        let response = api_request(&app, Method::PUT, uri, "hunter2").await;
+        assert_eq!(response.status(), status, "{uri}");
+    }
+
+    let response = api_request(&app, Method::DELETE, uri, "hunter2").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+    assert!(!feed.contains("<podcast:txt"), "{feed}");
+    let response = api_request(&app, Method::DELETE, uri, "hunter2").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,221 @@
+//! The consent screen shows the requesting app's name and icon, as looked up on its website.
+
+mod common;
+
+use axum::http::Uri;
+use axum::response::{Html, Redirect};
+use axum::routing;
+use axum::{Json, Router};
+use common::*;
+use hosting_company::fetch::{FeedFetcher, FetchConfig};
+use hosting_company::outbound;
+use std::time::Duration;
+use url::Url;
+
+/// The consent screen for an app at `app_url`, once its identity has been looked up in the
+/// background and `expected` shows up.
+async fn consent_screen_showing(app_url: &Url, expected: &str) -> String {
+    consent_screen_through(None, app_url, expected).await
+}
+
+/// [`consent_screen_showing`], with the app's website looked up through `proxy`.
+async fn consent_screen_through(
+    proxy: Option<outbound::Proxy>,
+    app_url: &Url,
+    expected: &str,
+) -> String {
+    let app = app_with(|config| {
+        config.fetch_app_identity = true;
+        // Allows looking up the test app, which is served on a loopback address.
+        config.dev_mode = true;
+        config.verify_rate_limit_per_ip = 1000;
+        config.verify_rate_limit_per_slug = 1000;
+        config.outbound_proxy = proxy;
+    });
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let return_url = app_url.join("verified").unwrap();
+    let uri = verify_uri(
+        "alice-podcast",
+        &[
+            ("encryptedString", &encrypted),
+            ("returnUrl", return_url.as_str()),
+        ],
+    );
+
+    for _ in 0..100 {
+        let page = body_text(get(&app, &uri).await).await;
+        if page.contains(expected) {
+            return page;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("consent screen never showed {expected:?}");
+}
+
+#[tokio::test]
+async fn name_and_icon_come_from_the_manifest() {
+    let app_url = serve(
+        Router::new()
+            .route(
+                "/",
+                routing::get(|| async {
+                    Html(
+                        "<html><head><title>Home</title>\
+                         <link rel='manifest' href=/app/manifest.json>\
+                         <link rel=\"shortcut icon\" href=\"/page-icon.png\"></head></html>",
+                    )
+                }),
+            )
+            .route(
+                "/app/manifest.json",
+                routing::get(|| async {
+                    Json(serde_json::json!({
+                        "name": "Pod  Player",
+                        "icons": [{"src": "icons/192.png", "sizes": "192x192"}],
+                    }))
+                }),
+            ),
+    );
+
+    let page = consent_screen_showing(&app_url, "<strong>Pod Player</strong>").await;
+    let icon_url = app_url.join("app/icons/192.png").unwrap();
+    assert!(page.contains(&format!("img src=\"{icon_url}\"")));
+}
+
+#[tokio::test]
+async fn name_falls_back_to_the_title_and_is_escaped() {
+    let app_url = serve(
+        Router::new()
+            .route(
+                "/",
+                routing::get(|| async {
+                    Html("<TITLE>\n  &lt;b&gt;Pod&amp;Player&lt;/b&gt;\n</TITLE>")
+                }),
+            )
+            .route("/favicon.ico", routing::get(|| async { "icon" })),
+    );
+
+    let page = consent_screen_showing(
+        &app_url,
+        "<strong>&lt;b&gt;Pod&amp;Player&lt;/b&gt;</strong>",
+    )
+    .await;
+    let icon_url = app_url.join("favicon.ico").unwrap();
+    assert!(page.contains(&format!("img src=\"{icon_url}\"")));
+}
+
+#[tokio::test]
+async fn manifests_and_icons_come_from_the_apps_own_origin() {
+    let elsewhere = serve(Router::new().route(
+        "/manifest.json",
+        routing::get(|| async { Json(serde_json::json!({"name": "Someone Else"})) }),
+    ));
+    let page = format!(
+        "<title>Pod Player</title>\
+         <link rel=manifest href={elsewhere}manifest.json>\
+         <link rel=icon href={elsewhere}icon.png>"
+    );
+    let app_url = serve(
+        Router::new()
+            .route("/", routing::get(|| async { Html(page) }))
+            .route("/favicon.ico", routing::get(|| async { "icon" })),
+    );
+
+    let page = consent_screen_showing(&app_url, "<strong>Pod Player</strong>").await;
+    assert!(!page.contains(elsewhere.as_str()));
+    let icon_url = app_url.join("favicon.ico").unwrap();
+    assert!(page.contains(&format!("img src=\"{icon_url}\"")));
+}
+
+/// Apps choose the URLs that are fetched, so they could point them at the server's own network.
+/// Only public addresses are contacted, whether the URL names them, a host resolves to them or a
+/// redirect leads to them.
+#[tokio::test]
+async fn internal_addresses_are_not_fetched() {
+    for url in [
+        "https://127.0.0.1/",
+        "https://10.0.0.1/",
+        "https://100.64.0.1/",
+        "https://169.254.169.254/latest/meta-data/",
+        "https://192.168.1.1/",
+        "https://[::1]/",
+        "https://[fd00::1]/",
+        "https://[fe80::1]/",
+        "https://[::ffff:10.0.0.1]/",
+        "http://app.example/",
+        "ftp://app.example/",
+    ] {
+        assert!(!outbound::allows(&Url::parse(url).unwrap(), false), "{url}");
+    }
+    assert!(outbound::allows(
+        &Url::parse("https://app.example/").unwrap(),
+        false
+    ));
+
+    let app_url = serve(
+        Router::new()
+            .route("/", routing::get(|| async { "home" }))
+            .route(
+                "/metadata",
+                routing::get(|| async { Redirect::temporary("http://169.254.169.254/") }),
+            ),
+    );
+    let by_name = Url::parse(&format!("http://localhost:{}/", app_url.port().unwrap())).unwrap();
+    let fetcher = |allow_loopback| {
+        let config = FetchConfig {
+            retries: 0,
+            ..FetchConfig::default()
+        };
+        let client = outbound::client_builder(config.max_redirects, allow_loopback, None);
+        FeedFetcher::with_client(client, config).unwrap()
+    };
+
+    // `localhost` only resolves to loopback addresses.
+    assert!(fetcher(false).fetch(&by_name).await.is_err());
+    assert_eq!(fetcher(true).fetch(&by_name).await.unwrap(), "home");
+    let redirect = by_name.join("metadata").unwrap();
+    assert!(fetcher(true).fetch(&redirect).await.is_err());
+}
+
+/// With a proxy configured, apps' websites are fetched through it, except for the hosts it is
+/// bypassed for.
+#[tokio::test]
+async fn websites_are_fetched_through_the_configured_proxy() {
+    let (sender, mut requested) = tokio::sync::mpsc::unbounded_channel();
+    let proxy_url = serve(Router::new().fallback(move |uri: Uri| {
+        let sender = sender.clone();
+        async move {
+            sender.send(uri.to_string()).unwrap();
+            Html("<html><head><title>Proxied App</title></head></html>")
+        }
+    }));
+    let proxy = outbound::Proxy {
+        url: proxy_url,
+        no_proxy: None,
+    };
+
+    // Nothing listens on the app's port: the page can only have come from the proxy.
+    let app_url = Url::parse("http://localhost:9/").unwrap();
+    consent_screen_through(
+        Some(proxy.clone()),
+        &app_url,
+        "<strong>Proxied App</strong>",
+    )
+    .await;
+    assert_eq!(requested.recv().await.unwrap(), "http://localhost:9/");
+
+    let app_url = serve(Router::new().route("/", routing::get(|| async { "home" })));
+    let direct = Url::parse(&format!("http://localhost:{}/", app_url.port().unwrap())).unwrap();
+    let bypassed = outbound::Proxy {
+        no_proxy: Some(String::from("localhost")),
+        ..proxy
+    };
+    let config = FetchConfig {
+        retries: 0,
+        ..FetchConfig::default()
+    };
+    let client = outbound::client_builder(config.max_redirects, true, Some(&bypassed));
+    let fetcher = FeedFetcher::with_client(client, config).unwrap();
+    // The proxy would have answered with its page instead.
+    assert_eq!(fetcher.fetch(&direct).await.unwrap(), "home");
+}
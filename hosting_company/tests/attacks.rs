@@ -0,0 +1,431 @@
+//! Plays a malicious app against the server. Each test names the defense that stops the attack.
+
+mod common;
+
+use axum::http::{header, StatusCode};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use common::*;
+use hosting_company::crypto;
+use url::Url;
+
+/// A returnUrl that isn't an absolute URL on a domain can't be shown to the owner as the app
+/// asking, so it could hide where the decrypted string would go, and one that isn't `http` or
+/// `https` could run script on our pages when the owner is sent back. Those requests are refused
+/// outright rather than redirected.
+#[tokio::test]
+async fn return_urls_without_a_domain_are_refused() {
+    let app = app();
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+
+    for return_url in [
+        "javascript:alert(document.cookie)",
+        "data:text/html,<script>alert(1)</script>",
+        "//evil.example/steal",
+        "/relative",
+        "file:///etc/passwd",
+        "javascript://app.example/%0Aalert(document.cookie)",
+        "data://app.example/text/html,<script>alert(1)</script>",
+        "ftp://app.example/verified",
+    ] {
+        let uri = verify_uri(
+            "alice-podcast",
+            &[("encryptedString", &encrypted), ("returnUrl", return_url)],
+        );
+        let response = get(&app, &uri).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{return_url}");
+        assert!(
+            !response.headers().contains_key(header::LOCATION),
+            "{return_url}"
+        );
+    }
+}
+
+/// Once an owner reports a phishing app, its domain is blocked and further requests from it go
+/// straight back to it with `error=access_denied`, without a consent screen to trick anyone with.
+/// Reporting takes the owner's password, so a rival can't get an app blocked by reporting it.
+#[tokio::test]
+async fn reported_domains_are_turned_away() {
+    let app = app_with(|config| config.auto_block_reported_domains = true);
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let phishing_return_url = "https://phishing.example/verified";
+    let uri = verify_uri(
+        "alice-podcast",
+        &[
+            ("encryptedString", &encrypted),
+            ("returnUrl", phishing_return_url),
+        ],
+    );
+    assert_eq!(get(&app, &uri).await.status(), StatusCode::OK);
+
+    // A report without the owner's password changes nothing.
+    for (password, status) in [
+        ("guess", StatusCode::UNAUTHORIZED),
+        ("password123", StatusCode::OK),
+    ] {
+        assert_eq!(get(&app, &uri).await.status(), StatusCode::OK);
+        let reported = post_form(
+            &app,
+            &uri,
+            &[
+                ("decision", "report"),
+                ("email", "alice@example.com"),
+                ("password", password),
+            ],
+        )
+        .await;
+        assert_eq!(reported.status(), status);
+    }
+
+    for response in [
+        get(&app, &uri).await,
+        post_form(
+            &app,
+            &uri,
+            &[
+                ("decision", "approve"),
+                ("email", "alice@example.com"),
+                ("password", "password123"),
+            ],
+        )
+        .await,
+    ] {
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let location = response.headers()[header::LOCATION].to_str().unwrap();
+        let location = Url::parse(location).unwrap();
+        assert_eq!(location.host_str(), Some("phishing.example"));
+        let params: Vec<_> = location.query_pairs().into_owned().collect();
+        assert!(params.contains(&("error".into(), "access_denied".into())));
+        assert!(!params.iter().any(|(name, _)| name == "decryptedString"));
+    }
+}
+
+/// A phishing page has no reason to send a challenge that decrypts, so reports don't depend on
+/// it: the owner's login and the validated returnUrl domain are enough.
+#[tokio::test]
+async fn requests_with_made_up_challenges_can_be_reported() {
+    let app = app_with(|config| config.auto_block_reported_domains = true);
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let len = crypto::decode_encrypted_string(&encrypted).unwrap().len();
+    let made_up = URL_SAFE_NO_PAD.encode(vec![1u8; len]);
+    let uri = verify_uri(
+        "alice-podcast",
+        &[
+            ("encryptedString", &made_up),
+            ("returnUrl", "https://phishing.example/verified"),
+        ],
+    );
+    assert_eq!(get(&app, &uri).await.status(), StatusCode::OK);
+
+    let reported = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "report"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    assert_eq!(reported.status(), StatusCode::OK);
+
+    let response = get(&app, &uri).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    let location = response.headers()[header::LOCATION].to_str().unwrap();
+    assert!(location.starts_with("https://phishing.example/verified?error=access_denied"));
+}
+
+/// Callbacks are sent from the server, so a callbackUrl on another host would let an app make
+/// the server post to internal services. It has to be on the returnUrl's host, which has to be
+/// registered for callbacks and on a public address.
+#[tokio::test]
+async fn callbacks_to_other_hosts_are_refused() {
+    let app = app_with(|config| {
+        config.admin_token = Some(secrecy::SecretString::new(String::from("admin")));
+    });
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    register_callbacks(&app, "app.example").await;
+    register_callbacks(&app, "10.0.0.1").await;
+
+    for (return_url, callback_url) in [
+        (RETURN_URL, "http://169.254.169.254/latest/meta-data/"),
+        (RETURN_URL, "http://localhost:8081/admin"),
+        (RETURN_URL, "https://app.example.evil.example/callback"),
+        (RETURN_URL, "ftp://app.example/callback"),
+        (RETURN_URL, "http://app.example/callback"),
+        ("https://10.0.0.1/verified", "https://10.0.0.1/callback"),
+        (
+            "https://other.example/verified",
+            "https://other.example/callback",
+        ),
+    ] {
+        let uri = verify_uri(
+            "alice-podcast",
+            &[
+                ("encryptedString", &encrypted),
+                ("returnUrl", return_url),
+                ("callbackUrl", callback_url),
+            ],
+        );
+        assert_eq!(
+            get(&app, &uri).await.status(),
+            StatusCode::BAD_REQUEST,
+            "{callback_url}"
+        );
+    }
+}
+
+/// A challenge only decrypts with the key of the feed it was encrypted for, so an app can't use
+/// one owner's approval to claim another feed.
+#[tokio::test]
+async fn challenges_for_one_feed_fail_on_another() {
+    let app = app();
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "bob-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "bob@example.com"),
+            ("password", "password456"),
+        ],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(!response.headers().contains_key(header::LOCATION));
+}
+
+/// Envelopes can carry strings of any length, so the length is capped before any work is done
+/// on them.
+#[tokio::test]
+async fn oversized_encrypted_strings_are_refused() {
+    let app = app();
+    let oversized = "A".repeat(crypto::MAX_ENCRYPTED_STRING_LEN + 4);
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &oversized), ("returnUrl", RETURN_URL)],
+    );
+
+    assert_eq!(get(&app, &uri).await.status(), StatusCode::BAD_REQUEST);
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Sign-in links are random one-time tokens tied to a podcast, so they can't be guessed or
+/// reused for another feed.
+#[tokio::test]
+async fn forged_sign_in_links_are_refused() {
+    let app = app();
+    let guessed = "0".repeat(64);
+    for token in ["", "0", guessed.as_str()] {
+        let uri = format!("/feed/alice-podcast/verify/magic?token={token}");
+        let response = get(&app, &uri).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+        assert!(!response.headers().contains_key(header::LOCATION));
+    }
+}
+
+/// Flooding the verify page, e.g. to spam owners with sign-in emails, is rate limited per client.
+#[tokio::test]
+async fn floods_are_rate_limited() {
+    let app = app_with(|config| config.verify_rate_limit_per_ip = 3);
+    let uri = verify_uri("alice-podcast", &[("returnUrl", RETURN_URL)]);
+
+    for _ in 0..3 {
+        assert_ne!(
+            get(&app, &uri).await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+    assert_eq!(
+        get(&app, &uri).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+/// The limit per podcast holds however the podcast is named, so that an app can't double its
+/// budget by switching between the slug and the GUID, or spelling the slug differently.
+#[tokio::test]
+async fn podcast_limits_count_every_name_of_the_podcast() {
+    let app = app_with(|config| config.verify_rate_limit_per_slug = 3);
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+    let (_, guid) = feed.split_once("<podcast:guid>").unwrap();
+    let (guid, _) = guid.split_once("</podcast:guid>").unwrap();
+    let uri = verify_uri("alice-podcast", &[("returnUrl", RETURN_URL)]);
+    let (_, query) = uri.split_once('?').unwrap();
+
+    for path in [
+        "/feed/alice-podcast/verify".to_string(),
+        format!("/feed/by-guid/{guid}/verify"),
+        format!("/feed/by-guid/{}/verify", guid.to_uppercase()),
+    ] {
+        let response = get(&app, &format!("{path}?{query}")).await;
+        assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS, "{path}");
+    }
+    assert_eq!(
+        get(&app, &uri).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    // Other podcasts have limits of their own.
+    let response = get(
+        &app,
+        &verify_uri("bob-podcast", &[("returnUrl", RETURN_URL)]),
+    )
+    .await;
+    assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+/// Password guessing is locked out after a few failures, per email address and per client IP,
+/// even if a guess during the lockout is right.
+#[tokio::test]
+async fn password_guessing_is_locked_out() {
+    let app = app_with(|config| config.login_lockout_threshold = 3);
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    let login = |email, password| {
+        let app = app.clone();
+        let uri = uri.clone();
+        async move {
+            post_form(
+                &app,
+                &uri,
+                &[
+                    ("decision", "approve"),
+                    ("email", email),
+                    ("password", password),
+                ],
+            )
+            .await
+        }
+    };
+
+    for guess in ["password", "123456", "hunter2"] {
+        let response = login("alice@example.com", guess).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    for (email, password) in [
+        ("alice@example.com", "password123"),
+        ("ALICE@example.com", "password123"),
+        ("someone@example.com", "password"),
+    ] {
+        let response = login(email, password).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS, "{email}");
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+        assert!(!response.headers().contains_key(header::LOCATION));
+    }
+}
+
+/// A successful login clears the failures before it, so owners who mistype now and then are
+/// never locked out.
+#[tokio::test]
+async fn logging_in_clears_failed_attempts() {
+    let app = app_with(|config| config.login_lockout_threshold = 3);
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+
+    for _ in 0..3 {
+        for password in ["typo", "password123"] {
+            post_form(
+                &app,
+                &uri,
+                &[
+                    ("decision", "approve"),
+                    ("email", "alice@example.com"),
+                    ("password", password),
+                ],
+            )
+            .await;
+        }
+    }
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    assert_eq!(redirect_params(&response)["decryptedString"], "challenge");
+}
+
+/// Probing for owners' email addresses by timing failed logins finds nothing: an unknown address
+/// is checked just as a wrong password is, in constant time. `/metrics` records how long each
+/// credential check and RSA operation took, so operators can see that invalid logins all take
+/// as long.
+#[tokio::test]
+async fn unknown_email_addresses_fail_like_wrong_passwords() {
+    let app = app_with_admin();
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+
+    for (email, password) in [
+        ("nobody@example.com", "password123"),
+        ("alice@example.com", "password124"),
+        ("alice@example.com", "password123"),
+    ] {
+        post_form(
+            &app,
+            &uri,
+            &[
+                ("decision", "approve"),
+                ("email", email),
+                ("password", password),
+            ],
+        )
+        .await;
+    }
+
+    for (series, count) in [
+        ("login_check_duration_seconds_count{outcome=\"invalid\"}", 2),
+        ("login_check_duration_seconds_count{outcome=\"valid\"}", 1),
+        ("crypto_duration_seconds_count{operation=\"decrypt\"}", 1),
+        ("crypto_duration_seconds_count{operation=\"sign\"}", 1),
+    ] {
+        assert_eq!(counter(&app, series).await, count, "{series}");
+    }
+}
+
+/// The consent screen is public, so it doesn't suggest owners' email addresses, not even the
+/// podcast's own, which would hand them to anyone with the feed URL to phish or guess passwords
+/// for.
+#[tokio::test]
+async fn the_consent_screen_names_no_owners() {
+    let app = app();
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+
+    let page = body_text(get(&app, &uri).await).await;
+    for email in ["alice@example.com", "bob@example.com", "carol@example.com"] {
+        assert!(!page.contains(email), "{email}");
+    }
+}
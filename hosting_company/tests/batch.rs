@@ -0,0 +1,197 @@
+//! Verifying several feeds in one flow, with a single login.
+
+mod common;
+
+use axum::http::StatusCode;
+use axum::Router;
+use common::*;
+use hosting_company::handshake::SignedVerification;
+use hosting_company::Podcast;
+use serde_json::Value;
+
+fn app() -> Router {
+    app_with_podcasts(vec![
+        Podcast::new(
+            "Alice's Podcast",
+            "alice-podcast",
+            "alice@example.com",
+            "password123",
+        ),
+        Podcast::new(
+            "Alice's Other Podcast",
+            "alice-other",
+            "alice@example.com",
+            "password123",
+        ),
+        Podcast::new(
+            "Bob's Podcast",
+            "bob-podcast",
+            "bob@example.com",
+            "password456",
+        ),
+    ])
+}
+
+/// A batch request for the given feeds, each with its challenge encrypted to the feed's key.
+async fn batch_uri(app: &Router, challenges: &[(&str, &str)]) -> String {
+    let mut query = url::form_urlencoded::Serializer::new(String::new());
+    query.append_pair("returnUrl", RETURN_URL);
+    query.append_pair("state", "xyz");
+    for (slug, challenge) in challenges {
+        let encrypted = encrypt_for(app, slug, challenge).await;
+        query.append_pair(&format!("encryptedString.{slug}"), &encrypted);
+    }
+    format!("/verify/batch?{}", query.finish())
+}
+
+/// The token of the form for choosing podcasts.
+fn token(page: &str) -> String {
+    let (_, token) = page.split_once("name=\"token\" value=\"").unwrap();
+    token.split_once('"').unwrap().0.to_string()
+}
+
+#[tokio::test]
+async fn one_login_verifies_the_chosen_podcasts() {
+    let app = app();
+    let uri = batch_uri(
+        &app,
+        &[
+            ("alice-podcast", "first"),
+            ("alice-other", "second"),
+            ("bob-podcast", "third"),
+        ],
+    )
+    .await;
+
+    let response = get(&app, &uri).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let page = body_text(response).await;
+    assert!(page.contains("Alice's Other Podcast"));
+    assert!(page.contains("Bob's Podcast"));
+
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "login"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let page = body_text(response).await;
+    assert!(page.contains("value=\"alice-podcast\""));
+    assert!(page.contains("value=\"alice-other\""));
+    assert!(!page.contains("value=\"bob-podcast\""));
+
+    let token = token(&page);
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("token", &token),
+            ("slug", "alice-podcast"),
+            ("slug", "bob-podcast"),
+        ],
+    )
+    .await;
+    let params = redirect_params(&response);
+    assert_eq!(params["state"], "xyz");
+    let results: Vec<Value> = serde_json::from_str(&params["results"]).unwrap();
+    let result = |slug: &str| {
+        results
+            .iter()
+            .find(|result| result["feedUrl"].as_str().unwrap().ends_with(slug))
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(results.len(), 3);
+    assert_eq!(result("/alice-podcast")["decryptedString"], "first");
+    assert!(result("/alice-podcast")["grantId"].is_string());
+    let token = result("/alice-podcast")["signedVerification"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let verification = SignedVerification::from_token(&token).unwrap();
+    assert_eq!(verification.challenge, "first");
+    assert_eq!(verification.audience, "https://app.example");
+    assert_eq!(
+        verification.feed.guid.as_deref(),
+        result("/alice-podcast")["feedGuid"].as_str()
+    );
+    // Not chosen by the owner, and not theirs to approve.
+    for slug in ["/alice-other", "/bob-podcast"] {
+        assert_eq!(result(slug)["error"], "access_denied");
+        assert!(result(slug).get("decryptedString").is_none());
+        assert!(result(slug).get("signedVerification").is_none());
+    }
+
+    // The token can't be used again.
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("token", &token),
+            ("slug", "alice-other"),
+        ],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn wrong_credentials_and_denials_verify_nothing() {
+    let app = app();
+    let uri = batch_uri(
+        &app,
+        &[("alice-podcast", "first"), ("alice-other", "second")],
+    )
+    .await;
+
+    for (email, password) in [
+        ("alice@example.com", "wrong"),
+        ("bob@example.com", "password456"),
+    ] {
+        let response = post_form(
+            &app,
+            &uri,
+            &[
+                ("decision", "login"),
+                ("email", email),
+                ("password", password),
+            ],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(!body_text(response).await.contains("name=\"token\""));
+    }
+
+    let response = post_form(&app, &uri, &[("decision", "deny")]).await;
+    let params = redirect_params(&response);
+    assert_eq!(params["error"], "access_denied");
+    assert!(!params.contains_key("results"));
+}
+
+#[tokio::test]
+async fn requests_for_unknown_podcasts_are_rejected() {
+    let app = app();
+    let uri = format!(
+        "/verify/batch?{}",
+        url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("returnUrl", RETURN_URL)
+            .append_pair("encryptedString.nobody-podcast", "Zm9v")
+            .finish()
+    );
+    assert_eq!(get(&app, &uri).await.status(), StatusCode::NOT_FOUND);
+
+    let uri = format!(
+        "/verify/batch?{}",
+        url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("returnUrl", RETURN_URL)
+            .finish()
+    );
+    assert_eq!(get(&app, &uri).await.status(), StatusCode::BAD_REQUEST);
+}
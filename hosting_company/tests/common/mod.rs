@@ -0,0 +1,318 @@
+//! Helpers shared by the integration tests, which drive the router in memory as an app and a
+//! podcast owner would.
+
+#![allow(dead_code)]
+
+use axum::body::Body;
+use axum::extract::connect_info::MockConnectInfo;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::response::Response;
+use axum::Router;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hosting_company::config::Config;
+use hosting_company::tag::feed_attr_to_public_key;
+use hosting_company::{crypto, handshake, AppState, Mailer, Podcast};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tower::ServiceExt;
+use url::Url;
+
+pub const RETURN_URL: &str = "https://app.example/verified?session=1";
+
+/// Key generation is slow, so all tests share one set of keys.
+pub fn keys() -> crypto::Keys {
+    static KEYS: OnceLock<crypto::Keys> = OnceLock::new();
+    KEYS.get_or_init(|| hosting_company::generate_keys(&hosting_company::demo_podcasts()))
+        .clone()
+}
+
+/// A path in the temporary directory that no other test uses.
+pub fn temp_path(extension: &str) -> String {
+    static PATHS: AtomicUsize = AtomicUsize::new(0);
+
+    std::env::temp_dir()
+        .join(format!(
+            "hosting_company-test-{}-{}.{extension}",
+            std::process::id(),
+            PATHS.fetch_add(1, Ordering::Relaxed)
+        ))
+        .to_string_lossy()
+        .into_owned()
+}
+
+pub fn app_with(configure: impl FnOnce(&mut Config)) -> Router {
+    app_with_keys(keys(), configure)
+}
+
+/// An app with its own keys, for tests that change them.
+pub fn app_with_keys(keys: crypto::Keys, configure: impl FnOnce(&mut Config)) -> Router {
+    build_app(keys, hosting_company::demo_podcasts(), configure)
+}
+
+/// An app hosting `podcasts` instead of the demo's. They must use the demo's key sizes.
+pub fn app_with_podcasts(podcasts: Vec<Podcast>) -> Router {
+    build_app(keys(), podcasts, |_| {})
+}
+
+fn build_app(
+    keys: crypto::Keys,
+    podcasts: Vec<Podcast>,
+    configure: impl FnOnce(&mut Config),
+) -> Router {
+    router_for(state_with(keys, podcasts, configure))
+}
+
+/// The state of an app, with everything it persists in temporary files.
+pub fn state_with(
+    keys: crypto::Keys,
+    podcasts: Vec<Podcast>,
+    configure: impl FnOnce(&mut Config),
+) -> AppState {
+    let mut config = Config::from_env();
+    // Tests that need new keys generate them on demand rather than in the background.
+    config.key_pool_size = 0;
+    // Tests' apps are on made-up domains, with no websites to look up.
+    config.fetch_app_identity = false;
+    config.blocklist_path = temp_path("json");
+    config.attempts_path = temp_path("jsonl");
+    config.grants_path = temp_path("json");
+    config.slug_history_path = temp_path("json");
+    config.callbacks_path = temp_path("json");
+    configure(&mut config);
+
+    AppState::new(config, podcasts, keys)
+}
+
+/// Keeps the email the app sends, as `(to, body)`.
+#[derive(Default)]
+pub struct Outbox(Mutex<Vec<(String, String)>>);
+
+impl Mailer for Outbox {
+    fn send(&self, to: &str, _subject: &str, body: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((to.to_string(), body.to_string()));
+    }
+}
+
+impl Outbox {
+    pub fn sent(&self) -> Vec<(String, String)> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// An app that sends its email to the returned [`Outbox`].
+pub fn app_with_outbox(configure: impl FnOnce(&mut Config)) -> (Router, Arc<Outbox>) {
+    let outbox = Arc::new(Outbox::default());
+    let state =
+        state_with(keys(), hosting_company::demo_podcasts(), configure).with_mailer(outbox.clone());
+    (router_for(state), outbox)
+}
+
+/// The router for `state`, with requests coming from a local client.
+pub fn router_for(state: AppState) -> Router {
+    let client = MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4321)));
+    hosting_company::router(state).layer(client)
+}
+
+/// Serves `app` on a free local port, for tests that need a real HTTP client.
+pub fn serve(app: Router) -> Url {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    serve_on(listener, app)
+}
+
+/// Serves an app on a free local port that links to itself there, for clients that follow
+/// the `verifyUrl` in its feeds.
+pub fn serve_app_with(configure: impl FnOnce(&mut Config)) -> Url {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+    let app = app_with(|config| {
+        config.public_base_url = base_url;
+        configure(config);
+    });
+    serve_on(listener, app)
+}
+
+fn serve_on(listener: std::net::TcpListener, app: Router) -> Url {
+    let addr = listener.local_addr().unwrap();
+    let server = axum::Server::from_tcp(listener)
+        .unwrap()
+        .serve(app.into_make_service());
+    tokio::spawn(server);
+    Url::parse(&format!("http://{addr}/")).unwrap()
+}
+
+/// Registers `domain` for callbacks with the admin API, as an app with the admin token `admin`
+/// can, and returns the secret they are signed with.
+pub async fn register_callbacks(app: &Router, domain: &str) -> String {
+    let request = Request::put(format!("/api/admin/callbacks/{domain}"))
+        .header(header::AUTHORIZATION, "Bearer admin")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let registration: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    registration["secret"].as_str().unwrap().to_string()
+}
+
+/// The value of a Prometheus counter on `/metrics`, with its labels as in the exposition format.
+/// `app` must have the admin token `admin`, as [`app_with_admin`]'s does.
+pub async fn counter(app: &Router, series: &str) -> u64 {
+    let request = Request::get("/metrics")
+        .header(header::AUTHORIZATION, "Bearer admin")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let metrics = body_text(response).await;
+    metrics
+        .lines()
+        .find_map(|line| line.strip_prefix(series)?.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+pub fn app() -> Router {
+    app_with(|_| {})
+}
+
+/// An app with the admin token `admin`, for tests that use the admin API or read `/metrics`.
+pub fn app_with_admin() -> Router {
+    app_with(|config| {
+        config.admin_token = Some(secrecy::SecretString::new(String::from("admin")));
+    })
+}
+
+pub async fn get(app: &Router, uri: &str) -> Response {
+    app.clone()
+        .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+pub async fn post_form(app: &Router, uri: &str, form: &[(&str, &str)]) -> Response {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(form)
+        .finish();
+    app.clone()
+        .oneshot(
+            Request::post(uri)
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+/// Sends a request signed in with HTTP Basic credentials, `email:password`.
+pub async fn request_as(app: &Router, method: Method, uri: &str, credentials: &str) -> Response {
+    let authorization = format!("Basic {}", STANDARD.encode(credentials));
+    app.clone()
+        .oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::AUTHORIZATION, authorization)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+/// Posts a form signed in with HTTP Basic credentials, as a browser would after signing in.
+pub async fn post_form_as(
+    app: &Router,
+    uri: &str,
+    credentials: &str,
+    form: &[(&str, &str)],
+) -> Response {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(form)
+        .finish();
+    app.clone()
+        .oneshot(
+            Request::post(uri)
+                .header(
+                    header::AUTHORIZATION,
+                    format!("Basic {}", STANDARD.encode(credentials)),
+                )
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+/// The token in the forms of the page at `uri`, as shown to the signed-in owner or admin.
+pub async fn csrf_token(app: &Router, uri: &str, credentials: &str) -> String {
+    let page = body_text(request_as(app, Method::GET, uri, credentials).await).await;
+    let (_, token) = page
+        .split_once("name=\"csrf\" value=\"")
+        .expect("page has a form token");
+    token.split_once('"').unwrap().0.to_string()
+}
+
+pub async fn body_text(response: Response) -> String {
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+/// The value of an attribute of `<podcast:verify>` in the feed.
+pub fn verify_attribute(feed: &str, name: &str) -> String {
+    let start = feed
+        .find(&format!("{name}=\""))
+        .expect("attribute is present")
+        + name.len()
+        + 2;
+    let end = start + feed[start..].find('"').unwrap();
+    feed[start..end].to_string()
+}
+
+/// Fetches the feed and encrypts `challenge` to the key and with the padding it advertises, as
+/// an app would.
+pub async fn encrypt_for(app: &Router, slug: &str, challenge: &str) -> String {
+    let response = get(app, &format!("/feed/{slug}")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let feed = body_text(response).await;
+
+    let public_key = feed_attr_to_public_key(&verify_attribute(&feed, "publicKey")).unwrap();
+    let padding = match verify_attribute(&feed, "encryption").as_str() {
+        "RSA-OAEP-256" => crypto::Padding::OaepSha256,
+        "RSA1_5" => crypto::Padding::Pkcs1v15,
+        other => panic!("unexpected encryption {other}"),
+    };
+    handshake::encrypt(&public_key, padding, challenge).unwrap()
+}
+
+pub fn verify_uri(slug: &str, params: &[(&str, &str)]) -> String {
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    format!("/feed/{slug}/verify?{query}")
+}
+
+/// Where a page's `<meta http-equiv="refresh">` sends the browser, and after how many seconds.
+pub fn meta_refresh(page: &str) -> (u64, Url) {
+    let (_, content) = page
+        .split_once("http-equiv=\"refresh\" content=\"")
+        .expect("page has a meta refresh");
+    let (content, _) = content.split_once('"').unwrap();
+    let (delay_secs, url) = content.split_once("; url=").unwrap();
+    let url = Url::parse(&url.replace("&amp;", "&")).unwrap();
+    (delay_secs.parse().unwrap(), url)
+}
+
+/// The query parameters of the redirect back to the app.
+pub fn redirect_params(response: &Response) -> HashMap<String, String> {
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    let location = response.headers()[header::LOCATION].to_str().unwrap();
+    let location = Url::parse(location).unwrap();
+    assert_eq!(location.host_str(), Some("app.example"));
+    location.query_pairs().into_owned().collect()
+}
@@ -0,0 +1,66 @@
+//! Compression: feeds and pages are sent gzip- or Brotli-compressed to clients that accept it.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request};
+use axum::response::Response;
+use axum::Router;
+use common::*;
+use flate2::read::GzDecoder;
+use std::io::Read;
+use tower::ServiceExt;
+
+async fn get_encoded(app: &Router, uri: &str, accept_encoding: &str) -> Response {
+    let request = Request::get(uri)
+        .header(header::ACCEPT_ENCODING, accept_encoding)
+        .body(Body::empty())
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap()
+}
+
+fn content_encoding(response: &Response) -> Option<&str> {
+    response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .map(|value| value.to_str().unwrap())
+}
+
+#[tokio::test]
+async fn feeds_are_gzipped_for_clients_that_accept_it() {
+    let app = app();
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+
+    let response = get_encoded(&app, "/feed/alice-podcast", "gzip").await;
+    assert_eq!(content_encoding(&response), Some("gzip"));
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let mut decoded = String::new();
+    GzDecoder::new(&body[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, feed);
+}
+
+#[tokio::test]
+async fn brotli_is_sent_when_accepted() {
+    let app = app();
+    let response = get_encoded(&app, "/feed/alice-podcast", "br").await;
+    assert_eq!(content_encoding(&response), Some("br"));
+
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    let response = get_encoded(&app, &uri, "br, gzip").await;
+    assert!(content_encoding(&response).is_some());
+}
+
+#[tokio::test]
+async fn responses_are_not_compressed_unasked() {
+    let app = app();
+    let response = get(&app, "/feed/alice-podcast").await;
+    assert_eq!(content_encoding(&response), None);
+    let response = get_encoded(&app, "/feed/alice-podcast", "identity").await;
+    assert_eq!(content_encoding(&response), None);
+}
@@ -0,0 +1,75 @@
+//! Conditional requests for feeds: apps that poll with the `ETag` or `Last-Modified` they were
+//! given get a `304 Not Modified` until the feed changes.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::response::Response;
+use axum::Router;
+use common::*;
+use tower::ServiceExt;
+
+async fn get_with(app: &Router, headers: &[(header::HeaderName, &str)]) -> Response {
+    let mut request = Request::get("/feed/alice-podcast");
+    for (name, value) in headers {
+        request = request.header(name, *value);
+    }
+    app.clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+fn header_value(response: &Response, name: header::HeaderName) -> String {
+    response.headers()[name].to_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn matching_etags_are_not_modified() {
+    let app = app();
+    let response = get(&app, "/feed/alice-podcast").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = header_value(&response, header::ETAG);
+
+    let response = get_with(&app, &[(header::IF_NONE_MATCH, &etag)]).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(header_value(&response, header::ETAG), etag);
+    assert_eq!(body_text(response).await, "");
+
+    let response = get_with(&app, &[(header::IF_NONE_MATCH, "\"something-else\"")]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body_text(response).await.contains("<podcast:verify "));
+}
+
+#[tokio::test]
+async fn feeds_are_not_modified_since_their_last_modified_date() {
+    let app = app();
+    let response = get(&app, "/feed/alice-podcast").await;
+    let last_modified = header_value(&response, header::LAST_MODIFIED);
+
+    let response = get_with(&app, &[(header::IF_MODIFIED_SINCE, &last_modified)]).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let long_ago = "Sat, 01 Jan 2000 00:00:00 GMT";
+    let response = get_with(&app, &[(header::IF_MODIFIED_SINCE, long_ago)]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// A client with an out-of-date `ETag` gets the new feed, whatever its `If-Modified-Since`.
+#[tokio::test]
+async fn etags_take_precedence_over_dates() {
+    let app = app();
+    let response = get(&app, "/feed/alice-podcast").await;
+    let last_modified = header_value(&response, header::LAST_MODIFIED);
+
+    let response = get_with(
+        &app,
+        &[
+            (header::IF_NONE_MATCH, "\"something-else\""),
+            (header::IF_MODIFIED_SINCE, &last_modified),
+        ],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
@@ -0,0 +1,65 @@
+//! The `verify-conformance` checker, run as a binary against hosts served on local ports.
+
+mod common;
+
+use axum::{routing, Router};
+use common::*;
+use std::process::{Command, Output};
+use url::Url;
+
+/// Runs `verify-conformance` with `args` without blocking the servers it checks.
+async fn conformance(args: Vec<String>) -> Output {
+    tokio::task::spawn_blocking(move || {
+        Command::new(env!("CARGO_BIN_EXE_verify-conformance"))
+            .args(args)
+            .output()
+            .unwrap()
+    })
+    .await
+    .unwrap()
+}
+
+fn feed_url(base_url: &Url) -> String {
+    base_url.join("feed/alice-podcast").unwrap().to_string()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn this_host_passes_the_checks() {
+    let base_url = serve_app_with(|_| {});
+
+    let output = conformance(vec![
+        feed_url(&base_url),
+        String::from("--email"),
+        String::from("alice@example.com"),
+        String::from("--password"),
+        String::from("password123"),
+    ])
+    .await;
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    assert!(!stdout.contains("FAIL"), "{stdout}");
+    assert!(stdout.contains("All checks passed."), "{stdout}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn broken_hosts_fail_the_checks() {
+    let untagged = serve(Router::new().route(
+        "/feed/alice-podcast",
+        routing::get(|| async {
+            r#"<?xml version="1.0"?><rss version="2.0"><channel><title>Alice</title></channel></rss>"#
+        }),
+    ));
+    let output = conformance(vec![feed_url(&untagged)]).await;
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{stdout}");
+    assert!(
+        stdout.contains("FAIL  <podcast:verify> is present"),
+        "{stdout}"
+    );
+
+    let missing = serve(Router::new());
+    let output = conformance(vec![feed_url(&missing)]).await;
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{stdout}");
+    assert!(stdout.contains("FAIL  feed is served"), "{stdout}");
+}
@@ -0,0 +1,200 @@
+//! Malformed `encryptedString`s: each way one can be wrong ends in its own decrypt error and a
+//! `400 Bad Request`, never a panic or a server error, whatever bytes an app sends.
+
+mod common;
+
+use axum::http::StatusCode;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use common::*;
+use hosting_company::crypto::{self, CryptoConfig, DecryptError, KeyPair, Padding};
+use hosting_company::handshake::encrypt;
+use proptest::prelude::*;
+use rsa::traits::PublicKeyParts;
+use rsa::{Oaep, Pkcs1v15Encrypt};
+use sha2::Sha256;
+
+/// The demo's 2048-bit key pair.
+fn key_pair() -> KeyPair {
+    let Ok(pairs) = keys().get(&CryptoConfig::default()) else {
+        panic!("the demo has a 2048-bit key");
+    };
+    pairs[0].clone()
+}
+
+fn decrypt(padding: Padding, encrypted_string: &str) -> Result<String, DecryptError> {
+    crypto::decrypt(&key_pair().private_key, padding, encrypted_string)
+}
+
+#[test]
+fn strings_that_are_not_base64_are_rejected() {
+    for encrypted_string in ["not base64!", "A", "AAAAA", "€€€€", "AA==AA"] {
+        assert_eq!(
+            decrypt(Padding::Pkcs1v15, encrypted_string),
+            Err(DecryptError::Base64),
+            "{encrypted_string:?}"
+        );
+    }
+}
+
+#[test]
+fn ciphertexts_of_the_wrong_length_are_rejected() {
+    let key_len = key_pair().public_key.size();
+    // Nothing, one byte short of a block, one byte over, and one byte short of the smallest
+    // envelope.
+    for len in [0, 1, 2, key_len - 1, key_len + 1, key_len + 12 + 16 - 1] {
+        let encrypted_string = STANDARD.encode(vec![0x42; len]);
+        for padding in [Padding::Pkcs1v15, Padding::OaepSha256] {
+            assert_eq!(
+                decrypt(padding, &encrypted_string),
+                Err(DecryptError::Length { len, key_len }),
+                "{len}"
+            );
+        }
+    }
+    assert_eq!(
+        crypto::decode_ciphertext(&STANDARD.encode(vec![0x42; key_len]), key_len).unwrap(),
+        vec![0x42; key_len]
+    );
+}
+
+#[test]
+fn blocks_that_do_not_decrypt_are_rejected() {
+    let key_pair = key_pair();
+    let garbage = STANDARD.encode(vec![0x42; key_pair.public_key.size()]);
+    for padding in [Padding::Pkcs1v15, Padding::OaepSha256] {
+        assert_eq!(
+            decrypt(padding, &garbage),
+            Err(DecryptError::Rsa),
+            "{padding:?}"
+        );
+    }
+}
+
+#[test]
+fn envelopes_that_do_not_open_are_rejected() {
+    let key_pair = key_pair();
+    let envelope = crypto::seal_envelope(&key_pair.public_key, b"challenge")
+        .ok()
+        .unwrap();
+
+    let mut tampered = envelope.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert_eq!(
+        decrypt(Padding::Pkcs1v15, &STANDARD.encode(&tampered)),
+        Err(DecryptError::Envelope)
+    );
+
+    // A wrapped key that isn't 32 bytes.
+    let mut short_key = key_pair
+        .public_key
+        .encrypt(&mut rand::thread_rng(), Oaep::new::<Sha256>(), &[0; 16])
+        .unwrap();
+    short_key.extend_from_slice(&envelope[key_pair.public_key.size()..]);
+    assert_eq!(
+        decrypt(Padding::Pkcs1v15, &STANDARD.encode(&short_key)),
+        Err(DecryptError::Envelope)
+    );
+
+    let mut garbage_key = envelope;
+    garbage_key[0] ^= 1;
+    assert_eq!(
+        decrypt(Padding::Pkcs1v15, &STANDARD.encode(&garbage_key)),
+        Err(DecryptError::Rsa)
+    );
+}
+
+#[test]
+fn plaintexts_that_are_not_utf8_are_rejected() {
+    let key_pair = key_pair();
+    let ciphertext = key_pair
+        .public_key
+        .encrypt(
+            &mut rand::thread_rng(),
+            Pkcs1v15Encrypt,
+            &[0xff, 0xfe, 0x00],
+        )
+        .unwrap();
+    assert_eq!(
+        decrypt(Padding::Pkcs1v15, &URL_SAFE_NO_PAD.encode(ciphertext)),
+        Err(DecryptError::Utf8)
+    );
+}
+
+#[test]
+fn no_keys_decrypt_nothing() {
+    let key_pair = key_pair();
+    let encrypted_string = encrypt(&key_pair.public_key, Padding::Pkcs1v15, "challenge").unwrap();
+    assert!(crypto::decrypt_with_any(&[], Padding::Pkcs1v15, &encrypted_string).is_err());
+}
+
+proptest! {
+    #[test]
+    fn any_string_decrypts_or_fails_cleanly(encrypted_string in "\\PC{0,600}") {
+        let _ = decrypt(Padding::Pkcs1v15, &encrypted_string);
+        let _ = decrypt(Padding::OaepSha256, &encrypted_string);
+    }
+
+    #[test]
+    fn any_bytes_decrypt_or_fail_cleanly(bytes in prop::collection::vec(any::<u8>(), 0..600)) {
+        let encrypted_string = STANDARD.encode(&bytes);
+        for padding in [Padding::Pkcs1v15, Padding::OaepSha256] {
+            if let Err(error) = decrypt(padding, &encrypted_string) {
+                prop_assert_ne!(error, DecryptError::Base64);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn malformed_encrypted_strings_are_bad_requests() {
+    let app = app();
+    let form = [
+        ("decision", "approve"),
+        ("email", "alice@example.com"),
+        ("password", "password123"),
+    ];
+    let block = STANDARD.encode(vec![0x42; 256]);
+    let short = STANDARD.encode(vec![0x42; 255]);
+    let envelope = STANDARD.encode(vec![0x42; 256 + 12 + 16]);
+    for encrypted_string in ["", "%%%", "A", "€", &short, &block, &envelope] {
+        let uri = verify_uri(
+            "alice-podcast",
+            &[
+                ("encryptedString", encrypted_string),
+                ("returnUrl", RETURN_URL),
+            ],
+        );
+        let response = post_form(&app, &uri, &form).await;
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "{encrypted_string:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn malformed_encrypted_strings_are_refused_before_the_login_form() {
+    let app = app();
+    let short = STANDARD.encode(vec![0x42; 255]);
+    for encrypted_string in ["not base64!", "A", &short] {
+        let uri = verify_uri(
+            "alice-podcast",
+            &[
+                ("encryptedString", encrypted_string),
+                ("returnUrl", RETURN_URL),
+            ],
+        );
+        let response = get(&app, &uri).await;
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "{encrypted_string:?}"
+        );
+        let page = body_text(response).await;
+        assert!(!page.contains("name=\"password\""), "{encrypted_string:?}");
+        let (_, url) = meta_refresh(&page);
+        assert!(url.as_str().starts_with(RETURN_URL));
+    }
+}
@@ -0,0 +1,92 @@
+//! The demo's app, verifying podcasts against a host served on a local port.
+
+mod common;
+
+use axum::http::{header, StatusCode};
+use axum::response::Response;
+use axum::Router;
+use common::*;
+use hosting_company::demo_app;
+use url::Url;
+
+/// Starts a verification of Alice's podcast with `app` and returns the `verifyUrl` it sends the
+/// owner to.
+async fn start(app: &Router, host_url: &Url) -> Url {
+    let feed_url = host_url.join("feed/alice-podcast").unwrap();
+    let uri = format!(
+        "/verify?{}",
+        url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("feedUrl", feed_url.as_str())
+            .finish()
+    );
+    let response = get(app, &uri).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    Url::parse(response.headers()[header::LOCATION].to_str().unwrap()).unwrap()
+}
+
+/// Answers the consent form at `verify_url` as the owner and returns where the host sends them.
+async fn answer(verify_url: Url, form: &[(&str, &str)]) -> Url {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let response = client.post(verify_url).form(form).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::SEE_OTHER);
+    Url::parse(response.headers()[header::LOCATION].to_str().unwrap()).unwrap()
+}
+
+/// Follows the host's redirect back to `app`.
+async fn finish(app: &Router, return_url: &Url) -> Response {
+    assert!(return_url.as_str().starts_with("http://app.example/verified?"));
+    get(app, &return_url[url::Position::BeforePath..]).await
+}
+
+fn app() -> Router {
+    let base_url = Url::parse("http://app.example/").unwrap();
+    demo_app::router(
+        base_url,
+        Url::parse("http://localhost:8081/feed/alice-podcast").unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn the_app_offers_the_default_feed() {
+    let page = body_text(get(&app(), "/").await).await;
+    assert!(page.contains("value=\"http://localhost:8081/feed/alice-podcast\""));
+}
+
+#[tokio::test]
+async fn podcasts_are_verified_once_their_owner_approves() {
+    let host_url = serve_app_with(|_| {});
+    let app = app();
+
+    let verify_url = start(&app, &host_url).await;
+    assert_eq!(verify_url.path(), "/feed/alice-podcast/verify");
+    let return_url = answer(
+        verify_url,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    let response = finish(&app, &return_url).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body_text(response).await.contains("<h1>Verified</h1>"));
+
+    let replayed = finish(&app, &return_url).await;
+    assert_eq!(replayed.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn podcasts_are_not_verified_when_their_owner_denies() {
+    let host_url = serve_app_with(|_| {});
+    let app = app();
+
+    let verify_url = start(&app, &host_url).await;
+    let return_url = answer(verify_url, &[("decision", "deny")]).await;
+    let response = finish(&app, &return_url).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(body_text(response).await.contains("access_denied"));
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::*;
+
+#[tokio::test]
+async fn the_verification_endpoints_are_described() {
+    let app = app();
+    let response = get(&app, "/openapi.json").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let spec: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+
+    let paths = &spec["paths"];
+    assert!(paths["/feed/{slug}"]["get"].is_object());
+    let parameters: Vec<&str> = paths["/feed/{slug}/verify"]["get"]["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|parameter| parameter["name"].as_str().unwrap())
+        .collect();
+    for name in [
+        "slug",
+        "encryptedString",
+        "returnUrl",
+        "state",
+        "callbackUrl",
+    ] {
+        assert!(parameters.contains(&name), "{name} is missing");
+    }
+    assert!(paths["/feed/{slug}/verify"]["post"]["requestBody"].is_object());
+    assert!(spec["components"]["schemas"]["ErrorBody"].is_object());
+
+    let response = get(&app, "/docs/").await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
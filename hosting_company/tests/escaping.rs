@@ -0,0 +1,78 @@
+//! Podcast titles are free text, so every character in them has to survive the feed and the
+//! verify page unchanged: escaped where it has meaning in XML or HTML, and kept as UTF-8
+//! otherwise.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::*;
+use hosting_company::Podcast;
+
+/// Titles, with how they must appear in the feed and on the verify page.
+const TITLES: [(&str, &str, &str); 6] = [
+    ("Plain Podcast", "Plain Podcast", "Plain Podcast"),
+    ("Tom & Jerry", "Tom &amp; Jerry", "Tom &amp; Jerry"),
+    (
+        "<script>alert(1)</script>",
+        "&lt;script&gt;alert(1)&lt;/script&gt;",
+        "&lt;script&gt;alert(1)&lt;/script&gt;",
+    ),
+    (
+        "“Smart” quotes & \"plain\" 'ones'",
+        "“Smart” quotes &amp; &quot;plain&quot; &apos;ones&apos;",
+        "“Smart” quotes &amp; &quot;plain&quot; 'ones'",
+    ),
+    ("🎙️ Late Night 🚀", "🎙️ Late Night 🚀", "🎙️ Late Night 🚀"),
+    (
+        "深夜ラジオ 🎧 <特別編>",
+        "深夜ラジオ 🎧 &lt;特別編&gt;",
+        "深夜ラジオ 🎧 &lt;特別編&gt;",
+    ),
+];
+
+fn app_with_title(title: &str) -> axum::Router {
+    app_with_podcasts(vec![Podcast::new(
+        title,
+        "show",
+        "owner@example.com",
+        "secret",
+    )])
+}
+
+#[tokio::test]
+async fn titles_are_escaped_in_the_feed() {
+    for (title, in_feed, _) in TITLES {
+        let app = app_with_title(title);
+        let response = get(&app, "/feed/show").await;
+        assert_eq!(response.status(), StatusCode::OK, "{title}");
+        let feed = body_text(response).await;
+        assert!(
+            feed.contains(&format!("<title>{in_feed}</title>")),
+            "{title}: {feed}"
+        );
+        assert_eq!(
+            feed.matches('<').count(),
+            feed.matches('>').count(),
+            "{title}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn titles_are_escaped_on_the_verify_page() {
+    for (title, _, on_page) in TITLES {
+        let app = app_with_title(title);
+        let encrypted = encrypt_for(&app, "show", "challenge").await;
+        let uri = verify_uri(
+            "show",
+            &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+        );
+        let response = get(&app, &uri).await;
+        assert_eq!(response.status(), StatusCode::OK, "{title}");
+        let page = body_text(response).await;
+        assert!(page.contains(on_page), "{title}: {page}");
+        if title.contains('<') {
+            assert!(!page.contains(title), "{title} is rendered unescaped");
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Apps that can't be redirected to, subscribing to a challenge and getting the result pushed once
+//! the owner approves in their browser.
+
+mod common;
+
+use axum::http::{header, StatusCode};
+use axum::response::Response;
+use axum::Router;
+use common::*;
+use hosting_company::handshake::SignedVerification;
+use serde_json::Value;
+use std::time::Duration;
+
+const OWNER: [(&str, &str); 3] = [
+    ("decision", "approve"),
+    ("email", "alice@example.com"),
+    ("password", "password123"),
+];
+
+async fn subscribe(app: &Router, challenge: &str) -> Response {
+    get(
+        app,
+        &format!("/feed/alice-podcast/verify/events?challenge={challenge}"),
+    )
+    .await
+}
+
+/// The event's name and its data, from a stream that sent a single event.
+fn only_event(stream: &str) -> (&str, Value) {
+    let mut name = None;
+    let mut data = None;
+    for line in stream.lines() {
+        // Fields may or may not have a space after the colon.
+        if let Some(value) = line.strip_prefix("event:").map(str::trim_start) {
+            assert_eq!(name.replace(value), None, "{stream}");
+        } else if let Some(value) = line.strip_prefix("data:").map(str::trim_start) {
+            data = Some(serde_json::from_str(value).unwrap());
+        }
+    }
+    (name.expect("an event"), data.expect("event data"))
+}
+
+#[tokio::test]
+async fn approvals_are_pushed_to_subscribed_apps() {
+    let app = app();
+    let response = subscribe(&app, "challenge").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/event-stream"
+    );
+    let stream = tokio::spawn(body_text(response));
+    // Another app's subscription, which this approval mustn't complete.
+    let other = subscribe(&app, "other").await;
+
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[
+            ("encryptedString", &encrypted),
+            ("returnUrl", RETURN_URL),
+            ("state", "tv-1"),
+        ],
+    );
+    let params = redirect_params(&post_form(&app, &uri, &OWNER).await);
+
+    let stream = tokio::time::timeout(Duration::from_secs(5), stream)
+        .await
+        .expect("the stream ends after the approval")
+        .unwrap();
+    let (name, data) = only_event(&stream);
+    assert_eq!(name, "approved");
+    assert!(data["feedUrl"]
+        .as_str()
+        .unwrap()
+        .ends_with("/feed/alice-podcast"));
+    assert_eq!(data["decryptedString"], "challenge");
+    assert_eq!(data["grantId"], params["grantId"].as_str());
+    assert_eq!(data["state"], "tv-1");
+    let verification =
+        SignedVerification::from_token(data["signedVerification"].as_str().unwrap()).unwrap();
+    assert_eq!(verification.challenge, "challenge");
+
+    let other = tokio::spawn(body_text(other));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!other.is_finished());
+    other.abort();
+}
+
+#[tokio::test]
+async fn subscriptions_need_a_challenge_and_a_published_podcast() {
+    let app = app();
+    for (uri, status) in [
+        ("/feed/alice-podcast/verify/events", StatusCode::BAD_REQUEST),
+        (
+            "/feed/alice-podcast/verify/events?challenge=",
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            "/feed/no-such-podcast/verify/events?challenge=challenge",
+            StatusCode::NOT_FOUND,
+        ),
+    ] {
+        assert_eq!(get(&app, uri).await.status(), status, "{uri}");
+    }
+}
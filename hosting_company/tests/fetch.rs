@@ -0,0 +1,111 @@
+mod common;
+
+use common::*;
+use hosting_company::fetch::{FeedCache, FeedFetcher, FetchConfig, FetchError};
+use std::time::Duration;
+
+const SERVED: &str = "feed_requests_total{outcome=\"served\"}";
+const NOT_MODIFIED: &str = "feed_requests_total{outcome=\"not_modified\"}";
+
+#[tokio::test]
+async fn fresh_feeds_are_served_from_the_cache() {
+    let app = app_with_admin();
+    let feed_url = serve(app.clone()).join("feed/alice-podcast").unwrap();
+    let fetcher = fetcher();
+    let cache = FeedCache::new(Duration::from_secs(60), 1024 * 1024);
+
+    let first = cache.fetch(&fetcher, &feed_url).await.unwrap();
+    let second = cache.fetch(&fetcher, &feed_url).await.unwrap();
+    assert_eq!(first, second);
+    assert!(first.contains("<podcast:verify "));
+    assert_eq!(counter(&app, SERVED).await, 1);
+    assert_eq!(cache.cached_bytes(), first.len());
+}
+
+#[tokio::test]
+async fn stale_feeds_are_revalidated() {
+    let app = app_with_admin();
+    let feed_url = serve(app.clone()).join("feed/alice-podcast").unwrap();
+    let fetcher = fetcher();
+    let cache = FeedCache::new(Duration::ZERO, 1024 * 1024);
+
+    let first = cache.fetch(&fetcher, &feed_url).await.unwrap();
+    let second = cache.fetch(&fetcher, &feed_url).await.unwrap();
+    assert_eq!(first, second);
+    assert_eq!(counter(&app, SERVED).await, 1);
+    assert_eq!(counter(&app, NOT_MODIFIED).await, 1);
+}
+
+#[tokio::test]
+async fn the_cache_stays_within_its_size_limit() {
+    let app = app_with_admin();
+    let base_url = serve(app.clone());
+    let alice = base_url.join("feed/alice-podcast").unwrap();
+    let bob = base_url.join("feed/bob-podcast").unwrap();
+    let fetcher = fetcher();
+
+    let feed = fetcher.fetch(&alice).await.unwrap().len();
+    let cache = FeedCache::new(Duration::from_secs(60), feed + feed / 2);
+    cache.fetch(&fetcher, &alice).await.unwrap();
+    cache.fetch(&fetcher, &bob).await.unwrap();
+    assert!(cache.cached_bytes() <= feed + feed / 2);
+
+    // Alice's feed was evicted to make room for Bob's, so it is downloaded again.
+    cache.fetch(&fetcher, &alice).await.unwrap();
+    assert_eq!(counter(&app, SERVED).await, 4);
+
+    let tiny = FeedCache::new(Duration::from_secs(60), 10);
+    tiny.fetch(&fetcher, &alice).await.unwrap();
+    assert_eq!(tiny.cached_bytes(), 0);
+}
+
+#[tokio::test]
+async fn failed_fetches_are_reported() {
+    let feed_url = serve(app()).join("feed/nobody").unwrap();
+    let cache = FeedCache::new(Duration::from_secs(60), 1024 * 1024);
+    let error = cache.fetch(&fetcher(), &feed_url).await.unwrap_err();
+    assert_eq!(error.to_string(), "status 404 Not Found");
+}
+
+fn fetcher() -> FeedFetcher {
+    FeedFetcher::new(FetchConfig::default()).unwrap()
+}
+
+#[tokio::test]
+async fn client_errors_are_not_retried() {
+    let app = app_with_admin();
+    let feed_url = serve(app.clone()).join("feed/nobody").unwrap();
+    let error = fetcher().fetch(&feed_url).await.unwrap_err();
+    assert!(matches!(error, FetchError::Status(status) if status == 404));
+    let series = "feed_requests_total{outcome=\"podcast_not_found\"}";
+    assert_eq!(counter(&app, series).await, 1);
+}
+
+#[tokio::test]
+async fn redirects_are_bounded() {
+    let base_url = serve(app());
+    let moved = base_url.join("feed/Alice-Podcast").unwrap();
+
+    let feed = fetcher().fetch(&moved).await.unwrap();
+    assert!(feed.contains("<podcast:verify "));
+
+    let fetcher = FeedFetcher::new(FetchConfig {
+        max_redirects: 0,
+        ..FetchConfig::default()
+    })
+    .unwrap();
+    let error = fetcher.fetch(&moved).await.unwrap_err();
+    assert!(matches!(error, FetchError::Http(e) if e.is_redirect()));
+}
+
+#[tokio::test]
+async fn oversized_feeds_are_abandoned() {
+    let feed_url = serve(app()).join("feed/alice-podcast").unwrap();
+    let fetcher = FeedFetcher::new(FetchConfig {
+        max_bytes: 100,
+        ..FetchConfig::default()
+    })
+    .unwrap();
+    let error = fetcher.fetch(&feed_url).await.unwrap_err();
+    assert!(matches!(error, FetchError::TooLarge { max_bytes: 100 }));
+}
@@ -0,0 +1,97 @@
+//! The protocol trace viewer: in dev mode, each verification flow is recorded step by step and
+//! can be viewed, or downloaded as a Mermaid sequence diagram, under `/dev/flows`.
+
+mod common;
+
+use axum::http::{header, StatusCode};
+use axum::Router;
+use common::*;
+
+/// Shows the consent screen for a verification request, and returns the page.
+async fn start_flow(app: &Router) -> String {
+    let encrypted = encrypt_for(app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    let response = get(app, &uri).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    body_text(response).await
+}
+
+/// The trace link on a page, `/dev/flows/<id>`.
+fn trace_link(page: &str) -> Option<String> {
+    let (_, rest) = page.split_once("href=\"/dev/flows/")?;
+    let (id, _) = rest.split_once('"')?;
+    Some(format!("/dev/flows/{id}"))
+}
+
+#[tokio::test]
+async fn flows_are_traced_in_dev_mode() {
+    let app = app_with(|config| config.dev_mode = true);
+    let page = start_flow(&app).await;
+    let flow = trace_link(&page).expect("consent screen links to its trace");
+
+    let flows = body_text(get(&app, "/dev/flows").await).await;
+    assert!(flows.contains(&flow), "{flows}");
+
+    let response = get(&app, &flow).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body_text(response).await.contains("alice-podcast"));
+
+    let response = get(&app, &format!("{flow}/sequence.mmd")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()[header::CONTENT_DISPOSITION]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+    let diagram = body_text(response).await;
+    assert!(diagram.starts_with("sequenceDiagram\n"), "{diagram}");
+    assert!(diagram.contains("User->>Host:"), "{diagram}");
+}
+
+#[tokio::test]
+async fn flows_are_not_traced_otherwise() {
+    let app = app();
+    let page = start_flow(&app).await;
+    assert_eq!(trace_link(&page), None);
+
+    let dev_app = app_with(|config| config.dev_mode = true);
+    let flow = trace_link(&start_flow(&dev_app).await).unwrap();
+    for uri in [
+        String::from("/dev/flows"),
+        flow.clone(),
+        format!("{flow}/sequence.mmd"),
+    ] {
+        assert_eq!(
+            get(&app, &uri).await.status(),
+            StatusCode::NOT_FOUND,
+            "{uri}"
+        );
+    }
+}
+
+/// What apps send ends up in the diagram, escaped so that it can't end a message or add lines.
+#[tokio::test]
+async fn sequence_diagrams_escape_what_apps_send() {
+    let app = app_with(|config| config.dev_mode = true);
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[
+            ("encryptedString", &encrypted),
+            ("returnUrl", RETURN_URL),
+            ("state", "a#b;c\nUser->>App: injected"),
+        ],
+    );
+    let page = body_text(get(&app, &uri).await).await;
+    let flow = trace_link(&page).unwrap();
+
+    let diagram = body_text(get(&app, &format!("{flow}/sequence.mmd")).await).await;
+    assert!(diagram.contains("a#35;b#59;c"), "{diagram}");
+    assert!(!diagram.contains("#35#59;"), "{diagram}");
+    for line in diagram.lines().skip(1) {
+        assert!(line.starts_with("    "), "{line}");
+        assert!(!line.starts_with("    User->>App: injected"), "{line}");
+    }
+}
@@ -0,0 +1,280 @@
+//! The whole handshake, encrypt → decrypt → sign → verify, over random challenges, both paddings
+//! and several key sizes, and what happens when any part of it is tampered with or presented to
+//! the wrong app.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hosting_company::crypto::{KeyPair, Padding};
+use hosting_company::handshake::{
+    audience, complete_handshake, encrypt, validate_handshake, FeedIdentity, HandshakeError,
+    SignedVerification, VERIFICATION_TTL,
+};
+use proptest::prelude::*;
+use proptest::sample::Index;
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+const KEY_BITS: [usize; 2] = [2048, 3072];
+
+/// Key generation is slow, so each size is generated once for all cases.
+fn key_pair(bits: usize) -> KeyPair {
+    static KEYS: OnceLock<Vec<KeyPair>> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        KEY_BITS
+            .iter()
+            .map(|bits| {
+                let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), *bits).unwrap();
+                KeyPair {
+                    public_key: RsaPublicKey::from(&private_key),
+                    private_key,
+                }
+            })
+            .collect()
+    })[KEY_BITS.iter().position(|size| *size == bits).unwrap()]
+    .clone()
+}
+
+/// The host's signing key, apart from the encryption keys.
+fn signing_key() -> KeyPair {
+    static KEY: OnceLock<KeyPair> = OnceLock::new();
+    KEY.get_or_init(|| {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+        KeyPair {
+            public_key: RsaPublicKey::from(&private_key),
+            private_key,
+        }
+    })
+    .clone()
+}
+
+fn return_url() -> Url {
+    Url::parse("https://app.example/verified?session=1").unwrap()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn padding() -> impl Strategy<Value = Padding> {
+    prop_oneof![Just(Padding::Pkcs1v15), Just(Padding::OaepSha256)]
+}
+
+#[test]
+fn ciphertexts_of_other_challenges_are_rejected() {
+    let key_pair = key_pair(2048);
+    let encrypted = encrypt(&key_pair.public_key, Padding::OaepSha256, "one").unwrap();
+    assert_eq!(
+        complete_handshake(
+            &key_pair,
+            &signing_key(),
+            Padding::OaepSha256,
+            &encrypted,
+            "two",
+            &return_url()
+        ),
+        Err(HandshakeError::Mismatch)
+    );
+}
+
+#[test]
+fn verifications_are_bound_to_the_app_and_expire() {
+    let key_pair = key_pair(2048);
+    let keys = [signing_key().public_key];
+    let verification = validate_handshake(
+        &key_pair,
+        &signing_key(),
+        Padding::OaepSha256,
+        "challenge",
+        &return_url(),
+    )
+    .unwrap();
+    assert_eq!(verification.audience, "https://app.example");
+    let issued_at = verification.issued_at;
+    assert_eq!(
+        verification.expires_at,
+        issued_at + VERIFICATION_TTL.as_secs()
+    );
+
+    let token = SignedVerification::from_token(&verification.to_token()).unwrap();
+    assert_eq!(token, verification);
+    // Only the origin counts, not the path or query.
+    let other_page = Url::parse("https://app.example/elsewhere").unwrap();
+    assert_eq!(token.validate(&keys, &other_page, issued_at), Ok(()));
+
+    for other_app in [
+        "https://evil.example/verified?session=1",
+        "http://app.example/verified?session=1",
+        "https://app.example:8443/verified?session=1",
+    ] {
+        assert_eq!(
+            token.validate(&keys, &Url::parse(other_app).unwrap(), issued_at),
+            Err(HandshakeError::WrongAudience),
+            "{other_app}"
+        );
+    }
+    assert_eq!(
+        token.validate(&keys, &return_url(), token.expires_at),
+        Err(HandshakeError::Expired)
+    );
+    assert_eq!(
+        token.validate(&keys, &return_url(), issued_at - 3600),
+        Err(HandshakeError::Expired)
+    );
+
+    // Moving the audience or the expiry breaks the signature.
+    let mut forged = token.clone();
+    forged.audience = String::from("https://evil.example");
+    assert_eq!(
+        forged.validate(&keys, &Url::parse("https://evil.example/").unwrap(), now()),
+        Err(HandshakeError::InvalidSignature)
+    );
+    let mut forged = token;
+    forged.expires_at += 3600;
+    assert_eq!(
+        forged.validate(&keys, &return_url(), issued_at + 3599),
+        Err(HandshakeError::InvalidSignature)
+    );
+
+    assert_eq!(
+        SignedVerification::from_token("not a token"),
+        Err(HandshakeError::InvalidToken)
+    );
+}
+
+/// Other implementations check signatures over the same bytes, so their layout can't change.
+#[test]
+fn the_signed_bytes_are_length_prefixed_fields() {
+    let verification = SignedVerification {
+        challenge: String::from("chälle\nnge"),
+        audience: String::from("https://app.example"),
+        feed: FeedIdentity {
+            guid: None,
+            url: Some(String::from("https://host.example/feed/show")),
+        },
+        issued_at: 1_700_000_000,
+        expires_at: 1_700_000_600,
+        signature: Vec::new(),
+    };
+    assert_eq!(
+        String::from_utf8(verification.signed_bytes()).unwrap(),
+        "podcast:verify signed verification v1\n\
+         19:https://app.example\n\
+         0:\n\
+         30:https://host.example/feed/show\n\
+         10:1700000000\n\
+         10:1700000600\n\
+         11:chälle\nnge\n"
+    );
+}
+
+#[test]
+fn apps_without_an_origin_are_identified_by_their_url() {
+    let url = Url::parse("podplayer://verified/callback?session=1#top").unwrap();
+    assert_eq!(audience(&url), "podplayer://verified/callback");
+}
+
+proptest! {
+    // RSA is slow in debug builds.
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    /// Short challenges fit in one RSA block and long ones are sent as envelopes.
+    #[test]
+    fn handshakes_complete_and_verify(
+        challenge in "\\PC{0,400}",
+        bits in proptest::sample::select(KEY_BITS.to_vec()),
+        padding in padding(),
+    ) {
+        let pair = key_pair(bits);
+        let verification =
+            validate_handshake(&pair, &signing_key(), padding, &challenge, &return_url()).unwrap();
+        prop_assert_eq!(&verification.challenge, &challenge);
+        prop_assert!(verification.verify(&signing_key().public_key).is_ok());
+        // Never signed with the key challenges are encrypted to.
+        prop_assert_eq!(
+            verification.verify(&pair.public_key),
+            Err(HandshakeError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn corrupted_ciphertexts_are_rejected(
+        challenge in "[a-z0-9]{1,64}",
+        padding in padding(),
+        index in any::<Index>(),
+        flip in 1u8..,
+    ) {
+        let key_pair = key_pair(2048);
+        let encrypted = encrypt(&key_pair.public_key, padding, &challenge).unwrap();
+        let mut ciphertext = URL_SAFE_NO_PAD.decode(&encrypted).unwrap();
+        let index = index.index(ciphertext.len());
+        ciphertext[index] ^= flip;
+
+        let result = complete_handshake(
+            &key_pair,
+            &signing_key(),
+            padding,
+            &URL_SAFE_NO_PAD.encode(ciphertext),
+            &challenge,
+            &return_url(),
+        );
+        prop_assert!(
+            matches!(result, Err(HandshakeError::Decrypt | HandshakeError::Mismatch)),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn arbitrary_ciphertexts_are_rejected_without_panicking(
+        bytes in proptest::collection::vec(any::<u8>(), 0..600),
+        padding in padding(),
+    ) {
+        let key_pair = key_pair(2048);
+        let result = complete_handshake(
+            &key_pair,
+            &signing_key(),
+            padding,
+            &URL_SAFE_NO_PAD.encode(bytes),
+            "challenge",
+            &return_url(),
+        );
+        prop_assert!(result.is_err());
+    }
+
+    #[test]
+    fn tampered_signatures_are_rejected(
+        challenge in "[a-z0-9]{1,64}",
+        index in any::<Index>(),
+        flip in 1u8..,
+    ) {
+        let key_pair = key_pair(2048);
+        let verification = validate_handshake(
+            &key_pair,
+            &signing_key(),
+            Padding::OaepSha256,
+            &challenge,
+            &return_url(),
+        )
+        .unwrap();
+
+        let mut tampered = verification.clone();
+        let index = index.index(tampered.signature.len());
+        tampered.signature[index] ^= flip;
+        prop_assert_eq!(
+            tampered.verify(&signing_key().public_key),
+            Err(HandshakeError::InvalidSignature)
+        );
+
+        let mut tampered = verification;
+        tampered.challenge.push('!');
+        prop_assert_eq!(
+            tampered.verify(&signing_key().public_key),
+            Err(HandshakeError::InvalidSignature)
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Security headers: the consent screen can't be framed or run inline scripts, and HTTPS is
+//! pinned once the server serves it.
+
+mod common;
+
+use axum::http::header;
+use axum::response::Response;
+use axum::Router;
+use common::*;
+use serde_json::json;
+
+async fn consent_screen(app: &Router) -> Response {
+    let encrypted = encrypt_for(app, "alice-podcast", "challenge").await;
+    get(
+        app,
+        &verify_uri(
+            "alice-podcast",
+            &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+        ),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn html_pages_cannot_be_framed_or_run_inline_scripts() {
+    let app = app();
+    let response = consent_screen(&app).await;
+    let headers = response.headers().clone();
+    let csp = headers[header::CONTENT_SECURITY_POLICY].to_str().unwrap();
+    assert!(csp.contains("frame-ancestors 'none'"), "{csp}");
+    let script_src = csp
+        .split(';')
+        .map(str::trim)
+        .find(|directive| directive.starts_with("script-src"))
+        .expect("a script-src directive");
+    assert_eq!(script_src, "script-src 'self'");
+    assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+    assert_eq!(headers[header::REFERRER_POLICY], "no-referrer");
+    assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+    assert!(!body_text(response).await.contains("<script"));
+
+    // Error pages are HTML too.
+    let response = get(&app, "/feed/no-such-podcast/verify?returnUrl=x").await;
+    assert!(response
+        .headers()
+        .contains_key(header::CONTENT_SECURITY_POLICY));
+
+    let response = get(&app, "/feed/alice-podcast").await;
+    assert!(!response
+        .headers()
+        .contains_key(header::CONTENT_SECURITY_POLICY));
+    assert!(!response.headers().contains_key(header::X_FRAME_OPTIONS));
+}
+
+#[tokio::test]
+async fn https_is_pinned_when_serving_tls() {
+    let app = app_with(|config| {
+        config.tls_cert_path = Some(String::from("cert.pem"));
+        config.tls_key_path = Some(String::from("key.pem"));
+    });
+    for uri in ["/", "/feed/alice-podcast"] {
+        let response = get(&app, uri).await;
+        assert_eq!(
+            response.headers()[header::STRICT_TRANSPORT_SECURITY],
+            "max-age=31536000",
+            "{uri}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn security_headers_follow_the_config_file() {
+    let path = temp_path("json");
+    std::fs::write(&path, "{}").unwrap();
+    let state = state_with(keys(), hosting_company::demo_podcasts(), |config| {
+        config.config_path = Some(path.clone());
+    });
+    let app = router_for(state.clone());
+
+    std::fs::write(
+        &path,
+        json!({
+            "contentSecurityPolicy": "default-src 'self'; frame-ancestors https://partner.example",
+            "frameOptions": "",
+            "referrerPolicy": "same-origin",
+        })
+        .to_string(),
+    )
+    .unwrap();
+    state.reload().await.unwrap();
+
+    let response = consent_screen(&app).await;
+    let headers = response.headers();
+    assert_eq!(
+        headers[header::CONTENT_SECURITY_POLICY],
+        "default-src 'self'; frame-ancestors https://partner.example"
+    );
+    assert!(!headers.contains_key(header::X_FRAME_OPTIONS));
+    assert_eq!(headers[header::REFERRER_POLICY], "same-origin");
+
+    std::fs::write(&path, json!({"referrerPolicy": "bad\nvalue"}).to_string()).unwrap();
+    assert!(state.reload().await.is_err());
+}
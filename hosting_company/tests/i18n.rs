@@ -0,0 +1,60 @@
+//! Translations: pages are shown in the best language the browser accepts, in English when there
+//! is none.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::response::Response;
+use axum::Router;
+use common::*;
+use tower::ServiceExt;
+
+async fn get_in(app: &Router, uri: &str, accept_language: &str) -> Response {
+    let request = Request::get(uri)
+        .header(header::ACCEPT_LANGUAGE, accept_language)
+        .body(Body::empty())
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn consent_screens_follow_accept_language() {
+    let app = app();
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+
+    for (accept_language, locale, approve) in [
+        ("de-DE,de;q=0.9,en;q=0.8", "de", "Zustimmen"),
+        ("fr-FR, es;q=0.5", "es", "Aprobar"),
+        ("fr-FR", "en-US", "Approve"),
+    ] {
+        let response = get_in(&app, &uri, accept_language).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_LANGUAGE], locale);
+        assert!(response
+            .headers()
+            .get_all(header::VARY)
+            .iter()
+            .any(|vary| vary == "accept-language"));
+        let page = body_text(response).await;
+        assert!(page.contains(&format!("lang=\"{locale}\"")), "{page}");
+        assert!(page.contains(approve), "{accept_language}");
+    }
+
+    let response = get(&app, &uri).await;
+    assert_eq!(response.headers()[header::CONTENT_LANGUAGE], "en-US");
+}
+
+#[tokio::test]
+async fn error_pages_are_translated() {
+    let app = app();
+    let uri = verify_uri("alice-podcast", &[("returnUrl", RETURN_URL)]);
+    let page = body_text(get_in(&app, &uri, "es").await).await;
+    assert!(page.contains("es obligatorio"), "{page}");
+    let page = body_text(get_in(&app, &uri, "de").await).await;
+    assert!(page.contains("ist erforderlich"), "{page}");
+}
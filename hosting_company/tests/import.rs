@@ -0,0 +1,148 @@
+//! Importing a podcast from a feed served elsewhere, and verifying it like any other.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::response::Response;
+use axum::routing::get as route_get;
+use axum::Router;
+use common::*;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+const FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd" xmlns:pi="https://podcastindex.org/namespace/1.0">
+  <channel>
+    <title>Dave &amp; Friends</title>
+    <description><![CDATA[Conversations <b>with</b> friends.]]></description>
+    <itunes:author>Dave</itunes:author>
+    <pi:guid>917393e3-1b1e-5cef-ace4-edaa54e1f810</pi:guid>
+    <pi:locked owner="dave@old-host.example">yes</pi:locked>
+    <pi:verify verifyUrl="https://old-host.example/verify" publicKey="old"/>
+    <item>
+      <title>Episode 1</title>
+      <guid isPermaLink="false">dave-1</guid>
+      <enclosure url="https://cdn.example/1.mp3" length="1" type="audio/mpeg"/>
+    </item>
+    <item>
+      <title>Episode 2</title>
+    </item>
+  </channel>
+</rss>"#;
+
+fn admin_app() -> Router {
+    app_with(|config| {
+        config.admin_token = Some(secrecy::SecretString::new(String::from("admin")));
+    })
+}
+
+/// Serves `body` as a feed on a local port.
+fn external_feed(body: &'static str) -> String {
+    serve(Router::new().route("/feed.xml", route_get(move || async move { body })))
+        .join("feed.xml")
+        .unwrap()
+        .to_string()
+}
+
+async fn import(app: &Router, slug: &str, url: &str) -> Response {
+    let body = json!({
+        "url": url,
+        "ownerEmail": "dave@example.com",
+        "ownerPassword": "password000",
+    });
+    app.clone()
+        .oneshot(
+            Request::put(format!("/api/admin/feeds/{slug}"))
+                .header(header::AUTHORIZATION, "Bearer admin")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn imported_feeds_are_republished_with_the_hosts_tags() {
+    let app = admin_app();
+    let response = import(&app, "dave-and-friends", &external_feed(FEED)).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let imported: Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(imported["title"], "Dave & Friends");
+    assert_eq!(
+        imported["description"],
+        "Conversations <b>with</b> friends."
+    );
+    assert_eq!(
+        imported["episodes"],
+        json!([
+            {"title": "Episode 1", "guid": "dave-1", "enclosureUrl": "https://cdn.example/1.mp3"},
+            {"title": "Episode 2"},
+        ])
+    );
+    assert!(imported["feedUrl"]
+        .as_str()
+        .unwrap()
+        .ends_with("/feed/dave-and-friends"));
+    // The GUID stays with the podcast when it moves hosts.
+    assert_eq!(imported["guid"], "917393e3-1b1e-5cef-ace4-edaa54e1f810");
+
+    let feed = body_text(get(&app, "/feed/dave-and-friends").await).await;
+    assert!(feed.contains(r#"xmlns:podcast="https://podcastindex.org/namespace/1.0""#));
+    assert!(feed.contains("<itunes:author>Dave</itunes:author>"));
+    assert!(feed.contains("<title>Episode 2</title>"));
+    assert!(feed.contains(r#"<podcast:locked owner="dave@example.com">no</podcast:locked>"#));
+    assert!(!feed.contains("old-host.example"), "{feed}");
+    assert!(!feed.contains("<podcast:guid>"), "{feed}");
+    let by_guid = get(&app, "/feed/by-guid/917393e3-1b1e-5cef-ace4-edaa54e1f810").await;
+    assert_eq!(body_text(by_guid).await, feed);
+    assert!(verify_attribute(&feed, "verifyUrl").ends_with("/feed/dave-and-friends/verify"));
+
+    let encrypted = encrypt_for(&app, "dave-and-friends", "challenge").await;
+    let uri = verify_uri(
+        "dave-and-friends",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "dave@example.com"),
+            ("password", "password000"),
+        ],
+    )
+    .await;
+    assert_eq!(redirect_params(&response)["decryptedString"], "challenge");
+}
+
+#[tokio::test]
+async fn imports_need_a_free_slug_and_an_rss_feed() {
+    let app = admin_app();
+    let feed = external_feed(FEED);
+    for slug in ["alice-podcast", "Dave"] {
+        let response = import(&app, slug, &feed).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{slug}");
+    }
+    assert_eq!(
+        import(&app, "dave", "ftp://example.com/feed.xml")
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    for body in [
+        "<html><body>Not a feed</body></html>",
+        "<rss><channel><title>Unclosed</channel></rss>",
+        "<rss><channel><description>No title</description></channel></rss>",
+        r#"<rss xmlns:podcast="urn:other"><channel><title>Clash</title></channel></rss>"#,
+    ] {
+        let response = import(&app, "dave", &external_feed(body)).await;
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY, "{body}");
+    }
+    assert_eq!(
+        get(&app, "/feed/dave").await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
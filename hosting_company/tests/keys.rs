@@ -0,0 +1,84 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::*;
+use hosting_company::crypto::{CryptoConfig, KeyPair, KeyPool, Keys};
+use std::time::Duration;
+
+#[tokio::test]
+async fn servers_start_before_their_keys_are_generated() {
+    let state = state_with(Keys::empty(), hosting_company::demo_podcasts(), |_| {});
+    let app = router_for(state.clone());
+
+    let response = get(&app, "/healthz").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(body_text(response).await.contains("\"keys_loaded\":false"));
+
+    state.load_keys().await;
+    assert_eq!(get(&app, "/healthz").await.status(), StatusCode::OK);
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+    assert_eq!(feed.matches("<podcast:verify ").count(), 1);
+}
+
+#[tokio::test]
+async fn the_pool_refills_in_the_background() {
+    let pool = KeyPool::start([2048], 1);
+    wait_until(|| pool.available(2048) == 1).await;
+
+    let pair = pool.take(2048).await;
+    assert_eq!(pool.available(2048), 0);
+    wait_until(|| pool.available(2048) == 1).await;
+    let next = pool.take(2048).await;
+    assert_ne!(pair.public_key, next.public_key);
+}
+
+#[tokio::test]
+async fn empty_pools_generate_on_demand() {
+    let pool = KeyPool::start([2048], 0);
+    let keys = Keys::empty();
+    keys.install(pool.take(2048).await);
+    assert!(keys.has(2048));
+    assert!(keys.valid());
+    assert_eq!(pool.available(2048), 0);
+}
+
+/// Keys are checked as they are installed, and `/healthz` reports the result from then on.
+#[tokio::test]
+async fn health_checks_report_mismatched_keys() {
+    // The demo podcasts all use the default key size.
+    let shared = keys();
+    let current = shared
+        .get(&CryptoConfig::default())
+        .ok()
+        .expect("key loaded")
+        .remove(0);
+    let signing = shared.signing().ok().expect("signing key loaded");
+    let keys = Keys::empty();
+    keys.install(current.clone());
+    keys.install_signing(signing.clone());
+    let app = router_for(state_with(
+        keys.clone(),
+        hosting_company::demo_podcasts(),
+        |_| {},
+    ));
+    assert_eq!(get(&app, "/healthz").await.status(), StatusCode::OK);
+
+    keys.install(KeyPair {
+        private_key: signing.private_key,
+        public_key: current.public_key,
+    });
+    assert!(!keys.valid());
+    let response = get(&app, "/healthz").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(body_text(response).await.contains("\"keys_loaded\":false"));
+}
+
+async fn wait_until(condition: impl Fn() -> bool) {
+    tokio::time::timeout(Duration::from_secs(60), async {
+        while !condition() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("condition not met in time");
+}
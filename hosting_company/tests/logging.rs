@@ -0,0 +1,112 @@
+//! Request logging: every request is logged under a span with its podcast, and handlers log the
+//! outcome of feeds and verifications.
+
+mod common;
+
+use common::*;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Collects everything logged while it is the default subscriber's writer.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl Logs {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Logs {
+    type Writer = Logs;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Logs everything the current thread logs until the guard is dropped. Tests run on a
+/// single-threaded runtime, so that includes the handlers.
+fn capture() -> (Logs, tracing::subscriber::DefaultGuard) {
+    let logs = Logs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(logs.clone())
+        .with_ansi(false)
+        .finish();
+    (logs, tracing::subscriber::set_default(subscriber))
+}
+
+#[tokio::test]
+async fn feeds_are_logged_with_their_podcast_and_outcome() {
+    let app = app();
+    let (logs, _guard) = capture();
+
+    get(&app, "/feed/alice-podcast").await;
+    get(&app, "/feed/nobody").await;
+
+    let logs = logs.text();
+    let served = logs
+        .lines()
+        .find(|line| line.contains("feed served"))
+        .unwrap();
+    assert!(served.contains("path=\"/feed/alice-podcast\""), "{served}");
+    assert!(served.contains("slug=\"alice-podcast\""), "{served}");
+    assert!(served.contains("outcome=\"served\""), "{served}");
+    let missing = logs
+        .lines()
+        .find(|line| line.contains("feed not served"))
+        .unwrap();
+    assert!(
+        missing.contains("outcome=\"podcast_not_found\""),
+        "{missing}"
+    );
+}
+
+#[tokio::test]
+async fn verifications_are_logged_with_the_app_and_outcome() {
+    let app = app();
+    let (logs, _guard) = capture();
+
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    get(&app, &uri).await;
+    get(
+        &app,
+        &verify_uri("alice-podcast", &[("encryptedString", &encrypted)]),
+    )
+    .await;
+
+    let logs = logs.text();
+    let started = logs
+        .lines()
+        .find(|line| line.contains("verification started"))
+        .unwrap();
+    assert!(
+        started.contains("return_url_domain=\"app.example\""),
+        "{started}"
+    );
+    assert!(started.contains("outcome=\"consent_shown\""), "{started}");
+    let rejected = logs
+        .lines()
+        .find(|line| line.contains("verification rejected"))
+        .unwrap();
+    assert!(
+        rejected.contains("outcome=\"missing_return_url\""),
+        "{rejected}"
+    );
+    assert!(!logs.contains(&encrypted), "the challenge is not logged");
+}
@@ -0,0 +1,63 @@
+//! Prometheus metrics: counters of feeds and verifications, served only to scrapers with the
+//! admin token.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use common::*;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn metrics_need_the_admin_token() {
+    let app = app_with_admin();
+    assert_eq!(
+        get(&app, "/metrics").await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    let request = Request::get("/metrics")
+        .header(header::AUTHORIZATION, "Bearer wrong")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Without a token configured, there are no metrics to scrape.
+    assert_eq!(
+        get(&common::app(), "/metrics").await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn feeds_and_verifications_are_counted() {
+    let app = app_with_admin();
+    get(&app, "/feed/alice-podcast").await;
+    get(&app, "/feed/nobody").await;
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    get(
+        &app,
+        &verify_uri("alice-podcast", &[("encryptedString", &encrypted)]),
+    )
+    .await;
+    get(
+        &app,
+        &verify_uri(
+            "alice-podcast",
+            &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+        ),
+    )
+    .await;
+
+    for (series, count) in [
+        ("feed_requests_total{outcome=\"served\"}", 2),
+        ("feed_requests_total{outcome=\"podcast_not_found\"}", 1),
+        ("verification_attempts_total", 2),
+        (
+            "verification_failures_total{reason=\"missing_return_url\"}",
+            1,
+        ),
+    ] {
+        assert_eq!(counter(&app, series).await, count, "{series}");
+    }
+}
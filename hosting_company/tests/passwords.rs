@@ -0,0 +1,367 @@
+//! Owners changing their password from the dashboard, and resetting a forgotten one with a link
+//! emailed to them.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::response::Response;
+use axum::Router;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use common::*;
+use serde_json::json;
+use tower::ServiceExt;
+use url::Url;
+
+async fn send_as(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    credentials: &str,
+    content_type: &str,
+    body: String,
+) -> Response {
+    app.clone()
+        .oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(
+                    header::AUTHORIZATION,
+                    format!("Basic {}", STANDARD.encode(credentials)),
+                )
+                .header(header::CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+async fn change_password(app: &Router, credentials: &str, form: &[(&str, &str)]) -> StatusCode {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(form)
+        .finish();
+    send_as(
+        app,
+        Method::POST,
+        "/feed/alice-podcast/password",
+        credentials,
+        "application/x-www-form-urlencoded",
+        body,
+    )
+    .await
+    .status()
+}
+
+async fn dashboard_as(app: &Router, credentials: &str) -> StatusCode {
+    request_as(
+        app,
+        Method::GET,
+        "/feed/alice-podcast/dashboard",
+        credentials,
+    )
+    .await
+    .status()
+}
+
+async fn reset(app: &Router, token: &str, password: &str, confirmation: &str) -> StatusCode {
+    let form = [
+        ("token", token),
+        ("password", password),
+        ("confirmation", confirmation),
+    ];
+    post_form(app, "/password/reset", &form).await.status()
+}
+
+/// The path and query of the link in an email.
+fn link_in(body: &str) -> String {
+    let link = body
+        .split_whitespace()
+        .find(|word| word.starts_with("http"))
+        .expect("email has a link");
+    let link = Url::parse(link).unwrap();
+    format!("{}?{}", link.path(), link.query().unwrap_or_default())
+}
+
+#[tokio::test]
+async fn owners_change_their_password_from_the_dashboard() {
+    let (app, _) = app_with_outbox(|_| {});
+    let old = "alice@example.com:password123";
+    let new = "alice@example.com:correct horse";
+    let csrf = csrf_token(&app, "/feed/alice-podcast/dashboard", old).await;
+
+    for form in [
+        [
+            ("current_password", "password123"),
+            ("password", "short"),
+            ("confirmation", "short"),
+            ("csrf", &csrf),
+        ],
+        [
+            ("current_password", "password123"),
+            ("password", "correct horse"),
+            ("confirmation", "correct hose"),
+            ("csrf", &csrf),
+        ],
+    ] {
+        assert_eq!(
+            change_password(&app, old, &form).await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+    assert_eq!(dashboard_as(&app, old).await, StatusCode::OK);
+
+    let form = [
+        ("current_password", "password123"),
+        ("password", "correct horse"),
+        ("confirmation", "correct horse"),
+        ("csrf", &csrf),
+    ];
+    assert_eq!(
+        change_password(&app, "alice@example.com:wrong", &form).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(change_password(&app, old, &form).await, StatusCode::OK);
+    assert_eq!(dashboard_as(&app, old).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(dashboard_as(&app, new).await, StatusCode::OK);
+
+    // The new password approves verifications too.
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "correct horse"),
+        ],
+    )
+    .await;
+    assert_eq!(redirect_params(&response)["decryptedString"], "challenge");
+
+    let set_password = |current_password: &str, password: &str| {
+        send_as(
+            &app,
+            Method::PUT,
+            "/api/feeds/alice-podcast/password",
+            new,
+            "application/json",
+            json!({ "currentPassword": current_password, "password": password }).to_string(),
+        )
+    };
+    assert_eq!(
+        set_password("correct horse", "short").await.status(),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        set_password("password123", "battery staple").await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        set_password("correct horse", "battery staple")
+            .await
+            .status(),
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        dashboard_as(&app, "alice@example.com:battery staple").await,
+        StatusCode::OK
+    );
+}
+
+/// The browser sends the owner's credentials with a form another site posts, but that site knows
+/// neither the current password nor the dashboard's token.
+#[tokio::test]
+async fn changing_a_password_takes_the_current_one_and_the_dashboards_token() {
+    let (app, _) = app_with_outbox(|_| {});
+    let credentials = "alice@example.com:password123";
+    let csrf = csrf_token(&app, "/feed/alice-podcast/dashboard", credentials).await;
+
+    let forms = [
+        [
+            ("current_password", "password12"),
+            ("password", "correct horse"),
+            ("confirmation", "correct horse"),
+            ("csrf", &csrf),
+        ],
+        [
+            ("current_password", "password123"),
+            ("password", "correct horse"),
+            ("confirmation", "correct horse"),
+            ("csrf", "forged"),
+        ],
+    ];
+    assert_eq!(
+        change_password(&app, credentials, &forms[0]).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        change_password(&app, credentials, &forms[1]).await,
+        StatusCode::FORBIDDEN
+    );
+    let form = [
+        ("current_password", "password123"),
+        ("password", "correct horse"),
+        ("confirmation", "correct horse"),
+    ];
+    assert_eq!(
+        change_password(&app, credentials, &form).await,
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+    assert_eq!(dashboard_as(&app, credentials).await, StatusCode::OK);
+
+    // Another podcast's token doesn't do either.
+    let bob_csrf = csrf_token(
+        &app,
+        "/feed/bob-podcast/dashboard",
+        "bob@example.com:password456",
+    )
+    .await;
+    let form = [
+        ("current_password", "password123"),
+        ("password", "correct horse"),
+        ("confirmation", "correct horse"),
+        ("csrf", &bob_csrf),
+    ];
+    assert_eq!(
+        change_password(&app, credentials, &form).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(dashboard_as(&app, credentials).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn forgotten_passwords_are_reset_with_a_one_time_link() {
+    let (app, outbox) = app_with_outbox(|config| config.login_lockout_threshold = 2);
+
+    // Unknown addresses get the same page, and no email.
+    let response = post_form(
+        &app,
+        "/password/forgot",
+        &[("email", "mallory@example.com")],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(outbox.sent().is_empty());
+
+    // Alice has locked herself out.
+    for _ in 0..2 {
+        dashboard_as(&app, "alice@example.com:password12").await;
+    }
+    assert_eq!(
+        dashboard_as(&app, "alice@example.com:password123").await,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    let response = post_form(&app, "/password/forgot", &[("email", "alice@example.com")]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let sent = outbox.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].0, "alice@example.com");
+    let link = link_in(&sent[0].1);
+    let token = link.rsplit_once("token=").unwrap().1.to_string();
+
+    // Opening the link, as a mail scanner might, doesn't use it up, and neither does a typo.
+    for _ in 0..2 {
+        let page = body_text(get(&app, &link).await).await;
+        assert!(page.contains(&format!("value=\"{token}\"")), "{page}");
+    }
+    assert_eq!(
+        reset(&app, &token, "correct horse", "correct hose").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        reset(&app, &token, "correct horse", "correct horse").await,
+        StatusCode::OK
+    );
+
+    assert_eq!(
+        dashboard_as(&app, "alice@example.com:correct horse").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        dashboard_as(&app, "alice@example.com:password123").await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    // The link works once.
+    assert_eq!(get(&app, &link).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        reset(&app, &token, "battery staple", "battery staple").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        get(&app, "/password/reset?token=forged").await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[tokio::test]
+async fn changing_a_password_revokes_outstanding_reset_links() {
+    let (app, outbox) = app_with_outbox(|_| {});
+    post_form(&app, "/password/forgot", &[("email", "alice@example.com")]).await;
+    let link = link_in(&outbox.sent()[0].1);
+
+    let credentials = "alice@example.com:password123";
+    let csrf = csrf_token(&app, "/feed/alice-podcast/dashboard", credentials).await;
+    let form = [
+        ("current_password", "password123"),
+        ("password", "correct horse"),
+        ("confirmation", "correct horse"),
+        ("csrf", &csrf),
+    ];
+    let status = change_password(&app, credentials, &form).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(get(&app, &link).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn reloading_the_config_file_keeps_changed_passwords() {
+    let path = temp_path("json");
+    let config = json!({"podcasts": [{
+        "slug": "dave-podcast",
+        "title": "Dave Talks",
+        "ownerEmail": "dave@example.com",
+        "ownerPassword": "password000",
+    }]});
+    std::fs::write(&path, config.to_string()).unwrap();
+    let state = state_with(keys(), hosting_company::demo_podcasts(), |config| {
+        config.config_path = Some(path.clone());
+    });
+    let app = router_for(state.clone());
+
+    let response = send_as(
+        &app,
+        Method::PUT,
+        "/api/feeds/dave-podcast/password",
+        "dave@example.com:password000",
+        "application/json",
+        json!({"currentPassword": "password000", "password": "correct horse"}).to_string(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    state.reload().await.unwrap();
+
+    let dashboard = |credentials: &'static str| {
+        request_as(
+            &app,
+            Method::GET,
+            "/feed/dave-podcast/dashboard",
+            credentials,
+        )
+    };
+    assert_eq!(
+        dashboard("dave@example.com:correct horse").await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        dashboard("dave@example.com:password000").await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
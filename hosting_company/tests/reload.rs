@@ -0,0 +1,167 @@
+//! Reloading podcasts and settings from the config file while verifications are under way.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::*;
+use hosting_company::tag::feed_attr_to_public_key;
+use rsa::traits::PublicKeyParts;
+use serde_json::json;
+
+const OWNER: [(&str, &str); 3] = [
+    ("decision", "approve"),
+    ("email", "dave@example.com"),
+    ("password", "password000"),
+];
+
+fn write_config(path: &str, config: serde_json::Value) {
+    std::fs::write(path, config.to_string()).unwrap();
+}
+
+fn dave(title: &str) -> serde_json::Value {
+    json!({
+        "slug": "dave-podcast",
+        "title": title,
+        "ownerEmail": "dave@example.com",
+        "ownerPassword": "password000",
+    })
+}
+
+#[tokio::test]
+async fn reloads_swap_in_podcasts_and_settings_without_dropping_verifications() {
+    let path = temp_path("json");
+    write_config(&path, json!({"podcasts": [dave("Dave Talks")]}));
+    let state = state_with(keys(), hosting_company::demo_podcasts(), |config| {
+        config.config_path = Some(path.clone());
+    });
+    let app = router_for(state.clone());
+    let feed = body_text(get(&app, "/feed/dave-podcast").await).await;
+    assert!(feed.contains("<title>Dave Talks</title>"), "{feed}");
+
+    // An owner opens the consent screen before the reload and approves after it.
+    let encrypted = encrypt_for(&app, "dave-podcast", "challenge").await;
+    let uri = verify_uri(
+        "dave-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    assert_eq!(get(&app, &uri).await.status(), StatusCode::OK);
+
+    write_config(
+        &path,
+        json!({
+            "publicBaseUrl": "https://podcasts.example/hosting",
+            "returnUrlAllowlist": ["app.example"],
+            "podcasts": [dave("Dave & Friends"), {
+                "slug": "erin-podcast",
+                "title": "Erin Talks",
+                "ownerEmail": "erin@example.com",
+                "ownerPassword": "password111",
+                "encryption": "RSA1_5",
+            }],
+        }),
+    );
+    state.reload().await.unwrap();
+
+    let params = redirect_params(&post_form(&app, &uri, &OWNER).await);
+    assert_eq!(params["decryptedString"], "challenge");
+
+    let feed = body_text(get(&app, "/feed/dave-podcast").await).await;
+    assert!(feed.contains("<title>Dave &amp; Friends</title>"), "{feed}");
+    assert_eq!(
+        verify_attribute(&feed, "verifyUrl"),
+        "https://podcasts.example/hosting/feed/dave-podcast/verify"
+    );
+    let feed = body_text(get(&app, "/feed/erin-podcast").await).await;
+    assert_eq!(verify_attribute(&feed, "encryption"), "RSA1_5");
+    // The demo's podcasts stay.
+    assert_eq!(
+        get(&app, "/feed/alice-podcast").await.status(),
+        StatusCode::OK
+    );
+
+    let other_app = verify_uri(
+        "dave-podcast",
+        &[
+            ("encryptedString", &encrypted),
+            ("returnUrl", "https://other.example/verified"),
+        ],
+    );
+    let response = get(&app, &other_app).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert!(response.headers()["location"]
+        .to_str()
+        .unwrap()
+        .contains("error=access_denied"));
+}
+
+#[tokio::test]
+async fn invalid_config_files_change_nothing() {
+    let path = temp_path("json");
+    write_config(&path, json!({"podcasts": [dave("Dave Talks")]}));
+    let state = state_with(keys(), hosting_company::demo_podcasts(), |config| {
+        config.config_path = Some(path.clone());
+    });
+    let app = router_for(state.clone());
+    let feed = body_text(get(&app, "/feed/dave-podcast").await).await;
+
+    for config in [
+        json!({"podcasts": [dave("Dave Talks"), dave("Dave again")]}),
+        json!({"podcasts": [dave("")]}),
+        json!({"podcasts": [{"slug": "Dave", "title": "Dave", "ownerEmail": "dave@example.com", "ownerPassword": "x"}]}),
+        json!({"podcasts": [dave("Dave Talks")], "publicBaseUrl": "not a url"}),
+        json!({"returnUrlAllowlist": ["other.example"], "unknownSetting": true}),
+    ] {
+        write_config(&path, config.clone());
+        assert!(state.reload().await.is_err(), "{config}");
+    }
+    std::fs::write(&path, "{").unwrap();
+    assert!(state.reload().await.is_err());
+
+    assert!(feed.contains("<title>Dave Talks</title>"), "{feed}");
+    assert_eq!(body_text(get(&app, "/feed/dave-podcast").await).await, feed);
+    let encrypted = encrypt_for(&app, "dave-podcast", "challenge").await;
+    let uri = verify_uri(
+        "dave-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    assert_eq!(get(&app, &uri).await.status(), StatusCode::OK);
+}
+
+/// Each podcast advertises a key of its own size and padding, which apps encrypt to as the feed
+/// says, and the host decrypts with.
+#[tokio::test]
+async fn podcasts_choose_their_key_size_and_padding() {
+    let path = temp_path("json");
+    let mut frank = dave("Frank Talks");
+    frank["slug"] = json!("frank-podcast");
+    frank["keyBits"] = json!(3072);
+    frank["encryption"] = json!("RSA1_5");
+    write_config(&path, json!({"podcasts": [frank]}));
+    let state = state_with(keys(), hosting_company::demo_podcasts(), |config| {
+        config.config_path = Some(path.clone());
+    });
+    state.load_keys().await;
+    let app = router_for(state.clone());
+
+    let feed = body_text(get(&app, "/feed/frank-podcast").await).await;
+    assert_eq!(verify_attribute(&feed, "encryption"), "RSA1_5");
+    let public_key = feed_attr_to_public_key(&verify_attribute(&feed, "publicKey")).unwrap();
+    assert_eq!(public_key.size() * 8, 3072);
+
+    let encrypted = encrypt_for(&app, "frank-podcast", "challenge").await;
+    let uri = verify_uri(
+        "frank-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    let params = redirect_params(&post_form(&app, &uri, &OWNER).await);
+    assert_eq!(params["decryptedString"], "challenge");
+
+    // Keys too small to be safe, or of sizes no other host uses, are refused.
+    for key_bits in [1024, 2047] {
+        let mut frank = dave("Frank Talks");
+        frank["slug"] = json!("frank-podcast");
+        frank["keyBits"] = json!(key_bits);
+        write_config(&path, json!({"podcasts": [frank]}));
+        assert!(state.reload().await.is_err(), "{key_bits}");
+    }
+}
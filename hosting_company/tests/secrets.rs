@@ -0,0 +1,32 @@
+//! The admin token can be read from a file, as container secrets are mounted, and never shows up
+//! in logs of the configuration.
+//!
+//! This is the only test in its binary, as it sets environment variables other tests read.
+
+mod common;
+
+use common::*;
+use hosting_company::config::Config;
+use secrecy::ExposeSecret;
+
+#[test]
+fn admin_tokens_are_read_from_files_and_redacted() {
+    let path = temp_path("token");
+    std::fs::write(&path, "hunter2\n").unwrap();
+    std::env::remove_var("ADMIN_TOKEN");
+    std::env::set_var("ADMIN_TOKEN_FILE", &path);
+
+    let config = Config::from_env();
+    let token = config.admin_token.as_ref().expect("token is loaded");
+    assert_eq!(token.expose_secret(), "hunter2");
+    assert!(!format!("{config:?}").contains("hunter2"));
+
+    // The variable itself takes precedence over the file.
+    std::env::set_var("ADMIN_TOKEN", "from-env");
+    let config = Config::from_env();
+    assert_eq!(config.admin_token.unwrap().expose_secret(), "from-env");
+
+    std::env::remove_var("ADMIN_TOKEN");
+    std::env::remove_var("ADMIN_TOKEN_FILE");
+    assert!(Config::from_env().admin_token.is_none());
+}
@@ -0,0 +1,220 @@
+//! The `<podcast:verify>` tag builder, as used by hosts that only emit the tag.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hosting_company::crypto::Padding;
+use hosting_company::tag::{
+    feed_attr_to_public_key, podcast_guid, public_key_fingerprint, public_key_to_feed_attr,
+    PublicKeyError, TagError, VerifyTag,
+};
+use proptest::prelude::*;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use std::sync::OnceLock;
+use url::Url;
+
+fn public_key() -> RsaPublicKey {
+    static KEY: OnceLock<RsaPublicKey> = OnceLock::new();
+    KEY.get_or_init(|| {
+        RsaPrivateKey::new(&mut rand::thread_rng(), 2048)
+            .unwrap()
+            .to_public_key()
+    })
+    .clone()
+}
+
+fn attribute<'a>(xml: &'a str, name: &str) -> &'a str {
+    let start = xml.find(&format!("{name}=\"")).unwrap() + name.len() + 2;
+    let end = start + xml[start..].find('"').unwrap();
+    &xml[start..end]
+}
+
+#[test]
+fn tags_round_trip_through_xml() {
+    let tag = VerifyTag::builder()
+        .verify_url(Url::parse("https://host.example/feed/show/verify?a=1&b=2").unwrap())
+        .public_key(&public_key())
+        .signing_key(&public_key())
+        .algorithm(Padding::OaepSha256)
+        .build()
+        .unwrap();
+    let xml = tag.to_xml();
+
+    assert!(xml.starts_with("<podcast:verify ") && xml.ends_with("/>"));
+    assert_eq!(
+        attribute(&xml, "verifyUrl"),
+        "https://host.example/feed/show/verify?a=1&amp;b=2"
+    );
+    assert_eq!(attribute(&xml, "encryption"), "RSA-OAEP-256");
+    let der = STANDARD.decode(attribute(&xml, "publicKey")).unwrap();
+    assert_eq!(
+        RsaPublicKey::from_public_key_der(&der).unwrap(),
+        public_key()
+    );
+    assert_eq!(attribute(&xml, "signingKey"), attribute(&xml, "publicKey"));
+}
+
+#[test]
+fn algorithm_defaults_to_pkcs1v15() {
+    let tag = VerifyTag::builder()
+        .verify_url(Url::parse("https://host.example/verify").unwrap())
+        .public_key(&public_key())
+        .build()
+        .unwrap();
+    assert_eq!(tag.algorithm(), Padding::Pkcs1v15);
+    assert_eq!(attribute(&tag.to_xml(), "encryption"), "RSA1_5");
+}
+
+#[test]
+fn verify_urls_must_be_secure() {
+    for (url, allowed) in [
+        ("https://host.example/verify", true),
+        ("http://localhost:8081/verify", true),
+        ("http://127.0.0.1/verify", true),
+        ("http://[::1]/verify", true),
+        ("http://host.example/verify", false),
+        ("ftp://host.example/verify", false),
+        ("mailto:owner@host.example", false),
+    ] {
+        let result = VerifyTag::builder()
+            .verify_url(Url::parse(url).unwrap())
+            .public_key(&public_key())
+            .build();
+        if allowed {
+            assert!(result.is_ok(), "{url}");
+        } else {
+            assert_eq!(result.unwrap_err(), TagError::InsecureVerifyUrl, "{url}");
+        }
+    }
+}
+
+#[test]
+fn incomplete_or_weak_tags_are_rejected() {
+    let verify_url = Url::parse("https://host.example/verify").unwrap();
+    assert_eq!(
+        VerifyTag::builder()
+            .public_key(&public_key())
+            .build()
+            .unwrap_err(),
+        TagError::MissingVerifyUrl
+    );
+    assert_eq!(
+        VerifyTag::builder()
+            .verify_url(verify_url.clone())
+            .build()
+            .unwrap_err(),
+        TagError::MissingPublicKey
+    );
+
+    let weak = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)
+        .unwrap()
+        .to_public_key();
+    assert_eq!(
+        VerifyTag::builder()
+            .verify_url(verify_url)
+            .public_key(&weak)
+            .build()
+            .unwrap_err(),
+        TagError::WeakPublicKey { bits: 1024 }
+    );
+}
+
+#[test]
+fn public_keys_round_trip_through_the_feed_attribute() {
+    let attr = public_key_to_feed_attr(&public_key()).unwrap();
+    assert_eq!(feed_attr_to_public_key(&attr).unwrap(), public_key());
+    assert!(!attr.contains(|c: char| c.is_whitespace() || c == '-'));
+}
+
+#[test]
+fn corrupt_attributes_are_described() {
+    let attr = public_key_to_feed_attr(&public_key()).unwrap();
+    assert!(matches!(
+        feed_attr_to_public_key(&attr.replacen('M', "!", 1)),
+        Err(PublicKeyError::Base64(_))
+    ));
+    assert!(matches!(
+        feed_attr_to_public_key(&format!("-----BEGIN PUBLIC KEY-----{attr}")),
+        Err(PublicKeyError::Base64(_))
+    ));
+    assert!(matches!(
+        feed_attr_to_public_key(&STANDARD.encode(b"not a key")),
+        Err(PublicKeyError::Spki(_))
+    ));
+    let error = feed_attr_to_public_key("abc").unwrap_err().to_string();
+    assert!(error.starts_with("publicKey isn't valid base64"), "{error}");
+}
+
+#[test]
+fn fingerprints_are_only_added_when_asked_for() {
+    let builder = || {
+        VerifyTag::builder()
+            .verify_url(Url::parse("https://host.example/verify").unwrap())
+            .public_key(&public_key())
+    };
+    let tag = builder().build().unwrap();
+    assert_eq!(tag.fingerprint(), None);
+    assert!(!tag.to_xml().contains("keyFingerprint"));
+
+    let tag = builder().fingerprint(true).build().unwrap();
+    let fingerprint = public_key_fingerprint(&public_key()).unwrap();
+    assert_eq!(tag.fingerprint(), Some(fingerprint.as_str()));
+    assert_eq!(attribute(&tag.to_xml(), "keyFingerprint"), fingerprint);
+    assert_eq!(fingerprint.len(), 8 * 3 - 1);
+    assert!(fingerprint
+        .split(':')
+        .all(|byte| byte.len() == 2 && byte.chars().all(|c| c.is_ascii_hexdigit())));
+}
+
+#[test]
+fn guids_follow_the_podcast_namespace() {
+    // The example from the specification of `<podcast:guid>`.
+    for feed_url in [
+        "https://podnews.net/rss",
+        "http://podnews.net/rss",
+        "https://podnews.net/rss/",
+    ] {
+        assert_eq!(
+            podcast_guid(&Url::parse(feed_url).unwrap()),
+            "9b024349-ccf0-5f69-a609-6b82873eab3c",
+            "{feed_url}"
+        );
+    }
+}
+
+proptest! {
+    /// Only a canonical encoding decodes, so whatever decodes re-encodes to the same attribute.
+    #[test]
+    fn decoded_attributes_re_encode_identically(attr in "[A-Za-z0-9+/=]{0,600}") {
+        if let Ok(public_key) = feed_attr_to_public_key(&attr) {
+            prop_assert_eq!(public_key_to_feed_attr(&public_key).unwrap(), attr);
+        }
+    }
+
+    #[test]
+    fn arbitrary_bytes_are_rejected_without_panicking(bytes in proptest::collection::vec(any::<u8>(), 0..600)) {
+        let attr = STANDARD.encode(&bytes);
+        prop_assert!(matches!(
+            feed_attr_to_public_key(&attr),
+            Err(PublicKeyError::Spki(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_attributes_are_rejected(len in 0usize..392) {
+        let attr = public_key_to_feed_attr(&public_key()).unwrap();
+        prop_assert!(feed_attr_to_public_key(&attr[..len.min(attr.len() - 1)]).is_err());
+    }
+
+    #[test]
+    fn altered_attributes_never_decode_to_the_same_key(index in 0usize..392, replacement in "[A-Za-z0-9+/]") {
+        let attr = public_key_to_feed_attr(&public_key()).unwrap();
+        let index = index % attr.len();
+        let mut altered = attr.clone();
+        altered.replace_range(index..index + 1, &replacement);
+        prop_assume!(altered != attr);
+        if let Ok(public_key) = feed_attr_to_public_key(&altered) {
+            prop_assert_ne!(public_key, self::public_key());
+        }
+    }
+}
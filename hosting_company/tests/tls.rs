@@ -0,0 +1,63 @@
+//! Serving over TLS: with a certificate and key configured, the server only speaks HTTPS. The
+//! certificate in `tests/tls` is for `localhost`, issued by the test CA in `ca.pem`.
+
+mod common;
+
+use axum_server::Handle;
+use common::*;
+use hosting_company::config::Config;
+use reqwest::{Certificate, Client};
+
+const TLS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/tls");
+
+/// Serves the demo over HTTPS with the test certificate for `localhost`, and returns its port.
+fn serve_tls() -> u16 {
+    let mut config = Config::from_env();
+    config.tls_cert_path = Some(format!("{TLS_DIR}/cert.pem"));
+    config.tls_key_path = Some(format!("{TLS_DIR}/key.pem"));
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(
+        async move { hosting_company::serve(listener, app(), &config, Handle::new()).await },
+    );
+    port
+}
+
+fn client() -> Client {
+    let ca = std::fs::read(format!("{TLS_DIR}/ca.pem")).unwrap();
+    Client::builder()
+        .add_root_certificate(Certificate::from_pem(&ca).unwrap())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn feeds_are_served_over_https() {
+    let port = serve_tls();
+    let response = client()
+        .get(format!("https://localhost:{port}/feed/alice-podcast"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await.unwrap().contains("<podcast:verify "));
+
+    // Plain HTTP on the same port gets no response at all.
+    let plain = client()
+        .get(format!("http://localhost:{port}/feed/alice-podcast"))
+        .send()
+        .await;
+    assert!(plain.is_err());
+}
+
+#[tokio::test]
+async fn certificates_need_their_keys() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut config = Config::from_env();
+    config.tls_cert_path = Some(format!("{TLS_DIR}/cert.pem"));
+    config.tls_key_path = None;
+    let error = hosting_company::serve(listener, app(), &config, Handle::new())
+        .await
+        .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,1233 @@
+//! Drives the verification protocol end to end against the router in memory, as an app and a
+//! podcast owner would.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, HeaderMap, Method, Request, StatusCode};
+use axum::{routing, Router};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use common::*;
+use hmac::{Hmac, Mac};
+use hosting_company::config::Config;
+use hosting_company::crypto::{self, Padding};
+use hosting_company::handshake::{encrypt_as, Encoding, HandshakeError, SignedVerification};
+use hosting_company::tag::{feed_attr_to_public_key, podcast_guid, public_key_fingerprint};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::Duration;
+use tower::ServiceExt;
+use url::Url;
+
+#[tokio::test]
+async fn approving_returns_the_decrypted_challenge() {
+    let app = app();
+    for (slug, email, password) in [
+        ("alice-podcast", "alice@example.com", "password123"),
+        ("bob-podcast", "bob@example.com", "password456"),
+    ] {
+        let encrypted = encrypt_for(&app, slug, "challenge-1234").await;
+        let uri = verify_uri(
+            slug,
+            &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+        );
+
+        let consent = get(&app, &uri).await;
+        assert_eq!(consent.status(), StatusCode::OK);
+        assert!(body_text(consent).await.contains("value=\"approve\""));
+
+        let response = post_form(
+            &app,
+            &uri,
+            &[
+                ("decision", "approve"),
+                ("email", email),
+                ("password", password),
+            ],
+        )
+        .await;
+        let params = redirect_params(&response);
+        assert_eq!(params["decryptedString"], "challenge-1234");
+        assert_eq!(params["session"], "1");
+        assert!(!params.contains_key("error"));
+    }
+}
+
+#[tokio::test]
+async fn approvals_are_signed_for_the_requesting_app() {
+    let app = app();
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    let params = redirect_params(&response);
+    let verification = SignedVerification::from_token(&params["signedVerification"]).unwrap();
+    assert_eq!(verification.challenge, "challenge");
+
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+    let signing_keys = [feed_attr_to_public_key(&verify_attribute(&feed, "signingKey")).unwrap()];
+    let return_url = Url::parse(RETURN_URL).unwrap();
+    let now = verification.issued_at;
+    assert_eq!(
+        verification.validate(&signing_keys, &return_url, now),
+        Ok(())
+    );
+    // The key challenges are encrypted to never signs.
+    let public_keys = [feed_attr_to_public_key(&verify_attribute(&feed, "publicKey")).unwrap()];
+    assert_eq!(
+        verification.validate(&public_keys, &return_url, now),
+        Err(HandshakeError::InvalidSignature)
+    );
+    assert_eq!(
+        verification.validate(
+            &signing_keys,
+            &Url::parse("https://other.example/verified").unwrap(),
+            now
+        ),
+        Err(HandshakeError::WrongAudience)
+    );
+    assert_eq!(
+        verification.validate(&signing_keys, &return_url, verification.expires_at),
+        Err(HandshakeError::Expired)
+    );
+}
+
+#[tokio::test]
+async fn podcasts_can_be_found_and_verified_by_guid() {
+    let app = app();
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+    let (_, guid) = feed.split_once("<podcast:guid>").unwrap();
+    let (guid, _) = guid.split_once("</podcast:guid>").unwrap();
+    let feed_url = Config::from_env()
+        .public_base_url
+        .join("feed/alice-podcast")
+        .unwrap();
+    assert_eq!(guid, podcast_guid(&feed_url));
+
+    let by_guid = get(&app, &format!("/feed/by-guid/{}", guid.to_uppercase())).await;
+    assert_eq!(by_guid.status(), StatusCode::OK);
+    assert_eq!(body_text(by_guid).await, feed);
+    assert_eq!(
+        get(&app, "/feed/by-guid/00000000-0000-0000-0000-000000000000")
+            .await
+            .status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    )
+    .replace("/feed/alice-podcast/", &format!("/feed/by-guid/{guid}/"));
+    assert_eq!(get(&app, &uri).await.status(), StatusCode::OK);
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    let params = redirect_params(&response);
+    let verification = SignedVerification::from_token(&params["signedVerification"]).unwrap();
+    assert_eq!(verification.feed.guid.as_deref(), Some(guid));
+
+    // The GUID decides when the app knows it, wherever the feed has moved to.
+    let moved = Url::parse("https://new-host.example/alice.xml").unwrap();
+    assert!(verification.matches_feed(Some(guid), &moved));
+    assert!(!verification.matches_feed(Some("another-guid"), &feed_url));
+    // Otherwise the feed URL does, regardless of scheme and trailing slashes.
+    let mut https = feed_url.clone();
+    https.set_scheme("https").unwrap();
+    assert!(verification.matches_feed(None, &https));
+    assert!(!verification.matches_feed(None, &moved));
+}
+
+/// Owners without their password can have a sign-in link emailed to them, which approves the
+/// request once.
+#[tokio::test]
+async fn emailed_sign_in_links_approve_once() {
+    let (app, outbox) = app_with_outbox(|_| {});
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[
+            ("encryptedString", &encrypted),
+            ("returnUrl", RETURN_URL),
+            ("state", "xyz"),
+        ],
+    );
+
+    for email in ["bob@example.com", "alice@example.com"] {
+        let form = [("decision", "email"), ("email", email)];
+        let response = post_form(&app, &uri, &form).await;
+        assert_eq!(response.status(), StatusCode::OK, "{email}");
+    }
+    let sent = outbox.sent();
+    assert_eq!(sent.len(), 1, "only the owner gets a link");
+    let (to, body) = &sent[0];
+    assert_eq!(to, "alice@example.com");
+    let start = body
+        .find("/feed/alice-podcast/verify/magic?token=")
+        .unwrap();
+    let link = body[start..].split_whitespace().next().unwrap();
+
+    let params = redirect_params(&get(&app, link).await);
+    assert_eq!(params["decryptedString"], "challenge");
+    assert_eq!(params["state"], "xyz");
+    assert_eq!(get(&app, link).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn wrong_credentials_show_the_consent_screen_again() {
+    let app = app();
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+
+    for (email, password) in [
+        ("alice@example.com", "wrong"),
+        ("bob@example.com", "password456"),
+        ("", ""),
+    ] {
+        let response = post_form(
+            &app,
+            &uri,
+            &[
+                ("decision", "approve"),
+                ("email", email),
+                ("password", password),
+            ],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(!response.headers().contains_key(header::LOCATION));
+        assert!(body_text(response).await.contains("value=\"approve\""));
+    }
+}
+
+#[tokio::test]
+async fn denying_redirects_with_access_denied() {
+    let app = app();
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+
+    let response = post_form(&app, &uri, &[("decision", "deny")]).await;
+    let params = redirect_params(&response);
+    assert_eq!(params["error"], "access_denied");
+    assert_eq!(params["error_description"], "access_denied");
+    assert!(!params.contains_key("decryptedString"));
+}
+
+/// With a redirect delay, an owner who approved sees a confirmation that sends them back to the
+/// app without script, and a return URL can't inject markup into it.
+#[tokio::test]
+async fn approving_can_show_a_confirmation_before_redirecting() {
+    let app = app_with(|config| config.redirect_delay_secs = 3);
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[
+            ("encryptedString", &encrypted),
+            (
+                "returnUrl",
+                "https://app.example/verified?x=\"><script>alert(1)</script>",
+            ),
+        ],
+    );
+
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key(header::LOCATION));
+
+    let page = body_text(response).await;
+    assert!(!page.contains("<script>"));
+    let (delay_secs, return_url) = meta_refresh(&page);
+    assert_eq!(delay_secs, 3);
+    assert_eq!(return_url.host_str(), Some("app.example"));
+    let params: HashMap<_, _> = return_url.query_pairs().into_owned().collect();
+    assert_eq!(params["x"], "\"><script>alert(1)</script>");
+    assert_eq!(params["decryptedString"], "challenge");
+}
+
+/// Error pages send the user back to the app with OAuth-style `error` and `error_description`
+/// parameters, so that the app learns why without parsing the page.
+#[tokio::test]
+async fn failures_send_the_app_an_error_code() {
+    let app = app();
+    let uri = verify_uri(
+        "alice-podcast",
+        &[
+            ("encryptedString", "bm90IGVuY3J5cHRlZA=="),
+            ("returnUrl", RETURN_URL),
+            ("state", "xyz"),
+        ],
+    );
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let (delay_secs, return_url) = meta_refresh(&body_text(response).await);
+    assert_eq!(delay_secs, 10);
+    let params: HashMap<_, _> = return_url.query_pairs().into_owned().collect();
+    assert_eq!(params["session"], "1");
+    assert_eq!(params["state"], "xyz");
+    assert_eq!(params["error"], "invalid_request");
+    assert_eq!(params["error_description"], "invalid_encrypted_string");
+}
+
+/// Errors are rendered as a page saying what went wrong, which only redirects once the app to
+/// send the user back to is known, after the configured delay.
+#[tokio::test]
+async fn errors_are_rendered_as_pages() {
+    let app = app_with(|config| config.error_redirect_delay_secs = 3);
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+
+    let uri = verify_uri(
+        "nobody-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    let response = get(&app, &uri).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let page = body_text(response).await;
+    assert!(page.contains("<code>nobody-podcast</code>"), "{page}");
+
+    let uri = verify_uri("alice-podcast", &[("encryptedString", &encrypted)]);
+    let response = get(&app, &uri).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let page = body_text(response).await;
+    assert!(page.contains("<code>returnUrl</code>"), "{page}");
+    assert!(!page.contains("http-equiv=\"refresh\""));
+
+    let uri = verify_uri("alice-podcast", &[("returnUrl", RETURN_URL)]);
+    let response = get(&app, &uri).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let (delay_secs, return_url) = meta_refresh(&body_text(response).await);
+    assert_eq!(delay_secs, 3);
+    assert_eq!(return_url.host_str(), Some("app.example"));
+}
+
+#[tokio::test]
+async fn state_is_returned_unchanged() {
+    let app = app();
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let state = "a b&c=d/é?+%20";
+    let uri = verify_uri(
+        "alice-podcast",
+        &[
+            ("encryptedString", &encrypted),
+            ("returnUrl", RETURN_URL),
+            ("state", state),
+        ],
+    );
+
+    let approved = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    assert_eq!(redirect_params(&approved)["state"], state);
+
+    let denied = post_form(&app, &uri, &[("decision", "deny")]).await;
+    assert_eq!(redirect_params(&denied)["state"], state);
+}
+
+#[test]
+fn encrypted_strings_decode_from_any_base64_variant() {
+    let bytes = vec![0xfb, 0xff, 0xfe, 0x01];
+    for encoded in [
+        "+//+AQ==",
+        "+//+AQ",
+        "-__-AQ==",
+        "-__-AQ",
+        "+//+AQ%3D%3D",
+        " //+AQ==",
+        "+//+AQ==\n",
+    ] {
+        assert_eq!(
+            crypto::decode_encrypted_string(encoded),
+            Some(bytes.clone()),
+            "{encoded:?}"
+        );
+    }
+    for encoded in ["-_/+AQ", "+//+A", "+//+AQ!", ""] {
+        assert_ne!(
+            crypto::decode_encrypted_string(encoded),
+            Some(bytes.clone()),
+            "{encoded:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn approvals_accept_any_base64_variant() {
+    let app = app();
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+    let public_key = feed_attr_to_public_key(&verify_attribute(&feed, "publicKey")).unwrap();
+    // A ciphertext whose standard encoding has both characters that differ in base64url.
+    let standard = loop {
+        let encrypted = encrypt_as(
+            &public_key,
+            Padding::OaepSha256,
+            "challenge",
+            Encoding::Standard,
+        )
+        .unwrap();
+        if encrypted.contains('+') && encrypted.contains('/') && encrypted.ends_with('=') {
+            break encrypted;
+        }
+    };
+    let url_safe = standard.replace('+', "-").replace('/', "_");
+    let encode =
+        |value: &str| url::form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>();
+
+    for raw in [
+        encode(&standard),
+        encode(standard.trim_end_matches('=')),
+        encode(&url_safe),
+        encode(url_safe.trim_end_matches('=')),
+        // Padding percent-encoded twice, so that it arrives as `%3D`.
+        encode(&standard.replace('=', "%3D")),
+        // Not percent-encoded at all, so that `+` arrives as a space.
+        standard.clone(),
+    ] {
+        let uri = format!(
+            "/feed/alice-podcast/verify?returnUrl={}&encryptedString={raw}",
+            encode(RETURN_URL)
+        );
+        let response = post_form(
+            &app,
+            &uri,
+            &[
+                ("decision", "approve"),
+                ("email", "alice@example.com"),
+                ("password", "password123"),
+            ],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER, "{raw}");
+        assert_eq!(redirect_params(&response)["decryptedString"], "challenge");
+    }
+}
+
+#[tokio::test]
+async fn long_challenges_can_be_sent_in_an_envelope() {
+    let app = app();
+    let response = get(&app, "/feed/alice-podcast").await;
+    let feed = body_text(response).await;
+    let public_key = feed_attr_to_public_key(&verify_attribute(&feed, "publicKey")).unwrap();
+
+    let challenge = "x".repeat(1000);
+    let envelope = crypto::seal_envelope(&public_key, challenge.as_bytes())
+        .ok()
+        .unwrap();
+    let uri = verify_uri(
+        "alice-podcast",
+        &[
+            ("encryptedString", &STANDARD.encode(&envelope)),
+            ("returnUrl", RETURN_URL),
+        ],
+    );
+    let form = [
+        ("decision", "approve"),
+        ("email", "alice@example.com"),
+        ("password", "password123"),
+    ];
+
+    let response = post_form(&app, &uri, &form).await;
+    assert_eq!(redirect_params(&response)["decryptedString"], challenge);
+
+    for len in [envelope.len() - 1, envelope.len() - 16, 256 + 12] {
+        let uri = verify_uri(
+            "alice-podcast",
+            &[
+                ("encryptedString", &STANDARD.encode(&envelope[..len])),
+                ("returnUrl", RETURN_URL),
+            ],
+        );
+        let response = post_form(&app, &uri, &form).await;
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "truncated to {len}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn invalid_requests_are_rejected() {
+    let app = app();
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+
+    let cases = [
+        (
+            verify_uri("alice-podcast", &[("encryptedString", &encrypted)]),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            verify_uri(
+                "alice-podcast",
+                &[("encryptedString", &encrypted), ("returnUrl", "/relative")],
+            ),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            verify_uri("alice-podcast", &[("returnUrl", RETURN_URL)]),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            verify_uri(
+                "nobody-podcast",
+                &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+            ),
+            StatusCode::NOT_FOUND,
+        ),
+    ];
+    for (uri, status) in cases {
+        assert_eq!(get(&app, &uri).await.status(), status, "GET {uri}");
+    }
+
+    let uri = verify_uri(
+        "alice-podcast",
+        &[
+            ("encryptedString", "bm90IGVuY3J5cHRlZA=="),
+            ("returnUrl", RETURN_URL),
+        ],
+    );
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Behind a reverse proxy, the feed advertises the public URL rather than the one it listens on,
+/// including the path the proxy serves it under.
+#[tokio::test]
+async fn feed_urls_use_the_public_base_url() {
+    let app = app_with(|config| {
+        config.public_base_url = Url::parse("https://podcasts.example/hosting/").unwrap();
+    });
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+    assert_eq!(
+        verify_attribute(&feed, "verifyUrl"),
+        "https://podcasts.example/hosting/feed/alice-podcast/verify"
+    );
+    assert!(!feed.contains("localhost"));
+}
+
+#[tokio::test]
+async fn feed_urls_follow_trusted_forwarded_headers() {
+    let app = app_with(|config| config.trust_forwarded_headers = true);
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/feed/alice-podcast")
+                .header("X-Forwarded-Proto", "https")
+                .header("X-Forwarded-Host", "podcasts.example")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let feed = body_text(response).await;
+    assert_eq!(
+        verify_attribute(&feed, "verifyUrl"),
+        "https://podcasts.example/feed/alice-podcast/verify"
+    );
+
+    let untrusted = app_with(|config| config.trust_forwarded_headers = false);
+    let response = untrusted
+        .oneshot(
+            Request::get("/feed/alice-podcast")
+                .header("X-Forwarded-Host", "podcasts.example")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let feed = body_text(response).await;
+    assert!(!verify_attribute(&feed, "verifyUrl").contains("podcasts.example"));
+}
+
+/// Callbacks are signed with the secret issued when the app's domain was registered, which the
+/// app checks them with.
+#[tokio::test]
+async fn callbacks_are_signed_with_the_domains_secret() {
+    let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let app_url = serve(Router::new().route(
+        "/callback",
+        routing::post(move |headers: HeaderMap, body: String| {
+            let sender = sender.clone();
+            async move {
+                sender.send((headers, body)).unwrap();
+                StatusCode::NO_CONTENT
+            }
+        }),
+    ));
+    let app = app_with(|config| {
+        config.admin_token = Some(secrecy::SecretString::new(String::from("admin")));
+        // Allows callbacks to the test app, which is served on a loopback address.
+        config.dev_mode = true;
+    });
+    let secret = register_callbacks(&app, &format!("127.0.0.1:{}", app_url.port().unwrap())).await;
+
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[
+            ("encryptedString", &encrypted),
+            ("returnUrl", app_url.join("verified").unwrap().as_str()),
+            ("callbackUrl", app_url.join("callback").unwrap().as_str()),
+        ],
+    );
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+    let (headers, body) = tokio::time::timeout(Duration::from_secs(5), received.recv())
+        .await
+        .expect("callback delivered")
+        .unwrap();
+    assert_eq!(headers["x-verify-event"], "verification.approved");
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(headers["x-verify-timestamp"].as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    assert_eq!(headers["x-verify-signature"], format!("sha256={signature}"));
+    let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(payload["decryptedString"], "challenge");
+}
+
+#[test]
+fn secrets_are_redacted_from_debug_output() {
+    let mut config = Config::from_env();
+    config.admin_token = Some(secrecy::SecretString::new(String::from("hunter2-admin")));
+    let debug = format!("{config:?}");
+    assert!(!debug.contains("hunter2"));
+}
+
+#[tokio::test]
+async fn owners_can_review_verification_attempts() {
+    let attempts_path = temp_path("jsonl");
+    let app = app_with(|config| config.attempts_path = attempts_path.clone());
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    get(&app, &uri).await;
+    for password in ["wrong", "password123"] {
+        post_form(
+            &app,
+            &uri,
+            &[
+                ("decision", "approve"),
+                ("email", "alice@example.com"),
+                ("password", password),
+            ],
+        )
+        .await;
+    }
+    get(
+        &app,
+        &verify_uri("bob-podcast", &[("returnUrl", RETURN_URL)]),
+    )
+    .await;
+
+    let uri = "/api/feeds/alice-podcast/attempts";
+    for credentials in ["alice@example.com:wrong", "bob@example.com:password456"] {
+        let response = request_as(&app, Method::GET, uri, credentials).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{credentials}");
+    }
+
+    // The log is persisted, so a restarted server still has it.
+    let restarted = app_with(|config| config.attempts_path = attempts_path.clone());
+    let response = request_as(
+        &restarted,
+        Method::GET,
+        uri,
+        "alice@example.com:password123",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let attempts: Vec<serde_json::Value> =
+        serde_json::from_str(&body_text(response).await).unwrap();
+    let outcomes: Vec<_> = attempts
+        .iter()
+        .map(|attempt| attempt["outcome"].as_str().unwrap())
+        .collect();
+    assert_eq!(outcomes, ["approved", "invalid_credentials", "requested"]);
+    for attempt in &attempts {
+        assert_eq!(attempt["slug"], "alice-podcast");
+        assert_eq!(attempt["domain"], "app.example");
+        assert_eq!(attempt["client_ip"], "127.0.0.1");
+    }
+}
+
+#[tokio::test]
+async fn owners_can_revoke_grants() {
+    let app = app();
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    let grant_id = redirect_params(&response)["grantId"].clone();
+
+    let status = |app: Router, grant_id: String| async move {
+        let response = get(&app, &format!("/api/grants/{grant_id}")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        serde_json::from_str::<serde_json::Value>(&body_text(response).await).unwrap()
+    };
+    let active = status(app.clone(), grant_id.clone()).await;
+    assert_eq!(active["status"], "active");
+    assert!(active["feedUrl"]
+        .as_str()
+        .unwrap()
+        .ends_with("/feed/alice-podcast"));
+
+    let revoke_uri = format!("/api/feeds/alice-podcast/grants/{grant_id}");
+    let response = request_as(
+        &app,
+        Method::DELETE,
+        &revoke_uri,
+        "bob@example.com:password456",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = request_as(
+        &app,
+        Method::DELETE,
+        &revoke_uri,
+        "alice@example.com:password123",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = request_as(
+        &app,
+        Method::DELETE,
+        &revoke_uri,
+        "alice@example.com:password123",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let revoked = status(app.clone(), grant_id).await;
+    assert_eq!(revoked["status"], "revoked");
+    assert!(revoked["revokedAt"].is_u64());
+
+    let response = get(&app, "/api/grants/unknown").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// The dashboard's revoke buttons take its token, so that another site can't post them with the
+/// credentials the browser keeps sending.
+#[tokio::test]
+async fn grants_are_revoked_from_the_dashboard_with_its_token() {
+    let app = app();
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    let response = post_form(
+        &app,
+        &uri,
+        &[
+            ("decision", "approve"),
+            ("email", "alice@example.com"),
+            ("password", "password123"),
+        ],
+    )
+    .await;
+    let grant_id = redirect_params(&response)["grantId"].clone();
+
+    let credentials = "alice@example.com:password123";
+    let csrf = csrf_token(&app, "/feed/alice-podcast/dashboard", credentials).await;
+    let revoke_uri = format!("/feed/alice-podcast/grants/{grant_id}/revoke");
+    async fn status(app: &Router, grant_id: &str) -> serde_json::Value {
+        let response = get(app, &format!("/api/grants/{grant_id}")).await;
+        serde_json::from_str::<serde_json::Value>(&body_text(response).await).unwrap()["status"]
+            .clone()
+    }
+
+    let response = post_form_as(&app, &revoke_uri, credentials, &[("csrf", "forged")]).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(status(&app, &grant_id).await, "active");
+
+    let response = post_form_as(&app, &revoke_uri, credentials, &[("csrf", &csrf)]).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "/feed/alice-podcast/dashboard"
+    );
+    assert_eq!(status(&app, &grant_id).await, "revoked");
+}
+
+#[tokio::test]
+async fn active_grants_are_listed_per_feed() {
+    let app = app();
+    let approve = |app: Router| async move {
+        let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+        let uri = verify_uri(
+            "alice-podcast",
+            &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+        );
+        let response = post_form(
+            &app,
+            &uri,
+            &[
+                ("decision", "approve"),
+                ("email", "alice@example.com"),
+                ("password", "password123"),
+            ],
+        )
+        .await;
+        redirect_params(&response)["grantId"].clone()
+    };
+    let kept = approve(app.clone()).await;
+    let revoked = approve(app.clone()).await;
+    let response = request_as(
+        &app,
+        Method::DELETE,
+        &format!("/api/feeds/alice-podcast/grants/{revoked}"),
+        "alice@example.com:password123",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let verifications = |app: Router, uri: &'static str| async move {
+        let response = get(&app, uri).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        serde_json::from_str::<Vec<serde_json::Value>>(&body_text(response).await).unwrap()
+    };
+    let listed = verifications(app.clone(), "/feed/alice-podcast/verifications").await;
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["grantId"], kept.as_str());
+    assert_eq!(listed[0]["domain"], "app.example");
+    assert!(listed[0]["verifiedAt"].is_u64());
+
+    let other = verifications(
+        app.clone(),
+        "/feed/alice-podcast/verifications?domain=other.example",
+    )
+    .await;
+    assert!(other.is_empty());
+    assert!(
+        verifications(app.clone(), "/feed/bob-podcast/verifications")
+            .await
+            .is_empty()
+    );
+
+    let response = get(&app, "/feed/nobody-podcast/verifications").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn challenges_encrypted_before_a_key_rotation_still_decrypt() {
+    let keys = hosting_company::generate_keys(&hosting_company::demo_podcasts());
+    let app = app_with_keys(keys, |config| {
+        config.admin_token = Some(secrecy::SecretString::new(String::from("admin")));
+    });
+    let rotate = || async {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/api/admin/keys/rotate")
+                    .header(header::AUTHORIZATION, "Bearer admin")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    };
+    let approve = |encrypted: String| {
+        let app = app.clone();
+        async move {
+            let uri = verify_uri(
+                "alice-podcast",
+                &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+            );
+            post_form(
+                &app,
+                &uri,
+                &[
+                    ("decision", "approve"),
+                    ("email", "alice@example.com"),
+                    ("password", "password123"),
+                ],
+            )
+            .await
+        }
+    };
+
+    let before = encrypt_for(&app, "alice-podcast", "before").await;
+    rotate().await;
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+    assert_eq!(feed.matches("<podcast:verify ").count(), 2);
+
+    let after = encrypt_for(&app, "alice-podcast", "after").await;
+    for (encrypted, challenge) in [(&before, "before"), (&after, "after")] {
+        let response = approve(encrypted.clone()).await;
+        assert_eq!(redirect_params(&response)["decryptedString"], challenge);
+    }
+
+    // After a second rotation, the first key is no longer advertised or accepted.
+    rotate().await;
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+    assert_eq!(feed.matches("<podcast:verify ").count(), 2);
+    assert_eq!(approve(before).await.status(), StatusCode::BAD_REQUEST);
+    let response = approve(after).await;
+    assert_eq!(redirect_params(&response)["decryptedString"], "after");
+}
+
+#[tokio::test]
+async fn slugs_are_normalized_and_renames_redirect() {
+    let app = app_with(|config| {
+        config.admin_token = Some(secrecy::SecretString::new(String::from("admin")));
+    });
+    let location = |response: &axum::response::Response| {
+        response.headers()[header::LOCATION]
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+
+    let response = get(&app, "/feed/Alice-Podcast").await;
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(location(&response), "/feed/alice-podcast");
+    let response = get(&app, "/feed/%61lice%2Dpodcast/verify?returnUrl=x").await;
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(
+        location(&response),
+        "/feed/alice-podcast/verify?returnUrl=x"
+    );
+
+    let rename = app
+        .clone()
+        .oneshot(
+            Request::put("/api/admin/slugs/Alices-Show?to=alice-podcast")
+                .header(header::AUTHORIZATION, "Bearer admin")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(rename.status(), StatusCode::CREATED);
+
+    let response = get(&app, "/feed/alices-show").await;
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(location(&response), "/feed/alice-podcast");
+    let response = post_form(&app, "/feed/ALICES-SHOW/verify", &[]).await;
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(location(&response), "/feed/alice-podcast/verify");
+
+    assert_eq!(
+        get(&app, "/feed/alice-podcast").await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        get(&app, "/feed/nobody").await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn drafts_are_only_shown_to_their_owner_until_published() {
+    let app = app();
+    let owner = "carol@example.com:password789";
+
+    assert!(!body_text(get(&app, "/").await).await.contains("Carol"));
+    let response = get(&app, "/feed/carol-podcast").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = request_as(&app, Method::GET, "/feed/carol-podcast", owner).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = request_as(
+        &app,
+        Method::GET,
+        "/feed/carol-podcast",
+        "alice@example.com:password123",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let verify = verify_uri(
+        "carol-podcast",
+        &[("encryptedString", "AAAA"), ("returnUrl", RETURN_URL)],
+    );
+    assert_eq!(get(&app, &verify).await.status(), StatusCode::NOT_FOUND);
+
+    // A body another site could send from the owner's browser isn't enough.
+    let response = request_as(
+        &app,
+        Method::POST,
+        "/api/feeds/carol-podcast/publish",
+        owner,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(
+        get(&app, "/feed/carol-podcast").await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let request = Request::post("/api/feeds/carol-podcast/publish")
+        .header(
+            header::AUTHORIZATION,
+            format!("Basic {}", STANDARD.encode(owner)),
+        )
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("{}"))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(body_text(get(&app, "/").await).await.contains("Carol"));
+    assert_eq!(
+        get(&app, "/feed/carol-podcast").await.status(),
+        StatusCode::OK
+    );
+    let encrypted = encrypt_for(&app, "carol-podcast", "challenge").await;
+    let verify = verify_uri(
+        "carol-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    assert_eq!(get(&app, &verify).await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn drafts_are_published_from_the_dashboard_with_its_token() {
+    let app = app();
+    let owner = "carol@example.com:password789";
+    let csrf = csrf_token(&app, "/feed/carol-podcast/dashboard", owner).await;
+
+    for form in [vec![], vec![("csrf", "forged")]] {
+        let response = post_form_as(&app, "/feed/carol-podcast/publish", owner, &form).await;
+        assert!(response.status().is_client_error(), "{form:?}");
+    }
+    let response = get(&app, "/feed/carol-podcast").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let form = [("csrf", csrf.as_str())];
+    let response = post_form_as(&app, "/feed/carol-podcast/publish", owner, &form).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(
+        get(&app, "/feed/carol-podcast").await.status(),
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn key_fingerprints_match_the_feed() {
+    let app = app_with(|config| config.feed_key_fingerprints = true);
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+    let public_key = feed_attr_to_public_key(&verify_attribute(&feed, "publicKey")).unwrap();
+    let fingerprint = public_key_fingerprint(&public_key).unwrap();
+    assert_eq!(verify_attribute(&feed, "keyFingerprint"), fingerprint);
+
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = verify_uri(
+        "alice-podcast",
+        &[("encryptedString", &encrypted), ("returnUrl", RETURN_URL)],
+    );
+    let page = body_text(get(&app, &uri).await).await;
+    assert!(page.contains(&format!("<code>{fingerprint}</code>")));
+
+    let feed = body_text(get(&common::app(), "/feed/alice-podcast").await).await;
+    assert!(!feed.contains("keyFingerprint"));
+}
+
+#[tokio::test]
+async fn locked_feeds_cannot_be_transferred() {
+    let app = app();
+    let set_locked = |app: Router, locked: bool| async move {
+        let request = Request::put("/api/feeds/alice-podcast/lock")
+            .header(
+                header::AUTHORIZATION,
+                format!("Basic {}", STANDARD.encode("alice@example.com:password123")),
+            )
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(format!("{{\"locked\": {locked}}}")))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    };
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+    assert!(feed.contains("<podcast:locked owner=\"alice@example.com\">no</podcast:locked>"));
+
+    set_locked(app.clone(), true).await;
+    let feed = body_text(get(&app, "/feed/alice-podcast").await).await;
+    assert!(feed.contains("<podcast:locked owner=\"alice@example.com\">yes</podcast:locked>"));
+
+    let encrypted = encrypt_for(&app, "alice-podcast", "challenge").await;
+    let uri = |purpose: &str| {
+        verify_uri(
+            "alice-podcast",
+            &[
+                ("encryptedString", &encrypted),
+                ("returnUrl", RETURN_URL),
+                ("purpose", purpose),
+            ],
+        )
+    };
+    let approval = [
+        ("decision", "approve"),
+        ("email", "alice@example.com"),
+        ("password", "password123"),
+    ];
+
+    let page = body_text(get(&app, &uri("transfer")).await).await;
+    assert!(page.contains("locked it against transfers"));
+    let response = post_form(&app, &uri("transfer"), &approval).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(!body_text(response).await.contains("decryptedString"));
+
+    // Verifying ownership is still allowed.
+    let page = body_text(get(&app, &uri("verify")).await).await;
+    assert!(!page.contains("locked it against transfers"));
+    let response = post_form(&app, &uri("verify"), &approval).await;
+    assert_eq!(redirect_params(&response)["decryptedString"], "challenge");
+
+    assert_eq!(
+        get(&app, &uri("takeover")).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    set_locked(app.clone(), false).await;
+    let response = post_form(&app, &uri("transfer"), &approval).await;
+    assert_eq!(redirect_params(&response)["decryptedString"], "challenge");
+}
+
+#[tokio::test]
+async fn feeds_are_locked_from_the_dashboard_with_its_token() {
+    let app = app();
+    let owner = "alice@example.com:password123";
+    let csrf = csrf_token(&app, "/feed/alice-podcast/dashboard", owner).await;
+    let locked = |app: Router| async move {
+        body_text(get(&app, "/feed/alice-podcast").await)
+            .await
+            .contains("<podcast:locked owner=\"alice@example.com\">yes</podcast:locked>")
+    };
+
+    for form in [
+        vec![("locked", "true")],
+        vec![("locked", "true"), ("csrf", "forged")],
+    ] {
+        let response = post_form_as(&app, "/feed/alice-podcast/lock", owner, &form).await;
+        assert!(response.status().is_client_error(), "{form:?}");
+    }
+    assert!(!locked(app.clone()).await);
+
+    let form = [("locked", "true"), ("csrf", &csrf)];
+    let response = post_form_as(&app, "/feed/alice-podcast/lock", owner, &form).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert!(locked(app.clone()).await);
+}
+
+#[tokio::test]
+async fn api_clients_get_json_errors() {
+    let app = app();
+    let uri = verify_uri("alice-podcast", &[("returnUrl", RETURN_URL)]);
+    let request = |accept: &str| {
+        Request::get(&uri)
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(request("application/json"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(body["error"], "missing_encrypted_string");
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .contains("encryptedString"));
+    assert!(!body["message"].as_str().unwrap().contains("<code>"));
+
+    let browser = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+    let response = app.clone().oneshot(request(browser)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(response.headers()[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+}